@@ -0,0 +1,3 @@
+ALTER TABLE newsletter_issues
+    ALTER COLUMN published_at TYPE timestamptz
+        USING published_at::timestamptz;
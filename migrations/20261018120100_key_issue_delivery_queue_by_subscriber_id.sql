@@ -0,0 +1,18 @@
+ALTER TABLE issue_delivery_queue
+    ADD COLUMN subscriber_id uuid NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE;
+
+UPDATE issue_delivery_queue
+SET subscriber_id = subscriptions.id
+FROM subscriptions
+WHERE subscriptions.email = issue_delivery_queue.subscriber_email;
+
+-- Tasks pointing at an email we no longer know about can never be delivered.
+DELETE FROM issue_delivery_queue
+WHERE subscriber_id IS NULL;
+
+ALTER TABLE issue_delivery_queue
+    DROP CONSTRAINT issue_delivery_queue_pkey,
+    DROP COLUMN subscriber_email,
+    ALTER COLUMN subscriber_id SET NOT NULL,
+    ADD PRIMARY KEY (newsletter_issue_id, subscriber_id);
@@ -0,0 +1,34 @@
+CREATE TABLE lists
+(
+    list_id    uuid        NOT NULL,
+    PRIMARY KEY (list_id),
+    name       TEXT        NOT NULL UNIQUE,
+    created_at timestamptz NOT NULL
+);
+
+-- Everything published so far went out to a single, implicit list.
+INSERT INTO lists (list_id, name, created_at)
+VALUES ('8d1f6c9e-3b5a-4f0e-9c2d-7a4b1e6f3c58', 'Newsletter', now());
+
+CREATE TABLE list_subscriptions
+(
+    list_id       uuid        NOT NULL
+        REFERENCES lists (list_id) ON DELETE CASCADE,
+    subscriber_id uuid        NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    subscribed_at timestamptz NOT NULL,
+    PRIMARY KEY (list_id, subscriber_id)
+);
+
+INSERT INTO list_subscriptions (list_id, subscriber_id, subscribed_at)
+SELECT '8d1f6c9e-3b5a-4f0e-9c2d-7a4b1e6f3c58', id, subscribed_at
+FROM subscriptions;
+
+ALTER TABLE newsletter_issues
+    ADD COLUMN list_id uuid NULL REFERENCES lists (list_id);
+
+UPDATE newsletter_issues
+SET list_id = '8d1f6c9e-3b5a-4f0e-9c2d-7a4b1e6f3c58';
+
+ALTER TABLE newsletter_issues
+    ALTER COLUMN list_id SET NOT NULL;
@@ -0,0 +1,14 @@
+ALTER TABLE subscriptions
+    ADD COLUMN plain_text_only BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE preference_tokens
+(
+    preference_token TEXT NOT NULL,
+    subscriber_id    uuid NOT NULL UNIQUE
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    PRIMARY KEY (preference_token)
+);
+
+INSERT INTO preference_tokens (preference_token, subscriber_id)
+SELECT replace(gen_random_uuid()::text, '-', ''), id
+FROM subscriptions;
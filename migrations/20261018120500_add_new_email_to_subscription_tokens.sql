@@ -0,0 +1,3 @@
+-- Tokens carrying a new address confirm an email change rather than a subscription.
+ALTER TABLE subscription_tokens
+    ADD COLUMN new_email TEXT NULL;
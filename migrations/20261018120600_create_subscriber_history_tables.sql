@@ -0,0 +1,31 @@
+CREATE TABLE subscription_status_changes
+(
+    subscriber_id uuid                NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    status        subscription_status NOT NULL,
+    changed_at    timestamptz         NOT NULL
+);
+CREATE INDEX subscription_status_changes_subscriber_id_idx
+    ON subscription_status_changes (subscriber_id);
+
+-- All we know about existing subscribers is when they signed up.
+INSERT INTO subscription_status_changes (subscriber_id, status, changed_at)
+SELECT id, 'pending_confirmation', subscribed_at
+FROM subscriptions;
+
+CREATE TABLE issue_deliveries
+(
+    newsletter_issue_id uuid        NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id       uuid        NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    delivered_at        timestamptz NOT NULL,
+    succeeded           BOOLEAN     NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_id)
+);
+
+-- Deleting a subscriber should take their outstanding confirmation tokens with them.
+ALTER TABLE subscription_tokens
+    DROP CONSTRAINT subscription_tokens_subscriber_id_fkey,
+    ADD CONSTRAINT subscription_tokens_subscriber_id_fkey
+        FOREIGN KEY (subscriber_id) REFERENCES subscriptions (id) ON DELETE CASCADE;
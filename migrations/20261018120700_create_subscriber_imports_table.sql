@@ -0,0 +1,12 @@
+CREATE TABLE subscriber_imports
+(
+    import_id   uuid        NOT NULL,
+    imported_by TEXT        NOT NULL,
+    -- Only set for imports that skip confirmation: who vouched for the consent and how.
+    attestation TEXT        NULL,
+    imported_at timestamptz NOT NULL,
+    PRIMARY KEY (import_id)
+);
+
+ALTER TABLE subscriptions
+    ADD COLUMN import_id uuid NULL REFERENCES subscriber_imports (import_id);
@@ -0,0 +1,19 @@
+-- Deliveries outlive the subscriber they were sent to, so issue statistics survive an erasure.
+ALTER TABLE issue_deliveries
+    DROP CONSTRAINT issue_deliveries_pkey,
+    DROP CONSTRAINT issue_deliveries_subscriber_id_fkey,
+    ALTER COLUMN subscriber_id DROP NOT NULL,
+    ADD CONSTRAINT issue_deliveries_subscriber_id_fkey
+        FOREIGN KEY (subscriber_id) REFERENCES subscriptions (id) ON DELETE SET NULL;
+CREATE UNIQUE INDEX issue_deliveries_newsletter_issue_id_subscriber_id_idx
+    ON issue_deliveries (newsletter_issue_id, subscriber_id);
+
+CREATE TABLE subscriber_erasures
+(
+    erasure_id    uuid        NOT NULL,
+    -- Not a foreign key: the subscriber is gone by the time we read this.
+    subscriber_id uuid        NOT NULL,
+    requested_by  TEXT        NOT NULL,
+    erased_at     timestamptz NOT NULL,
+    PRIMARY KEY (erasure_id)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE email_events
+(
+    -- The id assigned by the email provider, so that redelivered events are only applied once.
+    event_id      TEXT        NOT NULL,
+    subscriber_id uuid        NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    event_type    TEXT        NOT NULL,
+    reason        TEXT        NULL,
+    occurred_at   timestamptz NOT NULL,
+    received_at   timestamptz NOT NULL,
+    PRIMARY KEY (event_id)
+);
+CREATE INDEX email_events_subscriber_id_idx ON email_events (subscriber_id);
@@ -0,0 +1,16 @@
+-- Existing issues went out without a tracking pixel.
+ALTER TABLE newsletter_issues
+    ADD COLUMN track_opens BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE issue_opens
+(
+    newsletter_issue_id uuid        NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id       uuid        NULL
+        REFERENCES subscriptions (id) ON DELETE SET NULL,
+    first_opened_at     timestamptz NOT NULL,
+    last_opened_at      timestamptz NOT NULL,
+    open_count          INTEGER     NOT NULL
+);
+CREATE UNIQUE INDEX issue_opens_newsletter_issue_id_subscriber_id_idx
+    ON issue_opens (newsletter_issue_id, subscriber_id);
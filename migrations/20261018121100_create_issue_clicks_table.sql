@@ -0,0 +1,10 @@
+CREATE TABLE issue_clicks
+(
+    newsletter_issue_id uuid        NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id       uuid        NULL
+        REFERENCES subscriptions (id) ON DELETE SET NULL,
+    url                 TEXT        NOT NULL,
+    clicked_at          timestamptz NOT NULL
+);
+CREATE INDEX issue_clicks_newsletter_issue_id_idx ON issue_clicks (newsletter_issue_id);
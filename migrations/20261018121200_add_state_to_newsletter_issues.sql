@@ -0,0 +1,19 @@
+CREATE TYPE issue_state AS ENUM (
+    'sending',
+    'paused',
+    'cancelled'
+);
+
+ALTER TABLE newsletter_issues
+    ADD COLUMN state issue_state NOT NULL DEFAULT 'sending';
+
+CREATE TABLE cancelled_deliveries
+(
+    newsletter_issue_id uuid        NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id       uuid        NULL
+        REFERENCES subscriptions (id) ON DELETE SET NULL,
+    cancelled_at        timestamptz NOT NULL
+);
+CREATE INDEX cancelled_deliveries_newsletter_issue_id_idx
+    ON cancelled_deliveries (newsletter_issue_id);
@@ -0,0 +1,74 @@
+-- A segment narrows down the confirmed members of the list an issue is published to.
+-- Every rule that is set must match; NULL means "any".
+CREATE TABLE segments
+(
+    segment_id            uuid        NOT NULL,
+    PRIMARY KEY (segment_id),
+    name                  TEXT        NOT NULL UNIQUE,
+    -- Dates are UTC days; subscribed_before is exclusive.
+    subscribed_after      DATE        NULL,
+    subscribed_before     DATE        NULL,
+    list_id               uuid        NULL
+        REFERENCES lists (list_id) ON DELETE CASCADE,
+    opened_in_last_issues INTEGER     NULL CHECK (opened_in_last_issues > 0),
+    -- Stored in lowercase.
+    email_domain          TEXT        NULL,
+    created_at            timestamptz NOT NULL
+);
+
+ALTER TABLE newsletter_issues
+    ADD COLUMN segment_id uuid NULL REFERENCES segments (segment_id);
+
+-- Shared by the recipient preview and the delivery queue, so they cannot disagree.
+-- "The last N issues" are the most recent issues that went out with open tracking.
+CREATE FUNCTION subscriber_in_segment(uuid, uuid) RETURNS BOOLEAN
+    LANGUAGE sql
+    STABLE
+AS
+$$
+SELECT
+    (
+        segments.subscribed_after IS NULL OR
+        subscriptions.subscribed_at >= segments.subscribed_after::timestamp AT TIME ZONE 'UTC'
+    ) AND (
+        segments.subscribed_before IS NULL OR
+        subscriptions.subscribed_at < segments.subscribed_before::timestamp AT TIME ZONE 'UTC'
+    ) AND (
+        segments.list_id IS NULL OR
+        EXISTS (
+            SELECT 1
+            FROM list_subscriptions
+            WHERE
+                list_subscriptions.list_id = segments.list_id AND
+                list_subscriptions.subscriber_id = subscriptions.id
+        )
+    ) AND (
+        segments.email_domain IS NULL OR
+        lower(split_part(subscriptions.email, '@', 2)) = segments.email_domain
+    ) AND (
+        segments.opened_in_last_issues IS NULL OR
+        EXISTS (
+            SELECT 1
+            FROM issue_opens
+            JOIN (
+                SELECT
+                    newsletter_issue_id,
+                    row_number() OVER (ORDER BY published_at DESC) AS recency
+                FROM newsletter_issues
+                WHERE
+                    track_opens AND
+                    EXISTS (
+                        SELECT 1
+                        FROM issue_deliveries
+                        WHERE issue_deliveries.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+                    )
+            ) AS recent_issues
+                ON recent_issues.newsletter_issue_id = issue_opens.newsletter_issue_id
+            WHERE
+                issue_opens.subscriber_id = subscriptions.id AND
+                recent_issues.recency <= segments.opened_in_last_issues
+        )
+    )
+FROM subscriptions, segments
+WHERE subscriptions.id = $1 AND segments.segment_id = $2
+$$;
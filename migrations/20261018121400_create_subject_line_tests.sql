@@ -0,0 +1,37 @@
+CREATE TYPE subject_line_test_metric AS ENUM (
+    'open_rate',
+    'click_rate'
+);
+
+CREATE TABLE issue_subject_variants
+(
+    variant_id          uuid    NOT NULL,
+    PRIMARY KEY (variant_id),
+    newsletter_issue_id uuid    NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    position            INTEGER NOT NULL,
+    subject             TEXT    NOT NULL,
+    UNIQUE (newsletter_issue_id, position)
+);
+
+CREATE TABLE subject_line_tests
+(
+    newsletter_issue_id uuid                     NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    PRIMARY KEY (newsletter_issue_id),
+    sample_percent      INTEGER                  NOT NULL
+        CHECK (sample_percent BETWEEN 1 AND 100),
+    metric              subject_line_test_metric NOT NULL,
+    decide_at           timestamptz              NOT NULL,
+    winning_variant_id  uuid                     NULL
+        REFERENCES issue_subject_variants (variant_id),
+    decided_at          timestamptz              NULL
+);
+
+-- Held tasks wait for the subject line test of their issue to pick a winner.
+ALTER TABLE issue_delivery_queue
+    ADD COLUMN variant_id uuid    NULL REFERENCES issue_subject_variants (variant_id),
+    ADD COLUMN held       BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE issue_deliveries
+    ADD COLUMN variant_id uuid NULL REFERENCES issue_subject_variants (variant_id);
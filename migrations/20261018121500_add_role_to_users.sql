@@ -0,0 +1,17 @@
+CREATE TYPE user_role AS ENUM (
+    'owner',
+    'editor',
+    'viewer'
+);
+
+-- Everybody who could log in so far could do everything.
+ALTER TABLE users
+    ADD COLUMN role user_role NOT NULL DEFAULT 'owner';
+ALTER TABLE users
+    ALTER COLUMN role SET DEFAULT 'viewer';
+
+-- Removing a user takes their saved idempotent responses with them.
+ALTER TABLE idempotency
+    DROP CONSTRAINT idempotency_user_id_fkey,
+    ADD CONSTRAINT idempotency_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (user_id) ON DELETE CASCADE;
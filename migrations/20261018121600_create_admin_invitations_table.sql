@@ -0,0 +1,13 @@
+-- Only a hash of the token is stored: the token itself is only ever in the invitation email.
+CREATE TABLE admin_invitations
+(
+    token_hash  TEXT        NOT NULL,
+    PRIMARY KEY (token_hash),
+    email       TEXT        NOT NULL,
+    role        user_role   NOT NULL,
+    invited_by  uuid        NULL
+        REFERENCES users (user_id) ON DELETE SET NULL,
+    created_at  timestamptz NOT NULL,
+    expires_at  timestamptz NOT NULL,
+    accepted_at timestamptz NULL
+);
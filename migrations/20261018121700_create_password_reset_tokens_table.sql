@@ -0,0 +1,16 @@
+-- Where password reset links are sent. Users created before invitations existed can set it themselves.
+ALTER TABLE users
+    ADD COLUMN email TEXT NULL;
+CREATE UNIQUE INDEX users_email_idx ON users (lower(email));
+
+-- Only a hash of the token is stored: the token itself is only ever in the reset email.
+CREATE TABLE password_reset_tokens
+(
+    token_hash TEXT        NOT NULL,
+    PRIMARY KEY (token_hash),
+    user_id    uuid        NOT NULL
+        REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    used_at    timestamptz NULL
+);
@@ -0,0 +1,25 @@
+-- Set once the user has confirmed enrolment with a first code.
+ALTER TABLE users
+    ADD COLUMN totp_secret    TEXT   NULL,
+    -- The last accepted time step, so that a code cannot be used twice.
+    ADD COLUMN totp_last_step BIGINT NULL;
+
+-- One-off codes for when the authenticator app is lost. Only hashes are stored.
+CREATE TABLE two_factor_recovery_codes
+(
+    user_id   uuid        NOT NULL
+        REFERENCES users (user_id) ON DELETE CASCADE,
+    code_hash TEXT        NOT NULL,
+    PRIMARY KEY (user_id, code_hash),
+    used_at   timestamptz NULL
+);
+
+-- Settings that apply to every admin user. There is only ever one row.
+CREATE TABLE admin_settings
+(
+    id                 BOOLEAN NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (id),
+    CHECK (id),
+    require_two_factor BOOLEAN NOT NULL DEFAULT FALSE
+);
+INSERT INTO admin_settings DEFAULT VALUES;
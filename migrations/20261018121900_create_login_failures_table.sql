@@ -0,0 +1,16 @@
+CREATE TYPE login_throttle_kind AS ENUM (
+    'username',
+    'client_ip'
+);
+
+-- Failed login attempts, counted both per username (whether or not a user
+-- has it) and per client IP address.
+CREATE TABLE login_failures
+(
+    kind            login_throttle_kind NOT NULL,
+    key             TEXT                NOT NULL,
+    PRIMARY KEY (kind, key),
+    failures        INTEGER             NOT NULL,
+    last_failure_at timestamptz         NOT NULL,
+    locked_until    timestamptz         NULL
+);
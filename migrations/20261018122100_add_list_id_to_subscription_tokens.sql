@@ -0,0 +1,4 @@
+-- Tokens carrying a list confirm joining it: the membership is only created
+-- once the owner of the address follows the link.
+ALTER TABLE subscription_tokens
+    ADD COLUMN list_id uuid NULL REFERENCES lists (list_id) ON DELETE CASCADE;
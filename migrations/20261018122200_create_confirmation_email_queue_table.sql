@@ -0,0 +1,8 @@
+-- Confirmation emails for imported subscribers are sent by the background worker,
+-- so that large imports neither time out nor stop half-way through.
+CREATE TABLE confirmation_email_queue
+(
+    subscription_token TEXT NOT NULL PRIMARY KEY
+        REFERENCES subscription_tokens (subscription_token) ON DELETE CASCADE,
+    enqueued_at timestamptz NOT NULL
+);
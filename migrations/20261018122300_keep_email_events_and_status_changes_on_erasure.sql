@@ -0,0 +1,11 @@
+-- Like deliveries, bounces, complaints and status changes outlive the subscriber
+-- they were about, so aggregate statistics survive an erasure.
+ALTER TABLE email_events
+    DROP CONSTRAINT email_events_subscriber_id_fkey,
+    ADD CONSTRAINT email_events_subscriber_id_fkey
+        FOREIGN KEY (subscriber_id) REFERENCES subscriptions (id) ON DELETE SET NULL;
+ALTER TABLE subscription_status_changes
+    DROP CONSTRAINT subscription_status_changes_subscriber_id_fkey,
+    ALTER COLUMN subscriber_id DROP NOT NULL,
+    ADD CONSTRAINT subscription_status_changes_subscriber_id_fkey
+        FOREIGN KEY (subscriber_id) REFERENCES subscriptions (id) ON DELETE SET NULL;
@@ -0,0 +1,14 @@
+-- How many subscribers an issue was enqueued for. The worker drops tasks for subscribers
+-- who are no longer confirmed without recording a delivery, so it cannot be worked out later.
+ALTER TABLE newsletter_issues
+    ADD COLUMN recipient_count BIGINT NOT NULL DEFAULT 0;
+
+-- Best effort for issues published before we kept count.
+UPDATE newsletter_issues
+SET recipient_count =
+    (SELECT count(*) FROM issue_delivery_queue
+     WHERE issue_delivery_queue.newsletter_issue_id = newsletter_issues.newsletter_issue_id) +
+    (SELECT count(*) FROM cancelled_deliveries
+     WHERE cancelled_deliveries.newsletter_issue_id = newsletter_issues.newsletter_issue_id) +
+    (SELECT count(*) FROM issue_deliveries
+     WHERE issue_deliveries.newsletter_issue_id = newsletter_issues.newsletter_issue_id);
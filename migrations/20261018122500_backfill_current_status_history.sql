@@ -0,0 +1,14 @@
+-- The history backfill only recorded sign-ups, so subscribers who were already confirmed,
+-- unsubscribed etc. looked pending. We do not know when their status changed: record it
+-- right after the last change we know about, so that the history ends where they are.
+INSERT INTO subscription_status_changes (subscriber_id, status, changed_at)
+SELECT subscriptions.id, subscriptions.status, latest.changed_at + interval '1 second'
+FROM subscriptions
+JOIN LATERAL (
+    SELECT status, changed_at
+    FROM subscription_status_changes
+    WHERE subscription_status_changes.subscriber_id = subscriptions.id
+    ORDER BY changed_at DESC
+    LIMIT 1
+) AS latest ON TRUE
+WHERE latest.status <> subscriptions.status;
@@ -0,0 +1,62 @@
+-- subscriber_in_segment was evaluated once per subscriber and re-ranked every issue on each call.
+-- segment_members resolves a segment in a single pass: the last N issues are picked once
+-- and every rule is checked against the whole subscriber table.
+DROP FUNCTION subscriber_in_segment(uuid, uuid);
+
+-- Shared by the recipient preview and the delivery queue, so they cannot disagree.
+-- "The last N issues" are the most recent issues that went out with open tracking.
+CREATE FUNCTION segment_members(uuid) RETURNS TABLE (subscriber_id uuid)
+    LANGUAGE sql
+    STABLE
+AS
+$$
+WITH segment AS (
+    SELECT *
+    FROM segments
+    WHERE segment_id = $1
+),
+recent_issues AS (
+    SELECT newsletter_issue_id
+    FROM newsletter_issues
+    WHERE
+        track_opens AND
+        EXISTS (
+            SELECT 1
+            FROM issue_deliveries
+            WHERE issue_deliveries.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+        )
+    ORDER BY published_at DESC
+    LIMIT (SELECT opened_in_last_issues FROM segment)
+)
+SELECT subscriptions.id
+FROM subscriptions, segment
+WHERE
+    (
+        segment.subscribed_after IS NULL OR
+        subscriptions.subscribed_at >= segment.subscribed_after::timestamp AT TIME ZONE 'UTC'
+    ) AND (
+        segment.subscribed_before IS NULL OR
+        subscriptions.subscribed_at < segment.subscribed_before::timestamp AT TIME ZONE 'UTC'
+    ) AND (
+        segment.list_id IS NULL OR
+        EXISTS (
+            SELECT 1
+            FROM list_subscriptions
+            WHERE
+                list_subscriptions.list_id = segment.list_id AND
+                list_subscriptions.subscriber_id = subscriptions.id
+        )
+    ) AND (
+        segment.email_domain IS NULL OR
+        lower(split_part(subscriptions.email, '@', 2)) = segment.email_domain
+    ) AND (
+        segment.opened_in_last_issues IS NULL OR
+        EXISTS (
+            SELECT 1
+            FROM issue_opens
+            JOIN recent_issues
+                ON recent_issues.newsletter_issue_id = issue_opens.newsletter_issue_id
+            WHERE issue_opens.subscriber_id = subscriptions.id
+        )
+    )
+$$;
@@ -0,0 +1,2 @@
+-- Expired sessions are deleted by age on every login.
+CREATE INDEX user_sessions_last_seen_at_idx ON user_sessions (last_seen_at);
@@ -0,0 +1,12 @@
+-- A new account email address only replaces users.email once a link sent to it is clicked.
+-- Only a hash of the token is stored, as for password reset tokens.
+CREATE TABLE account_email_tokens
+(
+    token_hash TEXT        NOT NULL,
+    PRIMARY KEY (token_hash),
+    user_id    uuid        NOT NULL
+        REFERENCES users (user_id) ON DELETE CASCADE,
+    new_email  TEXT        NOT NULL,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL
+);
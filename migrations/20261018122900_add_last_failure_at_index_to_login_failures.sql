@@ -0,0 +1,2 @@
+-- Stale failures are deleted by age whenever a new one is recorded.
+CREATE INDEX login_failures_last_failure_at_idx ON login_failures (last_failure_at);
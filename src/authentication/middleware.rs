@@ -1,11 +1,13 @@
+use crate::authentication::{touch_session, Permission, Role};
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 use actix_web::body::MessageBody;
-use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
 use actix_web::error::InternalError;
-use actix_web::middleware::Next;
-use actix_web::FromRequest;
-use actix_web::HttpMessage;
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, FromRequest, HttpMessage, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
 use std::ops::Deref;
 use uuid::Uuid;
 
@@ -25,6 +27,8 @@ impl Deref for UserId {
     }
 }
 
+/// Also looks up the role of the logged-in user, so that handlers and
+/// [`require_permission`] can get it from the request extensions.
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
@@ -34,15 +38,106 @@ pub async fn reject_anonymous_users(
         TypedSession::from_request(http_request, payload).await
     }?;
 
-    match session.get_user_id().map_err(e500)? {
-        Some(user_id) => {
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .expect("The connection pool is registered as app data");
+    let user_id = session.get_user_id().map_err(e500)?;
+    let session_id = session.get_session_id().map_err(e500)?;
+    let access = match (user_id, session_id) {
+        (Some(user_id), Some(session_id)) => {
+            if touch_session(pool, user_id, session_id)
+                .await
+                .map_err(e500)?
+            {
+                get_access(pool, user_id)
+                    .await
+                    .map_err(e500)?
+                    .map(|access| (user_id, access))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    match access {
+        Some((user_id, access)) => {
+            // Users who still have to set up two-factor authentication can only do that.
+            if access.must_enrol_two_factor
+                && !req.path().starts_with("/admin/two-factor")
+                && req.path() != "/admin/logout"
+            {
+                let response = see_other("/admin/two-factor");
+                let e = anyhow::anyhow!("The user has not set up two-factor authentication");
+                return Err(InternalError::from_response(e, response).into());
+            }
             req.extensions_mut().insert(UserId(user_id));
+            req.extensions_mut().insert(access.role);
             next.call(req).await
-        },
+        }
         None => {
+            // The user may have been removed, or the session revoked, while they were logged in.
+            session.log_out();
             let response = see_other("/login");
             let e = anyhow::anyhow!("The user has not logged in");
             Err(InternalError::from_response(e, response).into())
         }
     }
 }
+
+/// Route middleware rejecting users whose role lacks `permission`.
+/// Must run inside [`reject_anonymous_users`].
+pub fn require_permission<S, B>(
+    permission: Permission,
+) -> impl Transform<
+    S,
+    ServiceRequest,
+    Response = ServiceResponse<impl MessageBody>,
+    Error = actix_web::Error,
+    InitError = (),
+>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    from_fn(move |req, next| check_permission(permission, req, next))
+}
+
+async fn check_permission(
+    permission: Permission,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let role = req.extensions().get::<Role>().copied();
+    if role.is_some_and(|role| role.can(permission)) {
+        next.call(req).await
+    } else {
+        let response = HttpResponse::Forbidden().body("You are not allowed to do that.");
+        let e = anyhow::anyhow!("The user is not allowed to {permission:?}");
+        Err(InternalError::from_response(e, response).into())
+    }
+}
+
+struct Access {
+    role: Role,
+    must_enrol_two_factor: bool,
+}
+
+#[tracing::instrument(name = "Get user access", skip(pool))]
+async fn get_access(pool: &PgPool, user_id: Uuid) -> Result<Option<Access>, anyhow::Error> {
+    let access = sqlx::query_as!(
+        Access,
+        // language=SQL
+        r#"
+        SELECT
+            role AS "role: Role",
+            totp_secret IS NULL AND admin_settings.require_two_factor AS "must_enrol_two_factor!"
+        FROM users, admin_settings
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the role of a user.")?;
+    Ok(access)
+}
@@ -1,5 +1,33 @@
 mod middleware;
 mod password;
-pub use middleware::reject_anonymous_users;
-pub use middleware::UserId;
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
+mod password_policy;
+mod role;
+mod sessions;
+mod throttle;
+mod token;
+mod totp;
+mod two_factor;
+pub use middleware::{reject_anonymous_users, require_permission, UserId};
+pub use password::{
+    change_password, change_password_in_transaction, create_user, validate_credentials, AuthError,
+    Credentials, PasswordHashingSettings,
+};
+pub use password_policy::{NewPassword, PasswordPolicy};
+pub use role::{Permission, Role};
+pub use sessions::{
+    get_active_sessions, record_session, revoke_other_sessions, revoke_session, session_lifecycle,
+    touch_session, ActiveSession,
+};
+pub use throttle::{
+    clear_login_failures, get_login_locks, is_login_locked, record_login_failure, LoginLock,
+    ThrottleKind,
+};
+pub use token::{generate_token, hash_token};
+pub use totp::{
+    current_totp_step, generate_recovery_codes, generate_totp_secret, qr_code_svg, totp_code,
+    totp_provisioning_uri, verify_totp_code,
+};
+pub use two_factor::{
+    disable_two_factor, enable_two_factor, get_totp_secret, is_two_factor_required,
+    set_two_factor_required, verify_second_factor,
+};
@@ -1,9 +1,11 @@
+use crate::authentication::{revoke_other_sessions, NewPassword, Role};
 use crate::telemetry::spawn_blocking_with_tracing;
 use anyhow::Context;
 use argon2::password_hash::{rand_core::OsRng, SaltString};
 use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
 use secrecy::{ExposeSecret, SecretString};
-use sqlx::PgPool;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
@@ -18,35 +20,86 @@ pub struct Credentials {
     pub password: SecretString,
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+/// The Argon2id parameters new password hashes are computed with.
+/// Hashes computed with other parameters are replaced on the next login.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct PasswordHashingSettings {
+    pub memory_size_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PasswordHashingSettings {
+    pub fn params(&self) -> Result<Params, anyhow::Error> {
+        Params::new(
+            self.memory_size_kib,
+            self.iterations,
+            self.parallelism,
+            None,
+        )
+        .context("Invalid Argon2 parameters.")
+    }
+
+    fn is_outdated(&self, hash: &PasswordHash) -> bool {
+        let Ok(params) = Params::try_from(hash) else {
+            return true;
+        };
+        hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+            || params.m_cost() != self.memory_size_kib
+            || params.t_cost() != self.iterations
+            || params.p_cost() != self.parallelism
+    }
+}
+
+#[tracing::instrument(name = "Validate credentials", skip(credentials, pool, hashing))]
 pub async fn validate_credentials(
     credentials: Credentials,
     pool: &PgPool,
+    hashing: &PasswordHashingSettings,
 ) -> Result<uuid::Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = SecretString::from(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
+    // Unknown usernames take as long to check as known ones: the cost of a
+    // verification only depends on the parameters in the hash.
+    let mut expected_password_hash = SecretString::from(format!(
+        "$argon2id$v=19$m={},t={},p={}$\
         gZiV/M1gPc22ElAH/Jh1Hw$\
-        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
+        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno",
+        hashing.memory_size_kib, hashing.iterations, hashing.parallelism,
+    ));
 
     if let Some((stored_user_id, stored_password_hash)) =
-        get_stored_credentials(&credentials.username, &pool).await?
+        get_stored_credentials(&credentials.username, pool).await?
     {
         user_id = Some(stored_user_id);
         expected_password_hash = stored_password_hash;
     }
 
-    spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
+    let old_password_hash = expected_password_hash.clone();
+    let hashing = hashing.clone();
+    let new_password_hash = spawn_blocking_with_tracing(move || {
+        verify_password_hash(&expected_password_hash, &credentials.password)?;
+        rehash_if_outdated(&expected_password_hash, credentials.password, &hashing)
     })
     .await
     .context("Failed to spawn blocking task.")??;
 
-    user_id
+    let user_id = user_id
         .ok_or_else(|| anyhow::anyhow!("Unknown username."))
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+    if let Some(new_password_hash) = new_password_hash {
+        // The old hash still works, so failing to replace it is no reason to refuse the login.
+        if let Err(e) =
+            store_rehashed_password(pool, user_id, &old_password_hash, &new_password_hash).await
+        {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to store a rehashed password."
+            );
+        }
+    }
+    Ok(user_id)
 }
 
 #[tracing::instrument(name = "Get stored credentials", skip(username, pool))]
@@ -75,8 +128,8 @@ async fn get_stored_credentials(
     skip(expected_password_hash, password_candidate)
 )]
 fn verify_password_hash(
-    expected_password_hash: SecretString,
-    password_candidate: SecretString,
+    expected_password_hash: &SecretString,
+    password_candidate: &SecretString,
 ) -> Result<(), AuthError> {
     let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
         .context("Failed to parse hash in PHC string format.")?;
@@ -90,16 +143,84 @@ fn verify_password_hash(
         .map_err(AuthError::InvalidCredentials)
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
-pub async fn change_password(
-    user_id: uuid::Uuid,
+/// Returns the hash computed with the current settings if `password_hash` was not.
+#[tracing::instrument(name = "Rehash if outdated", skip_all)]
+fn rehash_if_outdated(
+    password_hash: &SecretString,
     password: SecretString,
+    hashing: &PasswordHashingSettings,
+) -> Result<Option<SecretString>, AuthError> {
+    let password_hash = PasswordHash::new(password_hash.expose_secret())
+        .context("Failed to parse hash in PHC string format.")?;
+    if !hashing.is_outdated(&password_hash) {
+        return Ok(None);
+    }
+    let new_password_hash =
+        compute_password_hash(password, hashing).context("Failed to hash password")?;
+    Ok(Some(new_password_hash))
+}
+
+/// Leaves the hash alone if the password was changed in the meantime.
+#[tracing::instrument(name = "Store rehashed password", skip_all, fields(%user_id))]
+async fn store_rehashed_password(
     pool: &PgPool,
+    user_id: Uuid,
+    old_password_hash: &SecretString,
+    new_password_hash: &SecretString,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
     sqlx::query!(
+        // language=SQL
+        r#"
+        UPDATE users
+        SET password_hash = $3
+        WHERE user_id = $1 AND password_hash = $2
+        "#,
+        user_id,
+        old_password_hash.expose_secret(),
+        new_password_hash.expose_secret(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store a rehashed password in the database.")?;
+    Ok(())
+}
+
+/// Also logs the user out everywhere but in `current_session`.
+#[tracing::instrument(name = "Change password", skip(password, pool, hashing))]
+pub async fn change_password(
+    user_id: uuid::Uuid,
+    password: NewPassword,
+    current_session: Option<Uuid>,
+    pool: &PgPool,
+    hashing: &PasswordHashingSettings,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    change_password_in_transaction(&mut transaction, user_id, password, hashing).await?;
+    revoke_other_sessions(&mut *transaction, user_id, current_session).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to change a password.")?;
+    Ok(())
+}
+
+/// Sets the password as part of a larger change, e.g. using up a reset link.
+#[tracing::instrument(name = "Change password in transaction", skip_all, fields(%user_id))]
+pub async fn change_password_in_transaction(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    password: NewPassword,
+    hashing: &PasswordHashingSettings,
+) -> Result<(), anyhow::Error> {
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password.into(), &hashing))
+            .await?
+            .context("Failed to hash password")?;
+    let query = sqlx::query!(
         // language=SQL
         r#"
         UPDATE users
@@ -108,21 +229,116 @@ pub async fn change_password(
         "#,
         password_hash.expose_secret(),
         user_id
-    )
-    .execute(pool)
-    .await
-    .context("Failed to change user's password in the database.")?;
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to change user's password in the database.")?;
     Ok(())
 }
 
-fn compute_password_hash(password: SecretString) -> Result<SecretString, anyhow::Error> {
-    let salt = SaltString::generate(&mut OsRng::default());
-    let password_hash = Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
-    )
-    .hash_password(password.expose_secret().as_bytes(), &salt)?
-    .to_string();
+/// Returns `None` if the username or the email address is already taken.
+#[tracing::instrument(name = "Create user", skip(transaction, password, hashing))]
+pub async fn create_user(
+    transaction: &mut Transaction<'_, Postgres>,
+    username: &str,
+    email: &str,
+    password: NewPassword,
+    role: Role,
+    hashing: &PasswordHashingSettings,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password.into(), &hashing))
+            .await?
+            .context("Failed to hash password")?;
+    let user_id = Uuid::new_v4();
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO users (user_id, username, email, password_hash, role)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        username,
+        email,
+        password_hash.expose_secret(),
+        role as Role,
+    );
+    let n_inserted_rows = transaction
+        .execute(query)
+        .await
+        .context("Failed to store a new user in the database.")?
+        .rows_affected();
+    Ok((n_inserted_rows > 0).then_some(user_id))
+}
+
+fn compute_password_hash(
+    password: SecretString,
+    hashing: &PasswordHashingSettings,
+) -> Result<SecretString, anyhow::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, hashing.params()?)
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
     Ok(SecretString::from(password_hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_password_hash, PasswordHashingSettings};
+    use argon2::password_hash::{rand_core::OsRng, SaltString};
+    use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, Version};
+    use secrecy::{ExposeSecret, SecretString};
+
+    fn settings() -> PasswordHashingSettings {
+        PasswordHashingSettings {
+            memory_size_kib: 8192,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+
+    fn hash_with(algorithm: Algorithm, m_cost: u32, t_cost: u32) -> String {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::new(
+            algorithm,
+            Version::V0x13,
+            Params::new(m_cost, t_cost, 1, None).unwrap(),
+        )
+        .hash_password(b"password", &salt)
+        .unwrap()
+        .to_string()
+    }
+
+    #[test]
+    fn hashes_use_the_configured_parameters() {
+        let hash = compute_password_hash(SecretString::from("password"), &settings()).unwrap();
+        let hash = PasswordHash::new(hash.expose_secret()).unwrap();
+
+        assert!(!settings().is_outdated(&hash));
+    }
+
+    #[test]
+    fn hashes_with_other_parameters_or_algorithms_are_outdated() {
+        for hash in [
+            hash_with(Algorithm::Argon2id, 4096, 2),
+            hash_with(Algorithm::Argon2id, 8192, 1),
+            hash_with(Algorithm::Argon2i, 8192, 2),
+        ] {
+            let hash = PasswordHash::new(&hash).unwrap();
+            assert!(settings().is_outdated(&hash), "{hash}");
+        }
+    }
+
+    #[test]
+    fn parameters_argon2_cannot_use_are_rejected() {
+        assert!(settings().params().is_ok());
+        let settings = PasswordHashingSettings {
+            parallelism: 0,
+            ..settings()
+        };
+        assert!(settings.params().is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use secrecy::{ExposeSecret, SecretString};
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+/// Lowercase. Short entries are kept too: `min_length` is configurable.
+static COMMON_PASSWORDS: LazyLock<HashSet<&'static str>> =
+    LazyLock::new(|| include_str!("common_passwords.txt").lines().collect());
+
+/// Shorter usernames appear inside too many reasonable passwords.
+const MIN_USERNAME_LENGTH_TO_CHECK: usize = 3;
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+}
+
+impl PasswordPolicy {
+    /// Rejects a policy that no password could satisfy.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_length > self.max_length {
+            return Err(format!(
+                "The password policy's min_length ({}) is greater than its max_length ({}).",
+                self.min_length, self.max_length
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A password that satisfies the [`PasswordPolicy`]. Every way of setting a
+/// password takes one, so none of them can skip the rules.
+#[derive(Debug)]
+pub struct NewPassword(SecretString);
+
+impl NewPassword {
+    pub fn parse(
+        password: SecretString,
+        username: &str,
+        policy: &PasswordPolicy,
+    ) -> Result<NewPassword, String> {
+        let candidate = password.expose_secret();
+        let length = candidate.chars().count();
+        if length < policy.min_length {
+            return Err(format!(
+                "The new password must be at least {} characters long.",
+                policy.min_length
+            ));
+        }
+        if length > policy.max_length {
+            return Err(format!(
+                "The new password must be at most {} characters long.",
+                policy.max_length
+            ));
+        }
+        let lowercase = candidate.to_lowercase();
+        if COMMON_PASSWORDS.contains(lowercase.as_str()) {
+            return Err(
+                "The new password is too common - please choose one that is harder to guess."
+                    .into(),
+            );
+        }
+        let username = username.trim().to_lowercase();
+        if username.chars().count() >= MIN_USERNAME_LENGTH_TO_CHECK && lowercase.contains(&username)
+        {
+            return Err("The new password must not contain your username.".into());
+        }
+        Ok(Self(password))
+    }
+}
+
+impl From<NewPassword> for SecretString {
+    fn from(password: NewPassword) -> Self {
+        password.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NewPassword, PasswordPolicy};
+    use claims::{assert_err, assert_ok};
+    use secrecy::SecretString;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 12,
+            max_length: 128,
+        }
+    }
+
+    fn parse(password: &str, username: &str) -> Result<NewPassword, String> {
+        NewPassword::parse(SecretString::from(password), username, &policy())
+    }
+
+    #[test]
+    fn a_long_uncommon_password_is_accepted() {
+        assert_ok!(parse("gravel-orbit-lantern", "jane"));
+    }
+
+    #[test]
+    fn the_length_is_counted_in_characters() {
+        assert_ok!(parse("ééééééééééé-", "jane"));
+        assert_err!(parse("éééééé", "jane"));
+        assert_ok!(parse(&"a".repeat(128), "jane"));
+        assert_err!(parse(&"a".repeat(129), "jane"));
+    }
+
+    #[test]
+    fn common_passwords_are_rejected_whatever_their_case() {
+        for password in ["password1234", "Password1234", "CORRECTHORSEBATTERYSTAPLE"] {
+            assert_err!(parse(password, "jane"));
+        }
+    }
+
+    #[test]
+    fn passwords_containing_the_username_are_rejected() {
+        assert_err!(parse("hello-Jane.Doe-123", "jane.doe"));
+        assert_err!(parse("1234-JANE-5678", "jane"));
+    }
+
+    #[test]
+    fn very_short_usernames_are_not_checked() {
+        assert_ok!(parse("gravel-orbit-lantern", "or"));
+    }
+
+    #[test]
+    fn a_policy_no_password_can_satisfy_is_invalid() {
+        assert_ok!(policy().validate());
+        let policy = PasswordPolicy {
+            min_length: 20,
+            max_length: 19,
+        };
+        assert_err!(policy.validate());
+    }
+
+    #[test]
+    fn short_common_passwords_are_rejected_under_a_lenient_policy() {
+        let policy = PasswordPolicy {
+            min_length: 8,
+            max_length: 128,
+        };
+        for password in ["password", "12345678"] {
+            assert_err!(NewPassword::parse(
+                SecretString::from(password),
+                "jane",
+                &policy
+            ));
+        }
+    }
+
+    #[test]
+    fn every_common_password_is_lowercase() {
+        for password in super::COMMON_PASSWORDS.iter() {
+            assert_eq!(*password, password.to_lowercase());
+        }
+    }
+}
@@ -0,0 +1,89 @@
+/// What an admin user is allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "user_role", rename_all = "snake_case")]
+pub enum Role {
+    Owner,
+    Editor,
+    Viewer,
+}
+
+/// Actions that not every role may perform. Anything else only requires being logged in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    PublishIssues,
+    ManageAudience,
+    ManageUsers,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Owner, Role::Editor, Role::Viewer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Editor => "editor",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        match self {
+            Role::Owner => true,
+            Role::Editor => permission != Permission::ManageUsers,
+            Role::Viewer => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl TryFrom<&str> for Role {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("{s} is not a known role."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Permission::*;
+    use super::Role::{self, *};
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn owners_can_do_everything() {
+        for permission in [PublishIssues, ManageAudience, ManageUsers] {
+            assert!(Owner.can(permission));
+        }
+    }
+
+    #[test]
+    fn editors_cannot_manage_users() {
+        assert!(Editor.can(PublishIssues));
+        assert!(Editor.can(ManageAudience));
+        assert!(!Editor.can(ManageUsers));
+    }
+
+    #[test]
+    fn viewers_cannot_change_anything() {
+        for permission in [PublishIssues, ManageAudience, ManageUsers] {
+            assert!(!Viewer.can(permission));
+        }
+    }
+
+    #[test]
+    fn roles_are_parsed_from_their_names() {
+        for role in Role::ALL {
+            assert_ok_eq!(Role::try_from(role.as_str()), role);
+        }
+        assert_err!(Role::try_from("admin"));
+    }
+}
@@ -0,0 +1,172 @@
+use actix_session::config::{BrowserSession, TtlExtensionPolicy};
+use actix_web::cookie::time::Duration;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+/// How long a session lasts after its last use, both here and in the session store.
+const SESSION_TTL_HOURS: i32 = 24;
+/// `last_seen_at` is only written when it is older than this, so that browsing
+/// does not write to the database on every request.
+const LAST_SEEN_RESOLUTION_MINUTES: i32 = 5;
+
+/// Configures the session store to expire session state when `user_sessions` does.
+pub fn session_lifecycle() -> BrowserSession {
+    BrowserSession::default()
+        .state_ttl(Duration::hours(SESSION_TTL_HOURS.into()))
+        .state_ttl_extension_policy(TtlExtensionPolicy::OnEveryRequest)
+}
+
+pub struct ActiveSession {
+    pub session_id: Uuid,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+/// Records a login and returns the id to keep in the session.
+#[tracing::instrument(name = "Record session", skip(pool, user_agent))]
+pub async fn record_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    ip_address: &str,
+    user_agent: &str,
+) -> Result<Uuid, anyhow::Error> {
+    delete_expired_sessions(pool).await?;
+    let session_id = Uuid::new_v4();
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO user_sessions
+            (session_id, user_id, ip_address, user_agent, created_at, last_seen_at)
+        VALUES ($1, $2, $3, $4, now(), now())
+        "#,
+        session_id,
+        user_id,
+        ip_address,
+        user_agent,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record a session.")?;
+    Ok(session_id)
+}
+
+/// Expired sessions can never be used again, so their rows are only clutter.
+#[tracing::instrument(name = "Delete expired sessions", skip(pool))]
+async fn delete_expired_sessions(pool: &PgPool) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        // language=SQL
+        r#"DELETE FROM user_sessions WHERE last_seen_at <= now() - make_interval(hours => $1)"#,
+        SESSION_TTL_HOURS,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete expired sessions.")?;
+    Ok(())
+}
+
+/// Returns `false` if the session has been revoked or has expired.
+#[tracing::instrument(name = "Touch session", skip(pool))]
+pub async fn touch_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT last_seen_at > now() - make_interval(mins => $4) AS "is_recent!"
+        FROM user_sessions
+        WHERE session_id = $1 AND user_id = $2
+          AND last_seen_at > now() - make_interval(hours => $3)
+        "#,
+        session_id,
+        user_id,
+        SESSION_TTL_HOURS,
+        LAST_SEEN_RESOLUTION_MINUTES,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up a session.")?;
+    let Some(row) = row else {
+        return Ok(false);
+    };
+    if !row.is_recent {
+        sqlx::query!(
+            // language=SQL
+            r#"UPDATE user_sessions SET last_seen_at = now() WHERE session_id = $1"#,
+            session_id,
+        )
+        .execute(pool)
+        .await
+        .context("Failed to update the last use of a session.")?;
+    }
+    Ok(true)
+}
+
+#[tracing::instrument(name = "Get active sessions", skip(pool))]
+pub async fn get_active_sessions(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<ActiveSession>, anyhow::Error> {
+    let sessions = sqlx::query_as!(
+        ActiveSession,
+        // language=SQL
+        r#"
+        SELECT session_id, ip_address, user_agent, created_at, last_seen_at
+        FROM user_sessions
+        WHERE user_id = $1 AND last_seen_at > now() - make_interval(hours => $2)
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id,
+        SESSION_TTL_HOURS,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve active sessions.")?;
+    Ok(sessions)
+}
+
+/// Returns `false` if the user has no such session.
+#[tracing::instrument(name = "Revoke session", skip(pool))]
+pub async fn revoke_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        // language=SQL
+        r#"DELETE FROM user_sessions WHERE session_id = $1 AND user_id = $2"#,
+        session_id,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke a session.")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Revokes every session of the user but `keep`, or all of them.
+#[tracing::instrument(name = "Revoke other sessions", skip(executor))]
+pub async fn revoke_other_sessions<'c, E>(
+    executor: E,
+    user_id: Uuid,
+    keep: Option<Uuid>,
+) -> Result<(), anyhow::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query!(
+        // language=SQL
+        r#"DELETE FROM user_sessions WHERE user_id = $1 AND session_id IS DISTINCT FROM $2"#,
+        user_id,
+        keep,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to revoke sessions.")?;
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// Failures older than this are forgotten.
+const FAILURE_WINDOW_MINUTES: i32 = 60;
+const LOCKOUT: Duration = Duration::from_secs(15 * 60);
+const FIRST_DELAY: Duration = Duration::from_secs(5);
+
+/// What failed login attempts are counted against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "login_throttle_kind", rename_all = "snake_case")]
+pub enum ThrottleKind {
+    Username,
+    ClientIp,
+}
+
+impl ThrottleKind {
+    pub const ALL: [ThrottleKind; 2] = [ThrottleKind::Username, ThrottleKind::ClientIp];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThrottleKind::Username => "username",
+            ThrottleKind::ClientIp => "client_ip",
+        }
+    }
+
+    /// Failures before attempts are slowed down, and before they are locked out.
+    /// Many users can share an IP address, so it gets more slack.
+    fn thresholds(&self) -> (i32, i32) {
+        match self {
+            ThrottleKind::Username => (3, 10),
+            ThrottleKind::ClientIp => (20, 100),
+        }
+    }
+
+    /// How long to refuse attempts after `failures` failures in a row:
+    /// nothing at first, then a delay doubling with each failure, then a lockout.
+    pub fn lock_duration(&self, failures: i32) -> Option<Duration> {
+        let (delay_after, lock_after) = self.thresholds();
+        if failures >= lock_after {
+            Some(LOCKOUT)
+        } else if failures >= delay_after {
+            let doublings = (failures - delay_after).min(16) as u32;
+            Some((FIRST_DELAY * 2u32.pow(doublings)).min(LOCKOUT))
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for ThrottleKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl TryFrom<&str> for ThrottleKind {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        ThrottleKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| format!("{s} is not a kind of login lock."))
+    }
+}
+
+pub struct LoginLock {
+    pub kind: ThrottleKind,
+    pub key: String,
+    pub failures: i32,
+    pub locked_until: DateTime<Utc>,
+}
+
+/// Whether attempts for `username` or from `client_ip` are currently refused.
+#[tracing::instrument(name = "Check login lock", skip(pool))]
+pub async fn is_login_locked(
+    pool: &PgPool,
+    username: &str,
+    client_ip: &str,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM login_failures
+            WHERE ((kind = $1 AND key = $2) OR (kind = $3 AND key = $4))
+              AND locked_until > now()
+        ) AS "locked!"
+        "#,
+        ThrottleKind::Username as ThrottleKind,
+        username,
+        ThrottleKind::ClientIp as ThrottleKind,
+        client_ip,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to check whether logins are locked.")?;
+    Ok(row.locked)
+}
+
+/// Failures for any username are recorded, so old rows are pruned as new ones come in.
+#[tracing::instrument(name = "Record login failure", skip(pool))]
+pub async fn record_login_failure(
+    pool: &PgPool,
+    username: &str,
+    client_ip: &str,
+) -> Result<(), anyhow::Error> {
+    delete_stale_login_failures(pool).await?;
+    for (kind, key) in [
+        (ThrottleKind::Username, username),
+        (ThrottleKind::ClientIp, client_ip),
+    ] {
+        let failures = sqlx::query!(
+            // language=SQL
+            r#"
+            INSERT INTO login_failures (kind, key, failures, last_failure_at)
+            VALUES ($1, $2, 1, now())
+            ON CONFLICT (kind, key) DO UPDATE SET
+                failures = CASE
+                    WHEN login_failures.last_failure_at < now() - make_interval(mins => $3)
+                    THEN 1
+                    ELSE login_failures.failures + 1
+                END,
+                last_failure_at = now()
+            RETURNING failures
+            "#,
+            kind as ThrottleKind,
+            key,
+            FAILURE_WINDOW_MINUTES,
+        )
+        .fetch_one(pool)
+        .await
+        .context("Failed to record a failed login attempt.")?
+        .failures;
+        if let Some(duration) = kind.lock_duration(failures) {
+            sqlx::query!(
+                // language=SQL
+                r#"
+                UPDATE login_failures
+                SET locked_until = now() + make_interval(secs => $3)
+                WHERE kind = $1 AND key = $2
+                "#,
+                kind as ThrottleKind,
+                key,
+                duration.as_secs_f64(),
+            )
+            .execute(pool)
+            .await
+            .context("Failed to lock logins.")?;
+        }
+    }
+    Ok(())
+}
+
+/// Deletes failures that would be reset by the next one anyway, unless they still hold a lock.
+#[tracing::instrument(name = "Delete stale login failures", skip(pool))]
+async fn delete_stale_login_failures(pool: &PgPool) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        // language=SQL
+        r#"
+        DELETE FROM login_failures
+        WHERE last_failure_at < now() - make_interval(mins => $1)
+          AND (locked_until IS NULL OR locked_until <= now())
+        "#,
+        FAILURE_WINDOW_MINUTES,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete stale login failures.")?;
+    Ok(())
+}
+
+/// Forgets the failures counted against `key`, e.g. a username after a successful login.
+#[tracing::instrument(name = "Clear login failures", skip(pool))]
+pub async fn clear_login_failures(
+    pool: &PgPool,
+    kind: ThrottleKind,
+    key: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        // language=SQL
+        r#"DELETE FROM login_failures WHERE kind = $1 AND key = $2"#,
+        kind as ThrottleKind,
+        key,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to clear login failures.")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get login locks", skip(pool))]
+pub async fn get_login_locks(pool: &PgPool) -> Result<Vec<LoginLock>, anyhow::Error> {
+    let locks = sqlx::query_as!(
+        LoginLock,
+        // language=SQL
+        r#"
+        SELECT kind AS "kind: ThrottleKind", key, failures, locked_until AS "locked_until!"
+        FROM login_failures
+        WHERE locked_until > now()
+        ORDER BY locked_until DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve login locks.")?;
+    Ok(locks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThrottleKind::{self, *};
+    use super::{FIRST_DELAY, LOCKOUT};
+    use claims::{assert_err, assert_none, assert_ok_eq, assert_some_eq};
+
+    #[test]
+    fn the_first_failures_are_free() {
+        for failures in 1..3 {
+            assert_none!(Username.lock_duration(failures));
+        }
+        for failures in 1..20 {
+            assert_none!(ClientIp.lock_duration(failures));
+        }
+    }
+
+    #[test]
+    fn delays_double_until_the_lockout() {
+        assert_some_eq!(Username.lock_duration(3), FIRST_DELAY);
+        assert_some_eq!(Username.lock_duration(4), FIRST_DELAY * 2);
+        assert_some_eq!(Username.lock_duration(5), FIRST_DELAY * 4);
+        for failures in 3..10 {
+            assert!(Username.lock_duration(failures).unwrap() <= LOCKOUT);
+        }
+        assert_some_eq!(Username.lock_duration(10), LOCKOUT);
+        assert_some_eq!(Username.lock_duration(1000), LOCKOUT);
+        assert_some_eq!(ClientIp.lock_duration(99), LOCKOUT);
+    }
+
+    #[test]
+    fn kinds_are_parsed_from_their_names() {
+        for kind in ThrottleKind::ALL {
+            assert_ok_eq!(ThrottleKind::try_from(kind.as_str()), kind);
+        }
+        assert_err!(ThrottleKind::try_from("email"));
+    }
+}
@@ -0,0 +1,38 @@
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD};
+use rand::distr::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+/// A random token to be emailed to a user, e.g. in an invitation link.
+pub fn generate_token() -> String {
+    let mut rng = rand::rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+/// What we store instead of an emailed token, so that reading the database
+/// is not enough to use it.
+pub fn hash_token(token: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_token, hash_token};
+
+    #[test]
+    fn tokens_are_random() {
+        assert_ne!(generate_token(), generate_token());
+    }
+
+    #[test]
+    fn a_token_always_has_the_same_hash() {
+        let token = generate_token();
+
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), token);
+        assert_ne!(hash_token(&token), hash_token(&generate_token()));
+    }
+}
@@ -0,0 +1,163 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use rand::distr::Alphanumeric;
+use rand::Rng;
+use sha1::Sha1;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const STEP_SECONDS: u64 = 30;
+const DIGITS: u32 = 6;
+const ISSUER: &str = "Newsletter";
+const N_RECOVERY_CODES: usize = 10;
+
+/// A new TOTP secret, base32-encoded as authenticator apps expect it.
+pub fn generate_totp_secret() -> String {
+    let bytes: [u8; 20] = rand::rng().random();
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// The current RFC 6238 time step.
+pub fn current_totp_step() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("The clock is after the epoch");
+    now.as_secs() / STEP_SECONDS
+}
+
+/// The code an authenticator app shows for `secret` during `step`.
+/// Returns `None` if `secret` is not valid base32.
+pub fn totp_code(secret: &str, step: u64) -> Option<String> {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let mut mac = Hmac::<Sha1>::new_from_slice(&key).expect("HMAC accepts keys of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+    Some(format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    ))
+}
+
+/// Returns the time step `code` belongs to, allowing for one step of clock drift
+/// either way.
+pub fn verify_totp_code(secret: &str, code: &str) -> Option<u64> {
+    let code = code.trim();
+    let step = current_totp_step();
+    [step - 1, step, step + 1]
+        .into_iter()
+        .find(|&step| totp_code(secret, step).is_some_and(|expected| expected == code))
+}
+
+/// The `otpauth://` link authenticator apps enrol from.
+pub fn totp_provisioning_uri(secret: &str, username: &str) -> String {
+    format!(
+        "otpauth://totp/{ISSUER}:{}?secret={secret}&issuer={ISSUER}&digits={DIGITS}&period={STEP_SECONDS}",
+        percent_encode(username),
+    )
+}
+
+/// `uri` as a QR code, ready to be embedded in a page.
+pub fn qr_code_svg(uri: &str) -> String {
+    QrCode::new(uri.as_bytes())
+        .expect("Provisioning URIs fit in a QR code")
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build()
+}
+
+/// One-off codes to log in with when the authenticator app is lost.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::rng();
+    (0..N_RECOVERY_CODES)
+        .map(|_| {
+            std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .take(10)
+                .collect()
+        })
+        .collect()
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_none, assert_some, assert_some_eq};
+
+    // The SHA-1 test vectors from RFC 6238, appendix B.
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn codes_match_the_rfc_test_vectors() {
+        let secret = BASE32_NOPAD.encode(RFC_SECRET);
+        let test_cases = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+
+        for (time, expected) in test_cases {
+            assert_some_eq!(totp_code(&secret, time / STEP_SECONDS), expected);
+        }
+    }
+
+    #[test]
+    fn codes_from_neighbouring_steps_are_accepted() {
+        let secret = generate_totp_secret();
+        let step = current_totp_step();
+
+        for step in [step - 1, step, step + 1] {
+            let code = totp_code(&secret, step).unwrap();
+            assert_some!(verify_totp_code(&secret, &code));
+        }
+        let stale_code = totp_code(&secret, step - 5).unwrap();
+        let fresh_code = totp_code(&secret, step).unwrap();
+        if stale_code != fresh_code {
+            assert_none!(verify_totp_code(&secret, &stale_code));
+        }
+    }
+
+    #[test]
+    fn invalid_secrets_yield_no_code() {
+        assert_none!(totp_code("not base32!", 1));
+    }
+
+    #[test]
+    fn usernames_are_escaped_in_the_provisioning_uri() {
+        let uri = totp_provisioning_uri("SECRET", "jane doe&co");
+
+        assert_eq!(
+            uri,
+            "otpauth://totp/Newsletter:jane%20doe%26co?secret=SECRET&issuer=Newsletter&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn recovery_codes_are_distinct() {
+        let codes = generate_recovery_codes();
+
+        assert_eq!(codes.len(), N_RECOVERY_CODES);
+        assert!(codes.iter().all(|c| c.len() == 10));
+        let mut unique = codes.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), codes.len());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::authentication::hash_token;
+use crate::authentication::totp::verify_totp_code;
+use anyhow::Context;
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+/// `None` if the user has not set up two-factor authentication.
+#[tracing::instrument(name = "Get TOTP secret", skip(pool))]
+pub async fn get_totp_secret(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"SELECT totp_secret FROM users WHERE user_id = $1"#,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the TOTP secret of a user.")?;
+    Ok(row.and_then(|r| r.totp_secret))
+}
+
+/// `step` is the time step of the code that confirmed enrolment, so that it
+/// cannot be used again to log in. Replaces any previous recovery codes.
+#[tracing::instrument(
+    name = "Enable two-factor authentication",
+    skip(pool, secret, recovery_codes)
+)]
+pub async fn enable_two_factor(
+    pool: &PgPool,
+    user_id: Uuid,
+    secret: &str,
+    step: u64,
+    recovery_codes: &[String],
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"UPDATE users SET totp_secret = $2, totp_last_step = $3 WHERE user_id = $1"#,
+        user_id,
+        secret,
+        step as i64,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to store the TOTP secret of a user.")?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"DELETE FROM two_factor_recovery_codes WHERE user_id = $1"#,
+        user_id,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete old recovery codes.")?;
+    let code_hashes: Vec<String> = recovery_codes.iter().map(|c| hash_token(c)).collect();
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO two_factor_recovery_codes (user_id, code_hash)
+        SELECT $1, code_hash FROM unnest($2::text[]) AS code_hash
+        "#,
+        user_id,
+        &code_hashes,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to store recovery codes.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to enable two-factor authentication.")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(pool))]
+pub async fn disable_two_factor(pool: &PgPool, user_id: Uuid) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"UPDATE users SET totp_secret = NULL, totp_last_step = NULL WHERE user_id = $1"#,
+        user_id,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to remove the TOTP secret of a user.")?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"DELETE FROM two_factor_recovery_codes WHERE user_id = $1"#,
+        user_id,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete recovery codes.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to disable two-factor authentication.")?;
+    Ok(())
+}
+
+/// Accepts either a code from the authenticator app or an unused recovery code.
+/// Either can only be used once.
+#[tracing::instrument(name = "Verify second factor", skip(pool, code))]
+pub async fn verify_second_factor(
+    pool: &PgPool,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, anyhow::Error> {
+    let Some(secret) = get_totp_secret(pool, user_id).await? else {
+        return Ok(false);
+    };
+    if let Some(step) = verify_totp_code(&secret, code) {
+        let result = sqlx::query!(
+            // language=SQL
+            r#"
+            UPDATE users SET totp_last_step = $2
+            WHERE user_id = $1 AND (totp_last_step IS NULL OR totp_last_step < $2)
+            "#,
+            user_id,
+            step as i64,
+        )
+        .execute(pool)
+        .await
+        .context("Failed to record the use of a TOTP code.")?;
+        return Ok(result.rows_affected() == 1);
+    }
+    let result = sqlx::query!(
+        // language=SQL
+        r#"
+        UPDATE two_factor_recovery_codes SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        hash_token(&code.trim().to_lowercase()),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to use a recovery code.")?;
+    Ok(result.rows_affected() == 1)
+}
+
+#[tracing::instrument(
+    name = "Check whether two-factor authentication is required",
+    skip(pool)
+)]
+pub async fn is_two_factor_required(pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"SELECT require_two_factor FROM admin_settings"#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve admin settings.")?;
+    Ok(row.require_two_factor)
+}
+
+#[tracing::instrument(name = "Set whether two-factor authentication is required", skip(pool))]
+pub async fn set_two_factor_required(pool: &PgPool, required: bool) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        // language=SQL
+        r#"UPDATE admin_settings SET require_two_factor = $1"#,
+        required,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update admin settings.")?;
+    Ok(())
+}
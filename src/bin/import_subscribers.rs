@@ -0,0 +1,61 @@
+//! Bulk-loads subscribers from a CSV file with `email` and `name` columns.
+//!
+//! Usage: import_subscribers <file.csv> [--list <list_id>] [--confirmed <attestation>]
+//!
+//! Without `--confirmed` every imported subscriber is sent a confirmation email by the
+//! background worker.
+use anyhow::{bail, Context};
+use uuid::Uuid;
+use zero2prod::configuration::get_configuration;
+use zero2prod::lists::DEFAULT_LIST_ID;
+use zero2prod::startup::get_connection_pool;
+use zero2prod::subscriber_import::{import_subscribers, ImportMode};
+use zero2prod::telemetry::{get_subscriber, init_subscriber};
+
+const USAGE: &str =
+    "Usage: import_subscribers <file.csv> [--list <list_id>] [--confirmed <attestation>]";
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let subscriber = get_subscriber("import_subscribers".into(), "warn".into(), std::io::stderr);
+    init_subscriber(subscriber);
+
+    let mut path = None;
+    let mut list_id = DEFAULT_LIST_ID;
+    let mut mode = ImportMode::SendConfirmationEmails;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--list" => {
+                let value = args.next().context(USAGE)?;
+                list_id = Uuid::parse_str(&value).context("Invalid list id.")?;
+            }
+            "--confirmed" => {
+                let attestation = args.next().context(USAGE)?;
+                mode = ImportMode::Confirmed { attestation };
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => bail!(USAGE),
+        }
+    }
+    let path = path.context(USAGE)?;
+    let data = std::fs::read(&path).with_context(|| format!("Failed to read {path}."))?;
+
+    let configuration = get_configuration().expect("Failed to read configuration.");
+    let pool = get_connection_pool(&configuration.database);
+    let report = import_subscribers(&pool, &data, list_id, mode, "command line").await?;
+
+    for error in &report.errors {
+        eprintln!("Line {}: {}", error.line, error.message);
+    }
+    println!("Imported: {}", report.imported);
+    println!("Already subscribed: {}", report.duplicates);
+    println!("Rejected: {}", report.errors.len());
+    if report.queued_confirmation_emails > 0 {
+        println!(
+            "Confirmation emails queued: {}",
+            report.queued_confirmation_emails
+        );
+    }
+    Ok(())
+}
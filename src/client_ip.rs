@@ -0,0 +1,86 @@
+use actix_web::http::header::HeaderName;
+use actix_web::HttpRequest;
+use std::net::IpAddr;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// Reverse proxies allowed to tell us who the client is, e.g. our load balancer.
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(transparent)]
+pub struct TrustedProxies(Vec<IpAddr>);
+
+impl TrustedProxies {
+    pub fn new(proxies: Vec<IpAddr>) -> Self {
+        Self(proxies)
+    }
+
+    /// The address a request comes from. `X-Forwarded-For` is only believed when the
+    /// connection comes from a trusted proxy: anybody else can put what they like in it.
+    pub fn client_ip(&self, request: &HttpRequest) -> String {
+        let forwarded_for = request
+            .headers()
+            .get_all(X_FORWARDED_FOR)
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        self.resolve(request.peer_addr().map(|a| a.ip()), &forwarded_for)
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".into())
+    }
+
+    /// Each proxy appends the address it got the request from, so we walk back from
+    /// the end of the chain until we reach an address we do not trust.
+    fn resolve(&self, peer: Option<IpAddr>, forwarded_for: &str) -> Option<IpAddr> {
+        let mut client = peer?;
+        for hop in forwarded_for.rsplit(',') {
+            if !self.0.contains(&client) {
+                break;
+            }
+            match hop.trim().parse() {
+                Ok(ip) => client = ip,
+                Err(_) => break,
+            }
+        }
+        Some(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrustedProxies;
+    use claims::assert_some_eq;
+    use std::net::IpAddr;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_addresses_from_untrusted_peers_are_ignored() {
+        let proxies = TrustedProxies::default();
+        assert_some_eq!(
+            proxies.resolve(Some(ip("203.0.113.7")), "198.51.100.1"),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn trusted_proxies_are_skipped_until_the_first_untrusted_address() {
+        let proxies = TrustedProxies::new(vec![ip("10.0.0.1"), ip("10.0.0.2")]);
+        // The leftmost entry was written by the client and cannot be believed.
+        assert_some_eq!(
+            proxies.resolve(Some(ip("10.0.0.1")), "198.51.100.1, 203.0.113.7, 10.0.0.2"),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn a_trusted_proxy_without_a_header_is_the_client() {
+        let proxies = TrustedProxies::new(vec![ip("10.0.0.1")]);
+        assert_some_eq!(proxies.resolve(Some(ip("10.0.0.1")), ""), ip("10.0.0.1"));
+        assert_some_eq!(
+            proxies.resolve(Some(ip("10.0.0.1")), "not-an-address"),
+            ip("10.0.0.1")
+        );
+    }
+}
@@ -1,4 +1,7 @@
+use crate::authentication::{PasswordHashingSettings, PasswordPolicy};
+use crate::client_ip::TrustedProxies;
 use crate::email_client::EmailClient;
+use crate::email_events::EventWebhookKey;
 use config::{Config, ConfigError};
 use secrecy::{ExposeSecret, SecretString};
 use serde_aux::field_attributes::deserialize_number_from_string;
@@ -13,6 +16,8 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: SecretString,
+    pub password_policy: PasswordPolicy,
+    pub password_hashing: PasswordHashingSettings,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -22,6 +27,8 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: SecretString,
+    #[serde(default)]
+    pub trusted_proxies: TrustedProxies,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -41,6 +48,7 @@ pub struct EmailClientSettings {
     pub sender_email: String,
     pub authorization_token: SecretString,
     pub timeout_milliseconds: u64,
+    pub event_webhook_verification_key: String,
 }
 
 impl EmailClientSettings {
@@ -62,6 +70,10 @@ impl EmailClientSettings {
     pub fn timeout(&self) -> std::time::Duration {
         std::time::Duration::from_millis(self.timeout_milliseconds)
     }
+
+    pub fn event_webhook_key(&self) -> Result<EventWebhookKey, anyhow::Error> {
+        EventWebhookKey::parse(&self.event_webhook_verification_key)
+    }
 }
 
 impl DatabaseSettings {
@@ -80,7 +92,7 @@ impl DatabaseSettings {
         PgConnectOptions::new()
             .host(&self.host)
             .username(&self.username)
-            .password(&self.password.expose_secret())
+            .password(self.password.expose_secret())
             .port(self.port)
             .ssl_mode(ssl_mode)
     }
@@ -109,7 +121,12 @@ pub fn get_configuration() -> Result<Settings, ConfigError> {
         )
         .build()?;
 
-    settings.try_deserialize::<Settings>()
+    let settings = settings.try_deserialize::<Settings>()?;
+    settings
+        .password_policy
+        .validate()
+        .map_err(ConfigError::Message)?;
+    Ok(settings)
 }
 
 pub enum Environment {
@@ -0,0 +1,83 @@
+/// Whether the delivery worker should be sending an issue out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "issue_state", rename_all = "snake_case")]
+pub enum IssueState {
+    Sending,
+    Paused,
+    Cancelled,
+}
+
+impl IssueState {
+    pub const ALL: [IssueState; 3] = [
+        IssueState::Sending,
+        IssueState::Paused,
+        IssueState::Cancelled,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueState::Sending => "sending",
+            IssueState::Paused => "paused",
+            IssueState::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn can_transition_to(&self, next: IssueState) -> bool {
+        use IssueState::*;
+        matches!(
+            (self, next),
+            (Sending, Paused) | (Paused, Sending) | (Sending, Cancelled) | (Paused, Cancelled)
+        )
+    }
+
+    pub fn transition_to(self, next: IssueState) -> Result<IssueState, String> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(format!(
+                "An issue cannot go from {} to {}.",
+                self.as_str(),
+                next.as_str()
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for IssueState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IssueState::{self, *};
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn a_sending_issue_can_be_paused_and_resumed() {
+        assert_ok_eq!(Sending.transition_to(Paused), Paused);
+        assert_ok_eq!(Paused.transition_to(Sending), Sending);
+    }
+
+    #[test]
+    fn a_sending_or_paused_issue_can_be_cancelled() {
+        for current in [Sending, Paused] {
+            assert_ok_eq!(current.transition_to(Cancelled), Cancelled);
+        }
+    }
+
+    #[test]
+    fn a_cancelled_issue_stays_cancelled() {
+        for next in IssueState::ALL {
+            assert_err!(Cancelled.transition_to(next));
+        }
+    }
+
+    #[test]
+    fn an_issue_cannot_transition_to_its_current_state() {
+        for state in IssueState::ALL {
+            assert_err!(state.transition_to(state));
+        }
+    }
+}
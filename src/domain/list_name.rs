@@ -0,0 +1,64 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug)]
+pub struct ListName(String);
+
+impl ListName {
+    pub fn parse(s: String) -> Result<ListName, String> {
+        let s = s.trim().to_string();
+        let is_empty = s.is_empty();
+        let is_too_long = s.graphemes(true).count() > 100;
+        let forbidden_characters = ['/', '(', ')', '"', '<', '>', '\\', '{', '}', '&'];
+        let contains_forbidden_characters = s.chars().any(|g| forbidden_characters.contains(&g));
+
+        if is_empty || is_too_long || contains_forbidden_characters {
+            Err(format!("{s} is not a valid list name."))
+        } else {
+            Ok(Self(s))
+        }
+    }
+}
+
+impl AsRef<str> for ListName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::list_name::ListName;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn a_100_grapheme_long_name_is_valid() {
+        let name = "ї".repeat(100);
+        assert_ok!(ListName::parse(name));
+    }
+
+    #[test]
+    fn a_name_longer_than_100_graphemes_is_rejected() {
+        let name = "a".repeat(101);
+        assert_err!(ListName::parse(name));
+    }
+
+    #[test]
+    fn whitespace_only_names_are_rejected() {
+        let name = " ".to_string();
+        assert_err!(ListName::parse(name));
+    }
+
+    #[test]
+    fn names_containing_an_invalid_character_are_rejected() {
+        for name in &['/', '(', ')', '"', '<', '>', '\\', '{', '}', '&'] {
+            let name = format!("Weekly {name}");
+            assert_err!(ListName::parse(name));
+        }
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_trimmed() {
+        let name = ListName::parse("  Weekly digest ".to_string()).unwrap();
+        assert_eq!(name.as_ref(), "Weekly digest");
+    }
+}
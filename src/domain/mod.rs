@@ -1,7 +1,13 @@
+mod issue_state;
+mod list_name;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
+mod subscription_status;
 
+pub use issue_state::IssueState;
+pub use list_name::ListName;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use subscription_status::SubscriptionStatus;
@@ -0,0 +1,130 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, serde::Serialize)]
+#[sqlx(type_name = "subscription_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionStatus {
+    PendingConfirmation,
+    Confirmed,
+    Unsubscribed,
+    Bounced,
+    Complained,
+}
+
+impl SubscriptionStatus {
+    pub const ALL: [SubscriptionStatus; 5] = [
+        SubscriptionStatus::PendingConfirmation,
+        SubscriptionStatus::Confirmed,
+        SubscriptionStatus::Unsubscribed,
+        SubscriptionStatus::Bounced,
+        SubscriptionStatus::Complained,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriptionStatus::PendingConfirmation => "pending_confirmation",
+            SubscriptionStatus::Confirmed => "confirmed",
+            SubscriptionStatus::Unsubscribed => "unsubscribed",
+            SubscriptionStatus::Bounced => "bounced",
+            SubscriptionStatus::Complained => "complained",
+        }
+    }
+
+    pub fn can_transition_to(&self, next: SubscriptionStatus) -> bool {
+        use SubscriptionStatus::*;
+        matches!(
+            (self, next),
+            (PendingConfirmation, Confirmed)
+                | (Confirmed, Unsubscribed)
+                | (Confirmed, Bounced)
+                | (Confirmed, Complained)
+        )
+    }
+
+    pub fn transition_to(self, next: SubscriptionStatus) -> Result<SubscriptionStatus, String> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(format!(
+                "A subscription cannot go from {} to {}.",
+                self.as_str(),
+                next.as_str()
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for SubscriptionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl TryFrom<String> for SubscriptionStatus {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "pending_confirmation" => Ok(Self::PendingConfirmation),
+            "confirmed" => Ok(Self::Confirmed),
+            "unsubscribed" => Ok(Self::Unsubscribed),
+            "bounced" => Ok(Self::Bounced),
+            "complained" => Ok(Self::Complained),
+            other => Err(format!("{other} is not a valid subscription status.")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriptionStatus::{self, *};
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn a_pending_subscriber_can_be_confirmed() {
+        assert_ok_eq!(PendingConfirmation.transition_to(Confirmed), Confirmed);
+    }
+
+    #[test]
+    fn a_confirmed_subscriber_can_leave_the_list() {
+        for next in [Unsubscribed, Bounced, Complained] {
+            assert_ok_eq!(Confirmed.transition_to(next), next);
+        }
+    }
+
+    #[test]
+    fn a_pending_subscriber_cannot_skip_confirmation() {
+        for next in [Unsubscribed, Bounced, Complained] {
+            assert_err!(PendingConfirmation.transition_to(next));
+        }
+    }
+
+    #[test]
+    fn final_statuses_cannot_be_left() {
+        for current in [Unsubscribed, Bounced, Complained] {
+            for next in SubscriptionStatus::ALL {
+                assert_err!(current.transition_to(next));
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_leads_back_to_pending_confirmation() {
+        for current in SubscriptionStatus::ALL {
+            assert_err!(current.transition_to(PendingConfirmation));
+        }
+    }
+
+    #[test]
+    fn statuses_round_trip_through_their_string_form() {
+        for status in SubscriptionStatus::ALL {
+            assert_ok_eq!(
+                SubscriptionStatus::try_from(status.as_str().to_string()),
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_statuses_are_rejected() {
+        assert_err!(SubscriptionStatus::try_from("active".to_string()));
+    }
+}
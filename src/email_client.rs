@@ -36,6 +36,40 @@ impl EmailClient {
         subject: &str,
         html_content: &str,
         text_content: &str,
+    ) -> Result<(), reqwest::Error> {
+        let content = vec![
+            Content {
+                r#type: "text/plain",
+                value: text_content,
+            },
+            Content {
+                r#type: "text/html",
+                value: html_content,
+            },
+        ];
+        self.send(email, name, subject, content).await
+    }
+
+    pub async fn send_plain_text_email(
+        &self,
+        email: &SubscriberEmail,
+        name: &SubscriberName,
+        subject: &str,
+        text_content: &str,
+    ) -> Result<(), reqwest::Error> {
+        let content = vec![Content {
+            r#type: "text/plain",
+            value: text_content,
+        }];
+        self.send(email, name, subject, content).await
+    }
+
+    async fn send(
+        &self,
+        email: &SubscriberEmail,
+        name: &SubscriberName,
+        subject: &str,
+        content: Vec<Content<'_>>,
     ) -> Result<(), reqwest::Error> {
         let url = self.base_url.join("/v3/mail/send").unwrap();
         let request_body = MailSendRequest {
@@ -46,16 +80,7 @@ impl EmailClient {
                 }],
                 subject,
             }],
-            content: vec![
-                Content {
-                    r#type: "text/plain",
-                    value: text_content,
-                },
-                Content {
-                    r#type: "text/html",
-                    value: html_content,
-                },
-            ],
+            content,
             from: Subscriber {
                 email: self.sender.as_ref(),
                 name: "zero2prod",
@@ -155,6 +180,41 @@ mod tests {
             .await;
     }
 
+    struct PlainTextOnlyBodyMatcher;
+
+    impl wiremock::Match for PlainTextOnlyBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                let content = body["content"].as_array().unwrap();
+                content.len() == 1 && content[0]["type"] == "text/plain"
+            } else {
+                false
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn send_plain_text_email_only_sends_plain_text_content() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(path("/v3/mail/send"))
+            .and(method("POST"))
+            .and(MailSendRequestBodyMatcher)
+            .and(PlainTextOnlyBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_plain_text_email(&email(), &name(), &subject(), &content())
+            .await;
+
+        assert_ok!(outcome);
+    }
+
     #[tokio::test]
     async fn send_email_succeeds_if_the_server_returns_200() {
         let mock_server = MockServer::start().await;
@@ -0,0 +1,140 @@
+use crate::domain::SubscriptionStatus;
+use anyhow::Context;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use chrono::{DateTime, Utc};
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+
+pub const SIGNATURE_HEADER: &str = "X-Twilio-Email-Event-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Twilio-Email-Event-Webhook-Timestamp";
+
+/// The public half of the key SendGrid signs event webhook requests with.
+pub struct EventWebhookKey(VerifyingKey);
+
+impl EventWebhookKey {
+    /// Parses the key as shown in the SendGrid dashboard: base64-encoded DER.
+    pub fn parse(key: &str) -> Result<Self, anyhow::Error> {
+        let der = BASE64_STANDARD
+            .decode(key.trim())
+            .context("The event webhook verification key is not valid base64.")?;
+        let key = VerifyingKey::from_public_key_der(&der)
+            .context("The event webhook verification key is not a P-256 public key.")?;
+        Ok(Self(key))
+    }
+
+    /// SendGrid signs the timestamp header followed by the raw request body.
+    pub fn verify(&self, timestamp: &str, payload: &[u8], signature: &str) -> bool {
+        let Ok(signature) = BASE64_STANDARD.decode(signature.trim()) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_der(&signature) else {
+            return false;
+        };
+        let signed = [timestamp.as_bytes(), payload].concat();
+        self.0.verify(&signed, &signature).is_ok()
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct EmailEvent {
+    pub sg_event_id: String,
+    pub email: String,
+    pub event: String,
+    pub timestamp: i64,
+    /// Set on `bounce` events: `bounce` for hard bounces, `blocked` for temporary rejections.
+    #[serde(rename = "type")]
+    pub bounce_type: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl EmailEvent {
+    /// The status the subscriber should move to, if this event means we must stop mailing them.
+    pub fn subscription_status(&self) -> Option<SubscriptionStatus> {
+        match (self.event.as_str(), self.bounce_type.as_deref()) {
+            ("bounce", Some("blocked")) => None,
+            ("bounce", _) => Some(SubscriptionStatus::Bounced),
+            ("spamreport", _) => Some(SubscriptionStatus::Complained),
+            _ => None,
+        }
+    }
+
+    pub fn occurred_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.timestamp, 0).unwrap_or_else(Utc::now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmailEvent, EventWebhookKey};
+    use crate::domain::SubscriptionStatus;
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::{Signature, SigningKey};
+    use p256::pkcs8::EncodePublicKey;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_slice(&[7; 32]).unwrap()
+    }
+
+    fn verification_key() -> EventWebhookKey {
+        let der = signing_key().verifying_key().to_public_key_der().unwrap();
+        EventWebhookKey::parse(&BASE64_STANDARD.encode(der.as_bytes())).unwrap()
+    }
+
+    fn sign(timestamp: &str, payload: &[u8]) -> String {
+        let signature: Signature = signing_key().sign(&[timestamp.as_bytes(), payload].concat());
+        BASE64_STANDARD.encode(signature.to_der().as_bytes())
+    }
+
+    fn event(event: &str, bounce_type: Option<&str>) -> EmailEvent {
+        EmailEvent {
+            sg_event_id: "event-id".into(),
+            email: "ursula@example.com".into(),
+            event: event.into(),
+            timestamp: 1_700_000_000,
+            bounce_type: bounce_type.map(Into::into),
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn a_valid_signature_is_accepted() {
+        let signature = sign("1700000000", b"[]");
+
+        assert!(verification_key().verify("1700000000", b"[]", &signature));
+    }
+
+    #[test]
+    fn a_signature_over_a_different_payload_is_rejected() {
+        let signature = sign("1700000000", b"[]");
+
+        assert!(!verification_key().verify("1700000000", b"[{}]", &signature));
+        assert!(!verification_key().verify("1700000001", b"[]", &signature));
+    }
+
+    #[test]
+    fn a_malformed_signature_is_rejected() {
+        assert!(!verification_key().verify("1700000000", b"[]", "not base64!"));
+        assert!(!verification_key().verify("1700000000", b"[]", "AAAA"));
+    }
+
+    #[test]
+    fn hard_bounces_and_spam_reports_end_the_subscription() {
+        assert_eq!(
+            event("bounce", Some("bounce")).subscription_status(),
+            Some(SubscriptionStatus::Bounced)
+        );
+        assert_eq!(
+            event("spamreport", None).subscription_status(),
+            Some(SubscriptionStatus::Complained)
+        );
+    }
+
+    #[test]
+    fn other_events_leave_the_subscription_alone() {
+        assert_eq!(event("bounce", Some("blocked")).subscription_status(), None);
+        assert_eq!(event("delivered", None).subscription_status(), None);
+        assert_eq!(event("deferred", None).subscription_status(), None);
+    }
+}
@@ -1,8 +1,11 @@
-use crate::domain::{SubscriberEmail, SubscriberName};
+use crate::domain::{IssueState, SubscriberEmail, SubscriberName, SubscriptionStatus};
 use crate::email_client::EmailClient;
+use crate::subject_line_tests::decide_due_subject_line_tests;
+use crate::subscriber_import::try_send_confirmation_email;
+use crate::tracking::{rewrite_links, ClickToken, OpenToken, TrackingKey};
 use crate::{configuration::Settings, startup::get_connection_pool};
 use sqlx::{Executor, PgPool, Postgres, Transaction};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{field::display, Span};
 use uuid::Uuid;
 
@@ -11,7 +14,14 @@ use uuid::Uuid;
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
     let email_client = configuration.email_client.client();
-    worker_loop(connection_pool, email_client).await
+    let tracking_key = TrackingKey::new(configuration.application.hmac_secret);
+    worker_loop(
+        connection_pool,
+        email_client,
+        configuration.application.base_url,
+        tracking_key,
+    )
+    .await
 }
 
 pub enum ExecutionOutcome {
@@ -19,9 +29,32 @@ pub enum ExecutionOutcome {
     EmptyQueue,
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+    tracking_key: TrackingKey,
+) -> Result<(), anyhow::Error> {
+    let mut last_decided_at: Option<Instant> = None;
     loop {
-        match try_execute_task(&pool, &email_client).await {
+        if last_decided_at.is_none_or(|at| at.elapsed() >= Duration::from_secs(60)) {
+            if let Err(e) = decide_due_subject_line_tests(&pool).await {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to decide subject line tests.",
+                );
+            }
+            last_decided_at = Some(Instant::now());
+        }
+        // Newsletter issues go first; confirmation emails are sent when there are none left.
+        let outcome = match try_execute_task(&pool, &email_client, &base_url, &tracking_key).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                try_send_confirmation_email(&pool, &email_client, &base_url).await
+            }
+            outcome => outcome,
+        };
+        match outcome {
             Ok(ExecutionOutcome::EmptyQueue) => {
                 tokio::time::sleep(Duration::from_secs(10)).await;
             }
@@ -41,35 +74,86 @@ async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyh
     skip_all,
     fields(
         newsletter_issue_id=tracing::field::Empty,
-        subscriber_email=tracing::field::Empty
+        subscriber_id=tracing::field::Empty
     ),
     err
 )]
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    base_url: &str,
+    tracking_key: &TrackingKey,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
     let task = dequeue_task(pool).await?;
     if task.is_none() {
         return Ok(ExecutionOutcome::EmptyQueue);
     }
-    let (transaction, issue_id, email) = task.unwrap();
+    let (mut transaction, issue_id, subscriber_id, variant_id) = task.unwrap();
     Span::current()
-        .record("newsletter_issue_id", &display(issue_id))
-        .record("subscriber_email", &display(&email));
-    match get_subscriber(pool, &email).await {
-        Ok(subscriber) => {
-            let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
-                    &subscriber.email,
-                    &subscriber.name,
-                    &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
-                )
-                .await
-            {
+        .record("newsletter_issue_id", display(issue_id))
+        .record("subscriber_id", display(subscriber_id));
+    match get_confirmed_subscriber(pool, subscriber_id).await {
+        Ok(Some(subscriber)) => {
+            let issue = get_issue(pool, issue_id, variant_id).await?;
+            let preferences_link = format!(
+                "{base_url}/preferences?token={}",
+                subscriber.preference_token
+            );
+            let text_content = format!(
+                "{}\n\n--\nManage your subscription: {preferences_link}",
+                issue.text_content
+            );
+            let outcome = if subscriber.plain_text_only {
+                email_client
+                    .send_plain_text_email(
+                        &subscriber.email,
+                        &subscriber.name,
+                        &issue.subject,
+                        &text_content,
+                    )
+                    .await
+            } else {
+                let tracked_content = rewrite_links(&issue.html_content, |url| {
+                    let token = ClickToken {
+                        newsletter_issue_id: issue_id,
+                        subscriber_id,
+                        url: url.to_owned(),
+                    }
+                    .encode(tracking_key);
+                    format!("{base_url}/r/{token}")
+                });
+                let mut html_content = format!(
+                    "{tracked_content}<p><a href=\"{preferences_link}\">Manage your subscription</a></p>"
+                );
+                if issue.track_opens {
+                    let token = OpenToken {
+                        newsletter_issue_id: issue_id,
+                        subscriber_id,
+                    }
+                    .encode(tracking_key);
+                    html_content.push_str(&format!(
+                        "<img src=\"{base_url}/o/{token}\" width=\"1\" height=\"1\" alt=\"\">"
+                    ));
+                }
+                email_client
+                    .send_email(
+                        &subscriber.email,
+                        &subscriber.name,
+                        &issue.subject,
+                        &html_content,
+                        &text_content,
+                    )
+                    .await
+            };
+            record_delivery(
+                &mut transaction,
+                issue_id,
+                subscriber_id,
+                variant_id,
+                outcome.is_ok(),
+            )
+            .await?;
+            if let Err(e) = outcome {
                 tracing::error!(
                     error.cause_chain = ?e,
                     error.message = %e,
@@ -77,6 +161,9 @@ pub async fn try_execute_task(
                 );
             }
         }
+        Ok(None) => {
+            tracing::info!("Skipping a subscriber who is no longer confirmed.");
+        }
         Err(e) => {
             tracing::error!(
                 error.cause_chain = ?e,
@@ -89,27 +176,31 @@ pub async fn try_execute_task(
     //  This could be changed by enhancing issue_delivery_queue - e.g. adding a n_retries and execute_after
     //  columns to keep track of how many attempts have already taken place and how long we should wait before
     //  trying again. Try implementing it as an exercise!
-    delete_task(transaction, issue_id, &email).await?;
+    delete_task(transaction, issue_id, subscriber_id).await?;
 
     Ok(ExecutionOutcome::TaskCompleted)
 }
 
 type PgTransaction = Transaction<'static, Postgres>;
+/// The issue, the subscriber and the subject line variant they were assigned, if any.
+type DequeuedTask = (PgTransaction, Uuid, Uuid, Option<Uuid>);
 
 #[tracing::instrument(skip_all)]
-async fn dequeue_task(
-    pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+async fn dequeue_task(pool: &PgPool) -> Result<Option<DequeuedTask>, anyhow::Error> {
     let mut transaction = pool.begin().await?;
     let query = sqlx::query!(
         // language=SQL
         r#"
-        SELECT newsletter_issue_id, subscriber_email
+        SELECT issue_delivery_queue.newsletter_issue_id, subscriber_id, variant_id
         FROM issue_delivery_queue
-        FOR UPDATE
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_delivery_queue.newsletter_issue_id
+        WHERE newsletter_issues.state = $1 AND NOT held
+        FOR UPDATE OF issue_delivery_queue
         SKIP LOCKED
         LIMIT 1
         "#,
+        IssueState::Sending as IssueState,
     );
 
     let r = query.fetch_optional(&mut *transaction).await?;
@@ -117,7 +208,8 @@ async fn dequeue_task(
         Ok(Some((
             transaction,
             r.newsletter_issue_id,
-            r.subscriber_email,
+            r.subscriber_id,
+            r.variant_id,
         )))
     } else {
         Ok(None)
@@ -128,7 +220,7 @@ async fn dequeue_task(
 async fn delete_task(
     mut transaction: PgTransaction,
     issue_id: Uuid,
-    email: &str,
+    subscriber_id: Uuid,
 ) -> Result<(), anyhow::Error> {
     let query = sqlx::query!(
         // language=SQL
@@ -136,34 +228,77 @@ async fn delete_task(
         DELETE FROM issue_delivery_queue
         WHERE
         newsletter_issue_id = $1 AND
-        subscriber_email = $2
+        subscriber_id = $2
         "#,
         issue_id,
-        email
+        subscriber_id
     );
     transaction.execute(query).await?;
     transaction.commit().await?;
     Ok(())
 }
 
+#[tracing::instrument(skip(transaction))]
+async fn record_delivery(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+    variant_id: Option<Uuid>,
+    succeeded: bool,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO issue_deliveries (
+            newsletter_issue_id,
+            subscriber_id,
+            delivered_at,
+            succeeded,
+            variant_id
+        )
+        VALUES ($1, $2, now(), $3, $4)
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+        subscriber_id,
+        succeeded,
+        variant_id,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
 struct NewsletterIssue {
-    title: String,
+    subject: String,
     text_content: String,
     html_content: String,
+    track_opens: bool,
 }
 
 #[tracing::instrument(skip_all)]
-async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
+async fn get_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+    variant_id: Option<Uuid>,
+) -> Result<NewsletterIssue, anyhow::Error> {
     let issue = sqlx::query_as!(
         NewsletterIssue,
         // language=SQL
         r#"
-        SELECT title, text_content, html_content
+        SELECT
+            coalesce(issue_subject_variants.subject, title) AS "subject!",
+            text_content,
+            html_content,
+            track_opens
         FROM newsletter_issues
+        LEFT JOIN issue_subject_variants
+            ON issue_subject_variants.newsletter_issue_id = newsletter_issues.newsletter_issue_id AND
+               issue_subject_variants.variant_id = $2
         WHERE
-        newsletter_issue_id = $1
+        newsletter_issues.newsletter_issue_id = $1
         "#,
-        issue_id
+        issue_id,
+        variant_id,
     )
     .fetch_one(pool)
     .await?;
@@ -173,21 +308,31 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
 struct Subscriber {
     email: SubscriberEmail,
     name: SubscriberName,
+    plain_text_only: bool,
+    preference_token: String,
 }
 
 #[tracing::instrument(skip_all)]
-async fn get_subscriber(pool: &PgPool, email: &str) -> Result<Subscriber, anyhow::Error> {
+async fn get_confirmed_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Subscriber>, anyhow::Error> {
     let r = sqlx::query!(
         // language=SQL
         r#"
-        SELECT email, name
+        SELECT email, name, plain_text_only, preference_token
         FROM subscriptions
-        WHERE email = $1
+        JOIN preference_tokens ON preference_tokens.subscriber_id = subscriptions.id
+        WHERE id = $1 AND status = $2
         "#,
-        email
+        subscriber_id,
+        SubscriptionStatus::Confirmed as SubscriptionStatus,
     )
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await?;
+    let Some(r) = r else {
+        return Ok(None);
+    };
 
     let email = match SubscriberEmail::parse(r.email) {
         Ok(email) => email,
@@ -198,5 +343,10 @@ async fn get_subscriber(pool: &PgPool, email: &str) -> Result<Subscriber, anyhow
         Err(e) => return Err(anyhow::anyhow!(e)),
     };
 
-    Ok(Subscriber { email, name })
+    Ok(Some(Subscriber {
+        email,
+        name,
+        plain_text_only: r.plain_text_only,
+        preference_token: r.preference_token,
+    }))
 }
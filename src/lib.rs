@@ -1,11 +1,19 @@
 pub mod authentication;
+pub mod client_ip;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
+pub mod email_events;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod lists;
 pub mod routes;
+pub mod segments;
 pub mod session_state;
 pub mod startup;
+pub mod subject_line_tests;
+pub mod subscriber_data;
+pub mod subscriber_import;
 pub mod telemetry;
+pub mod tracking;
 pub mod utils;
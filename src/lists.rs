@@ -0,0 +1,125 @@
+use crate::domain::ListName;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// The list every subscriber and issue belonged to before we supported more than one.
+pub const DEFAULT_LIST_ID: Uuid = uuid::uuid!("8d1f6c9e-3b5a-4f0e-9c2d-7a4b1e6f3c58");
+
+pub struct MailingList {
+    pub list_id: Uuid,
+    pub name: String,
+}
+
+#[tracing::instrument(name = "Get mailing lists", skip(pool))]
+pub async fn get_lists(pool: &PgPool) -> Result<Vec<MailingList>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        MailingList,
+        // language=SQL
+        r#"
+        SELECT list_id, name
+        FROM lists
+        ORDER BY created_at, name
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve mailing lists.")?;
+    Ok(lists)
+}
+
+#[tracing::instrument(name = "Check that a mailing list exists", skip(transaction))]
+pub async fn list_exists(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"SELECT list_id FROM lists WHERE list_id = $1"#,
+        list_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to look up a mailing list.")?;
+    Ok(row.is_some())
+}
+
+#[tracing::instrument(name = "Insert a mailing list", skip(pool))]
+pub async fn insert_list(pool: &PgPool, name: &ListName) -> Result<Option<Uuid>, anyhow::Error> {
+    let list_id = Uuid::new_v4();
+    let n_inserted_rows = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO lists (list_id, name, created_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        list_id,
+        name.as_ref(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert a mailing list.")?
+    .rows_affected();
+    Ok((n_inserted_rows > 0).then_some(list_id))
+}
+
+#[tracing::instrument(name = "Add subscriber to a mailing list", skip(transaction))]
+pub async fn add_subscriber_to_list(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO list_subscriptions (list_id, subscriber_id, subscribed_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        list_id,
+        subscriber_id,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Check mailing list membership", skip(transaction))]
+pub async fn is_subscribed_to_list(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT subscriber_id FROM list_subscriptions
+        WHERE list_id = $1 AND subscriber_id = $2
+        "#,
+        list_id,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(row.is_some())
+}
+
+/// Renders `<option>` elements for a list selector; list names are validated by `ListName`.
+pub fn list_options_html(lists: &[MailingList], selected: Option<Uuid>) -> String {
+    let mut html = String::new();
+    for list in lists {
+        let is_selected = if Some(list.list_id) == selected {
+            " selected"
+        } else {
+            ""
+        };
+        writeln!(
+            html,
+            r#"<option value="{}"{is_selected}>{}</option>"#,
+            list.list_id, list.name
+        )
+        .unwrap();
+    }
+    html
+}
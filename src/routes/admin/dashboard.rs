@@ -1,20 +1,46 @@
-use crate::authentication::UserId;
+use crate::authentication::{Permission, Role, UserId};
+use crate::domain::SubscriptionStatus;
 use crate::utils::e500;
-use actix_web::{
-    http::header::ContentType,
-    web, HttpResponse,
-};
+use actix_web::{http::header::ContentType, web, HttpResponse};
 use anyhow::Context;
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
+use std::fmt::Write;
 use uuid::Uuid;
 
+const GROWTH_DAYS: i32 = 30;
+
+struct DailyGrowth {
+    day: DateTime<Utc>,
+    confirmations: i64,
+    churned: i64,
+}
+
 pub async fn admin_dashboard(
     user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
     pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let manage_users = if role.can(Permission::ManageUsers) {
+        r#"<li><a href="/admin/users">Manage users</a></li>"#
+    } else {
+        ""
+    };
     let username = get_username(*user_id.into_inner(), &pool)
         .await
         .map_err(e500)?;
+    let mut growth_html = String::new();
+    for d in get_list_growth(&pool).await.map_err(e500)? {
+        writeln!(
+            growth_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:+}</td></tr>",
+            d.day.format("%Y-%m-%d"),
+            d.confirmations,
+            d.churned,
+            d.confirmations - d.churned,
+        )
+        .unwrap();
+    }
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -30,13 +56,25 @@ pub async fn admin_dashboard(
     <p>Available actions:</p>
     <ol>
         <li><a href="/admin/password">Change password</a></li>
+        <li><a href="/admin/two-factor">Two-factor authentication</a></li>
+        <li><a href="/admin/sessions">Active sessions</a></li>
         <li><a href="/admin/newsletters">Submit new issue</a></li>
+        <li><a href="/admin/newsletters/opens">Issue analytics</a></li>
+        <li><a href="/admin/lists">Manage mailing lists</a></li>
+        <li><a href="/admin/segments">Manage segments</a></li>
+        <li><a href="/admin/subscribers">Manage subscribers</a></li>
+        {manage_users}
         <li>
             <form name="logoutForm" action="/admin/logout" method="post">
                 <input type="submit" value="Logout">
             </form>
         </li>
     </ol>
+    <h2>List growth (last {GROWTH_DAYS} days)</h2>
+    <table>
+        <tr><th>Day</th><th>Confirmations</th><th>Churn</th><th>Net</th></tr>
+        {growth_html}
+    </table>
 </body>
 </html>"#
         )))
@@ -58,3 +96,45 @@ pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow
     .context("Failed to perform a query to retrieve a username.")?;
     Ok(row.username)
 }
+
+/// Churn counts subscribers who unsubscribed, bounced or complained.
+const CHURNED: [SubscriptionStatus; 3] = [
+    SubscriptionStatus::Unsubscribed,
+    SubscriptionStatus::Bounced,
+    SubscriptionStatus::Complained,
+];
+
+#[tracing::instrument(name = "Get list growth", skip(pool))]
+async fn get_list_growth(pool: &PgPool) -> Result<Vec<DailyGrowth>, anyhow::Error> {
+    let growth = sqlx::query_as!(
+        DailyGrowth,
+        // language=SQL
+        r#"
+        SELECT
+            days.day AS "day!",
+            count(*) FILTER (
+                WHERE subscription_status_changes.status = $2
+            ) AS "confirmations!",
+            count(*) FILTER (
+                WHERE subscription_status_changes.status = ANY($3)
+            ) AS "churned!"
+        FROM generate_series(
+            date_trunc('day', now()) - make_interval(days => $1 - 1),
+            date_trunc('day', now()),
+            interval '1 day'
+        ) AS days (day)
+        LEFT JOIN subscription_status_changes
+            ON subscription_status_changes.changed_at >= days.day AND
+               subscription_status_changes.changed_at < days.day + interval '1 day'
+        GROUP BY days.day
+        ORDER BY days.day DESC
+        "#,
+        GROWTH_DAYS,
+        SubscriptionStatus::Confirmed as SubscriptionStatus,
+        &CHURNED as &[SubscriptionStatus],
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to compute list growth.")?;
+    Ok(growth)
+}
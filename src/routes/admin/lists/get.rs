@@ -0,0 +1,52 @@
+use crate::lists::get_lists;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn lists_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut lists_html = String::new();
+    for list in get_lists(&pool).await.map_err(e500)? {
+        writeln!(lists_html, "<li>{}</li>", list.name).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Mailing lists</title>
+</head>
+<body>
+    {msg_html}
+    <ul>
+        {lists_html}
+    </ul>
+    <form action="/admin/lists" method="post">
+        <label>Name
+            <input
+                type="text"
+                placeholder="Enter the list name"
+                name="name"
+            >
+        </label>
+        <br>
+        <button type="submit">Create list</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
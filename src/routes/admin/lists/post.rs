@@ -0,0 +1,31 @@
+use crate::domain::ListName;
+use crate::lists::insert_list;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+}
+
+#[tracing::instrument(name = "Create a mailing list", skip_all)]
+pub async fn create_list(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = match ListName::parse(form.0.name) {
+        Ok(name) => name,
+        Err(_) => {
+            FlashMessage::error("Please enter a list name without special characters.").send();
+            return Ok(see_other("/admin/lists"));
+        }
+    };
+    match insert_list(&pool, &name).await.map_err(e500)? {
+        Some(_) => FlashMessage::info(format!("The list {} has been created.", name.as_ref())),
+        None => FlashMessage::error(format!("A list named {} already exists.", name.as_ref())),
+    }
+    .send();
+    Ok(see_other("/admin/lists"))
+}
@@ -1,9 +1,21 @@
 mod dashboard;
+mod lists;
 mod logout;
 mod newsletters;
 mod password;
+mod segments;
+mod sessions;
+mod subscribers;
+mod two_factor;
+mod users;
 
-pub use dashboard::admin_dashboard;
+pub use dashboard::{admin_dashboard, get_username};
+pub use lists::*;
 pub use logout::log_out;
 pub use newsletters::*;
 pub use password::*;
+pub use segments::*;
+pub use sessions::*;
+pub use subscribers::*;
+pub use two_factor::*;
+pub use users::*;
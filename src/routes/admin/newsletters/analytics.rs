@@ -0,0 +1,395 @@
+use crate::authentication::{Permission, Role};
+use crate::domain::{IssueState, SubscriptionStatus};
+use crate::subject_line_tests::{get_subject_line_test, get_variant_results, Metric};
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct Issue {
+    title: String,
+    published_at: DateTime<Utc>,
+    track_opens: bool,
+    state: IssueState,
+}
+
+struct IssueStats {
+    enqueued: i64,
+    queued: i64,
+    cancelled: i64,
+    sent: i64,
+    failed: i64,
+    bounced: i64,
+    opened: i64,
+    clicked: i64,
+    unsubscribed: i64,
+}
+
+struct DeliveryProgress {
+    minute: DateTime<Utc>,
+    sent: i64,
+    failed: i64,
+}
+
+struct LinkClicks {
+    url: String,
+    clicks: i64,
+    subscribers: i64,
+}
+
+pub async fn issue_analytics(
+    newsletter_issue_id: web::Path<Uuid>,
+    flash_messages: IncomingFlashMessages,
+    role: web::ReqData<Role>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let Some(issue) = get_issue(&pool, newsletter_issue_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let stats = get_issue_stats(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    let IssueStats {
+        enqueued,
+        queued,
+        cancelled,
+        sent,
+        failed,
+        bounced,
+        opened,
+        clicked,
+        unsubscribed,
+    } = stats;
+    // Subscribers who stopped being confirmed before their turn came are dropped from the queue.
+    let skipped = (enqueued - queued - cancelled - sent - failed).max(0);
+    let opened = if issue.track_opens {
+        opened.to_string()
+    } else {
+        "not tracked".into()
+    };
+
+    let mut progress_html = String::new();
+    let mut processed = 0;
+    for p in get_delivery_progress(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        processed += p.sent + p.failed;
+        writeln!(
+            progress_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{processed} / {enqueued}</td></tr>",
+            p.minute.format("%Y-%m-%d %H:%M"),
+            p.sent,
+            p.failed,
+        )
+        .unwrap();
+    }
+    if progress_html.is_empty() {
+        progress_html.push_str(r#"<tr><td colspan="4">Nothing delivered yet.</td></tr>"#);
+    }
+    let mut links_html = String::new();
+    for link in get_link_clicks(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        writeln!(
+            links_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            html_escape::encode_text(&link.url),
+            link.clicks,
+            link.subscribers,
+        )
+        .unwrap();
+    }
+    if links_html.is_empty() {
+        links_html.push_str(r#"<tr><td colspan="3">No clicks yet.</td></tr>"#);
+    }
+    let subject_line_test_html = subject_line_test_html(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut actions_html = String::new();
+    for (next, action, label) in [
+        (IssueState::Paused, "pause", "Pause"),
+        (IssueState::Sending, "resume", "Resume"),
+        (IssueState::Cancelled, "cancel", "Cancel"),
+    ] {
+        if role.can(Permission::PublishIssues) && issue.state.can_transition_to(next) {
+            writeln!(
+                actions_html,
+                r#"<form action="/admin/newsletters/{newsletter_issue_id}/{action}" method="post"><button type="submit">{label}</button></form>"#
+            )
+            .unwrap();
+        }
+    }
+    let state = issue.state;
+    let title = html_escape::encode_text(&issue.title);
+    let published_at = issue.published_at.format("%Y-%m-%d %H:%M");
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Issue analytics</title>
+</head>
+<body>
+    {msg_html}
+    <h1>{title}</h1>
+    <p>Published at {published_at}</p>
+    <p>State: {state}</p>
+    {actions_html}
+    <table>
+        <tr><th>Enqueued</th><td>{enqueued}</td></tr>
+        <tr><th>Still queued</th><td>{queued}</td></tr>
+        <tr><th>Cancelled</th><td>{cancelled}</td></tr>
+        <tr><th>Sent</th><td>{sent}</td></tr>
+        <tr><th>Failed</th><td>{failed}</td></tr>
+        <tr><th>Skipped</th><td>{skipped}</td></tr>
+        <tr><th>Bounced</th><td>{bounced}</td></tr>
+        <tr><th>Opened</th><td>{opened}</td></tr>
+        <tr><th>Clicked</th><td>{clicked}</td></tr>
+        <tr><th>Unsubscribed</th><td>{unsubscribed}</td></tr>
+    </table>
+    {subject_line_test_html}
+    <h2>Delivery progress</h2>
+    <table>
+        <tr><th>Minute</th><th>Sent</th><th>Failed</th><th>Processed</th></tr>
+        {progress_html}
+    </table>
+    <h2>Clicks per link</h2>
+    <table>
+        <tr><th>Link</th><th>Clicks</th><th>Subscribers</th></tr>
+        {links_html}
+    </table>
+    <p><a href="/admin/newsletters/opens">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+/// Empty if the issue was sent without testing subject lines.
+async fn subject_line_test_html(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<String, anyhow::Error> {
+    let Some(test) = get_subject_line_test(pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve the subject line test.")?
+    else {
+        return Ok(String::new());
+    };
+    let results = get_variant_results(pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve subject line test results.")?;
+    let mut rows_html = String::new();
+    for result in &results {
+        let rate = |metric| {
+            if result.sent == 0 {
+                "-".to_string()
+            } else {
+                format!("{:.1}%", 100.0 * result.rate(metric))
+            }
+        };
+        let winner = if test.winning_variant_id == Some(result.variant_id) {
+            "Winner"
+        } else {
+            ""
+        };
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{winner}</td></tr>",
+            html_escape::encode_text(&result.subject),
+            result.sent,
+            result.opened,
+            rate(Metric::OpenRate),
+            result.clicked,
+            rate(Metric::ClickRate),
+        )
+        .unwrap();
+    }
+    let outcome = if test.winning_variant_id.is_some() {
+        "The winner went out to the rest of the audience.".to_string()
+    } else {
+        format!(
+            "The winner will be picked at {}.",
+            test.decide_at.format("%Y-%m-%d %H:%M")
+        )
+    };
+    Ok(format!(
+        // language=HTML
+        r#"<h2>Subject line test</h2>
+    <p>Sent to {}% of the audience, winner picked by {}. {outcome}</p>
+    <table>
+        <tr><th>Subject</th><th>Sent</th><th>Opened</th><th>Open rate</th><th>Clicked</th><th>Click rate</th><th></th></tr>
+        {rows_html}
+    </table>"#,
+        test.sample_percent, test.metric,
+    ))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<Issue>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        Issue,
+        // language=SQL
+        r#"
+        SELECT title, published_at, track_opens, state AS "state: IssueState"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the newsletter issue.")?;
+    Ok(issue)
+}
+
+/// Bounces and unsubscribes are attributed to the last issue a subscriber was sent
+/// before their status changed.
+#[tracing::instrument(skip(pool))]
+async fn get_issue_stats(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<IssueStats, anyhow::Error> {
+    let stats = sqlx::query_as!(
+        IssueStats,
+        // language=SQL
+        r#"
+        WITH delivery_windows AS (
+            SELECT
+                newsletter_issue_id,
+                subscriber_id,
+                delivered_at,
+                succeeded,
+                lead(delivered_at) OVER (
+                    PARTITION BY subscriber_id ORDER BY delivered_at
+                ) AS next_delivered_at
+            FROM issue_deliveries
+            WHERE subscriber_id IN (
+                SELECT subscriber_id FROM issue_deliveries WHERE newsletter_issue_id = $1
+            )
+        ),
+        status_changes_after_delivery AS (
+            SELECT subscription_status_changes.status
+            FROM delivery_windows
+            JOIN subscription_status_changes
+                ON subscription_status_changes.subscriber_id = delivery_windows.subscriber_id
+            WHERE
+                delivery_windows.newsletter_issue_id = $1 AND
+                delivery_windows.succeeded AND
+                subscription_status_changes.changed_at >= delivery_windows.delivered_at AND
+                (
+                    delivery_windows.next_delivered_at IS NULL OR
+                    subscription_status_changes.changed_at < delivery_windows.next_delivered_at
+                )
+        )
+        SELECT
+            (
+                SELECT recipient_count FROM newsletter_issues WHERE newsletter_issue_id = $1
+            ) AS "enqueued!",
+            (
+                SELECT count(*) FROM issue_delivery_queue WHERE newsletter_issue_id = $1
+            ) AS "queued!",
+            (
+                SELECT count(*) FROM cancelled_deliveries WHERE newsletter_issue_id = $1
+            ) AS "cancelled!",
+            (
+                SELECT count(*) FROM issue_deliveries
+                WHERE newsletter_issue_id = $1 AND succeeded
+            ) AS "sent!",
+            (
+                SELECT count(*) FROM issue_deliveries
+                WHERE newsletter_issue_id = $1 AND NOT succeeded
+            ) AS "failed!",
+            (
+                SELECT count(*) FROM status_changes_after_delivery WHERE status = $2
+            ) AS "bounced!",
+            (
+                SELECT count(*) FROM issue_opens WHERE newsletter_issue_id = $1
+            ) AS "opened!",
+            (
+                SELECT count(DISTINCT subscriber_id) FROM issue_clicks
+                WHERE newsletter_issue_id = $1
+            ) AS "clicked!",
+            (
+                SELECT count(*) FROM status_changes_after_delivery WHERE status = $3
+            ) AS "unsubscribed!"
+        "#,
+        newsletter_issue_id,
+        SubscriptionStatus::Bounced as SubscriptionStatus,
+        SubscriptionStatus::Unsubscribed as SubscriptionStatus,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to compute issue statistics.")?;
+    Ok(stats)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_delivery_progress(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<DeliveryProgress>, anyhow::Error> {
+    let progress = sqlx::query_as!(
+        DeliveryProgress,
+        // language=SQL
+        r#"
+        SELECT
+            date_trunc('minute', delivered_at) AS "minute!",
+            count(*) FILTER (WHERE succeeded) AS "sent!",
+            count(*) FILTER (WHERE NOT succeeded) AS "failed!"
+        FROM issue_deliveries
+        WHERE newsletter_issue_id = $1
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve delivery progress.")?;
+    Ok(progress)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_link_clicks(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<LinkClicks>, anyhow::Error> {
+    let links = sqlx::query_as!(
+        LinkClicks,
+        // language=SQL
+        r#"
+        SELECT
+            url,
+            count(*) AS "clicks!",
+            count(DISTINCT subscriber_id) AS "subscribers!"
+        FROM issue_clicks
+        WHERE newsletter_issue_id = $1
+        GROUP BY url
+        ORDER BY 2 DESC, url
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve link clicks.")?;
+    Ok(links)
+}
@@ -1,15 +1,94 @@
+use crate::lists::{get_lists, list_options_html, DEFAULT_LIST_ID};
+use crate::segments::{count_recipients, get_segments, segment_options_html};
+use crate::subject_line_tests::Metric;
+use crate::utils::{e400, e500};
 use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
 use std::fmt::Write;
+use uuid::Uuid;
+
+/// Set when the editor asks for a recipient preview: the form is submitted back to us
+/// so we can render it again, filled in, together with the recipient count.
+#[derive(serde::Deserialize)]
+pub struct PreviewParameters {
+    preview: Option<String>,
+    list_id: Option<Uuid>,
+    segment_id: Option<String>,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text_content: String,
+    #[serde(default)]
+    html_content: String,
+    track_opens: Option<String>,
+    #[serde(default)]
+    subject_variants: String,
+    ab_test_sample_percent: Option<String>,
+    ab_test_wait_hours: Option<String>,
+    ab_test_metric: Option<String>,
+}
 
 pub async fn publish_newsletter_form(
+    parameters: web::Query<PreviewParameters>,
     flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let parameters = parameters.into_inner();
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
+    let segment_id = parse_segment_id(parameters.segment_id.as_deref()).map_err(e400)?;
+    if parameters.preview.is_some() {
+        let n_recipients = count_recipients(
+            &pool,
+            parameters.list_id.unwrap_or(DEFAULT_LIST_ID),
+            segment_id,
+        )
+        .await
+        .map_err(e500)?;
+        writeln!(
+            msg_html,
+            "<p><i>This issue would go out to {n_recipients} subscriber(s).</i></p>"
+        )
+        .unwrap();
+    }
+    let lists = get_lists(&pool).await.map_err(e500)?;
+    let list_options = list_options_html(&lists, parameters.list_id);
+    let segments = get_segments(&pool).await.map_err(e500)?;
+    let segment_options = segment_options_html(&segments, segment_id);
+    let title = html_escape::encode_double_quoted_attribute(&parameters.title);
+    let text_content = html_escape::encode_text(&parameters.text_content);
+    let html_content = html_escape::encode_text(&parameters.html_content);
+    // Opens are tracked unless the editor unticked the box before previewing.
+    let track_opens = if parameters.preview.is_none() || parameters.track_opens.is_some() {
+        "checked"
+    } else {
+        ""
+    };
+    let subject_variants = html_escape::encode_text(&parameters.subject_variants);
+    let sample_percent = html_escape::encode_double_quoted_attribute(
+        parameters.ab_test_sample_percent.as_deref().unwrap_or("20"),
+    );
+    let wait_hours = html_escape::encode_double_quoted_attribute(
+        parameters.ab_test_wait_hours.as_deref().unwrap_or("4"),
+    );
+    let mut metric_options = String::new();
+    for metric in [Metric::OpenRate, Metric::ClickRate] {
+        let selected = if parameters.ab_test_metric.as_deref() == Some(metric.as_str()) {
+            " selected"
+        } else {
+            ""
+        };
+        writeln!(
+            metric_options,
+            r#"<option value="{}"{selected}>{metric}</option>"#,
+            metric.as_str()
+        )
+        .unwrap();
+    }
 
     let idempotency_key = uuid::Uuid::new_v4();
     Ok(HttpResponse::Ok()
@@ -25,11 +104,27 @@ pub async fn publish_newsletter_form(
 <body>
     {msg_html}
     <form action="/admin/newsletters" method="post">
+        <label>Mailing list:<br>
+            <select name="list_id">
+                {list_options}
+            </select>
+        </label>
+        <br>
+        <label>Segment:<br>
+            <select name="segment_id">
+                {segment_options}
+            </select>
+        </label>
+        <button type="submit" formmethod="get" name="preview" value="true">
+            Preview recipients
+        </button>
+        <br>
         <label>Title:<br>
             <input
                 type="text"
                 placeholder="Enter the issue title"
                 name="title"
+                value="{title}"
             >
         </label>
         <br>
@@ -39,7 +134,7 @@ pub async fn publish_newsletter_form(
                 name="text_content"
                 rows="20"
                 cols="50"
-            ></textarea>
+            >{text_content}</textarea>
         </label>
         <br>
         <label>HTML content:<br>
@@ -48,9 +143,38 @@ pub async fn publish_newsletter_form(
                 name="html_content"
                 rows="20"
                 cols="50"
-            ></textarea>
+            >{html_content}</textarea>
+        </label>
+        <br>
+        <label>
+            <input type="checkbox" name="track_opens" value="true" {track_opens}>
+            Track opens
         </label>
         <br>
+        <fieldset>
+            <legend>Subject line test</legend>
+            <label>Subject variants, one per line (leave empty to use the title):<br>
+                <textarea
+                    name="subject_variants"
+                    rows="4"
+                    cols="50"
+                >{subject_variants}</textarea>
+            </label>
+            <br>
+            <label>Test audience (percent):
+                <input type="number" name="ab_test_sample_percent" min="1" max="100" value="{sample_percent}">
+            </label>
+            <br>
+            <label>Hours to wait before sending the winner:
+                <input type="number" name="ab_test_wait_hours" min="0" max="168" value="{wait_hours}">
+            </label>
+            <br>
+            <label>Pick the winner by:
+                <select name="ab_test_metric">
+                    {metric_options}
+                </select>
+            </label>
+        </fieldset>
         <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
         <button type="submit">Publish</button>
     </form>
@@ -59,3 +183,11 @@ pub async fn publish_newsletter_form(
 </html>"#,
         )))
 }
+
+/// The segment selector submits an empty value for "all subscribers".
+pub fn parse_segment_id(segment_id: Option<&str>) -> Result<Option<Uuid>, uuid::Error> {
+    match segment_id.unwrap_or_default() {
+        "" => Ok(None),
+        s => Uuid::parse_str(s).map(Some),
+    }
+}
@@ -1,5 +1,11 @@
+mod analytics;
 mod get;
+mod opens;
 mod post;
+mod state;
 
-pub use get::publish_newsletter_form;
+pub use analytics::issue_analytics;
+pub use get::{parse_segment_id, publish_newsletter_form};
+pub use opens::open_rates;
 pub use post::publish_newsletter;
+pub use state::{cancel_issue, pause_issue, resume_issue};
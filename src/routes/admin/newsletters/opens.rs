@@ -0,0 +1,121 @@
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct IssueOpens {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+    track_opens: bool,
+    delivered: i64,
+    unique_opens: i64,
+    total_opens: i64,
+}
+
+impl IssueOpens {
+    fn open_rate(&self) -> String {
+        if !self.track_opens {
+            "not tracked".into()
+        } else if self.delivered == 0 {
+            "-".into()
+        } else {
+            format!(
+                "{:.1}%",
+                100.0 * self.unique_opens as f64 / self.delivered as f64
+            )
+        }
+    }
+}
+
+pub async fn open_rates(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let issues = get_issue_opens(&pool).await.map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for issue in &issues {
+        writeln!(
+            rows_html,
+            r#"<tr><td><a href="/admin/newsletters/{}">{}</a></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+            issue.newsletter_issue_id,
+            html_escape::encode_text(&issue.title),
+            issue.published_at.format("%Y-%m-%d %H:%M"),
+            issue.delivered,
+            issue.unique_opens,
+            issue.total_opens,
+            issue.open_rate(),
+        )
+        .unwrap();
+    }
+    if issues.is_empty() {
+        rows_html.push_str(r#"<tr><td colspan="6">No issues have been published yet.</td></tr>"#);
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Open rates</title>
+</head>
+<body>
+    <table>
+        <tr>
+            <th>Issue</th>
+            <th>Published at</th>
+            <th>Delivered</th>
+            <th>Unique opens</th>
+            <th>Total opens</th>
+            <th>Open rate</th>
+        </tr>
+        {rows_html}
+    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Get open rates", skip(pool))]
+async fn get_issue_opens(pool: &PgPool) -> Result<Vec<IssueOpens>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        IssueOpens,
+        // language=SQL
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            published_at,
+            track_opens,
+            (
+                SELECT count(*)
+                FROM issue_deliveries
+                WHERE
+                    issue_deliveries.newsletter_issue_id = newsletter_issues.newsletter_issue_id AND
+                    succeeded
+            ) AS "delivered!",
+            (
+                SELECT count(*)
+                FROM issue_opens
+                WHERE issue_opens.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+            ) AS "unique_opens!",
+            (
+                SELECT coalesce(sum(open_count), 0)
+                FROM issue_opens
+                WHERE issue_opens.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+            ) AS "total_opens!"
+        FROM newsletter_issues
+        ORDER BY published_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve open rates.")?;
+    Ok(issues)
+}
@@ -1,10 +1,16 @@
 use crate::authentication::UserId;
+use crate::domain::SubscriptionStatus;
 use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
+use crate::lists::{list_exists, DEFAULT_LIST_ID};
+use crate::routes::parse_segment_id;
+use crate::segments::segment_exists;
+use crate::subject_line_tests::{start_subject_line_test, Metric, SubjectLineTest};
 use crate::utils::{e400, e500, see_other};
 use actix_web::web::ReqData;
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
+use chrono::Utc;
 use sqlx::{Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
@@ -14,6 +20,18 @@ pub struct FormData {
     text_content: String,
     html_content: String,
     idempotency_key: String,
+    list_id: Option<Uuid>,
+    segment_id: Option<String>,
+    #[serde(default)]
+    track_opens: bool,
+    #[serde(default)]
+    subject_variants: String,
+    #[serde(default)]
+    ab_test_sample_percent: String,
+    #[serde(default)]
+    ab_test_wait_hours: String,
+    #[serde(default)]
+    ab_test_metric: String,
 }
 
 #[tracing::instrument(
@@ -32,7 +50,30 @@ pub async fn publish_newsletter(
         text_content,
         html_content,
         idempotency_key,
+        list_id,
+        segment_id,
+        track_opens,
+        subject_variants,
+        ab_test_sample_percent,
+        ab_test_wait_hours,
+        ab_test_metric,
     } = form.0;
+    let list_id = list_id.unwrap_or(DEFAULT_LIST_ID);
+    let segment_id = parse_segment_id(segment_id.as_deref()).map_err(e400)?;
+    let subject_line_test = SubjectLineTest::parse(
+        &subject_variants,
+        &ab_test_sample_percent,
+        &ab_test_wait_hours,
+        &ab_test_metric,
+    )
+    .map_err(e400)?;
+    if let Some(test) = &subject_line_test {
+        if test.metric == Metric::OpenRate && !track_opens {
+            return Err(e400(
+                "Picking a winner by open rate requires open tracking to be enabled.",
+            ));
+        }
+    }
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
     let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
         .await
@@ -44,14 +85,45 @@ pub async fn publish_newsletter(
             return Ok(saved_response);
         }
     };
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
-        .await
-        .context("Failed to store newsletter issue details")
-        .map_err(e500)?;
-    enqueue_delivery_tasks(&mut transaction, issue_id)
-        .await
-        .context("Failed to enqueue delivery tasks")
-        .map_err(e500)?;
+    if !list_exists(&mut transaction, list_id).await.map_err(e500)? {
+        return Err(e400(format!("{list_id} is not a known mailing list.")));
+    }
+    if let Some(segment_id) = segment_id {
+        if !segment_exists(&mut transaction, segment_id)
+            .await
+            .map_err(e500)?
+        {
+            return Err(e400(format!("{segment_id} is not a known segment.")));
+        }
+    }
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        list_id,
+        segment_id,
+        &title,
+        &text_content,
+        &html_content,
+        track_opens,
+    )
+    .await
+    .context("Failed to store newsletter issue details")
+    .map_err(e500)?;
+    enqueue_delivery_tasks(
+        &mut transaction,
+        issue_id,
+        list_id,
+        segment_id,
+        subject_line_test.is_some(),
+    )
+    .await
+    .context("Failed to enqueue delivery tasks")
+    .map_err(e500)?;
+    if let Some(test) = &subject_line_test {
+        start_subject_line_test(&mut transaction, issue_id, test)
+            .await
+            .context("Failed to start the subject line test")
+            .map_err(e500)?;
+    }
     let response = see_other("/admin/newsletters");
     let response = save_response(transaction, &idempotency_key, *user_id, response)
         .await
@@ -67,9 +139,12 @@ fn success_message() -> FlashMessage {
 #[tracing::instrument(skip_all)]
 async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    segment_id: Option<Uuid>,
     title: &str,
     text_content: &str,
     html_content: &str,
+    track_opens: bool,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
     let query = sqlx::query!(
@@ -80,14 +155,21 @@ async fn insert_newsletter_issue(
             title,
             text_content,
             html_content,
-            published_at
+            published_at,
+            list_id,
+            segment_id,
+            track_opens
         )
-        VALUES ($1, $2, $3, $4, now())
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         "#,
         newsletter_issue_id,
         title,
         text_content,
-        html_content
+        html_content,
+        Utc::now(),
+        list_id,
+        segment_id,
+        track_opens,
     );
     transaction.execute(query).await?;
     Ok(newsletter_issue_id)
@@ -97,19 +179,39 @@ async fn insert_newsletter_issue(
 async fn enqueue_delivery_tasks(
     transaction: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
+    list_id: Uuid,
+    segment_id: Option<Uuid>,
+    held: bool,
 ) -> Result<(), sqlx::Error> {
     let query = sqlx::query!(
         // language=SQL
         r#"
         INSERT INTO issue_delivery_queue (
             newsletter_issue_id,
-            subscriber_email
+            subscriber_id,
+            held
         )
-        SELECT $1, email
+        SELECT $1, subscriptions.id, $5
         FROM subscriptions
-        WHERE status = 'confirmed'
+        JOIN list_subscriptions
+            ON list_subscriptions.subscriber_id = subscriptions.id
+        WHERE
+            list_subscriptions.list_id = $2 AND
+            subscriptions.status = $3 AND
+            ($4::uuid IS NULL OR subscriptions.id IN (SELECT subscriber_id FROM segment_members($4)))
         "#,
         newsletter_issue_id,
+        list_id,
+        SubscriptionStatus::Confirmed as SubscriptionStatus,
+        segment_id,
+        held,
+    );
+    let recipient_count = transaction.execute(query).await?.rows_affected();
+    let query = sqlx::query!(
+        // language=SQL
+        r#"UPDATE newsletter_issues SET recipient_count = $2 WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id,
+        recipient_count as i64,
     );
     transaction.execute(query).await?;
     Ok(())
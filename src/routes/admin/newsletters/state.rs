@@ -0,0 +1,153 @@
+use crate::domain::IssueState;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[tracing::instrument(name = "Pause a newsletter issue", skip(pool))]
+pub async fn pause_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    change_state(
+        &pool,
+        newsletter_issue_id.into_inner(),
+        IssueState::Paused,
+        "The issue has been paused.",
+    )
+    .await
+}
+
+#[tracing::instrument(name = "Resume a newsletter issue", skip(pool))]
+pub async fn resume_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    change_state(
+        &pool,
+        newsletter_issue_id.into_inner(),
+        IssueState::Sending,
+        "The issue has been resumed.",
+    )
+    .await
+}
+
+#[tracing::instrument(name = "Cancel a newsletter issue", skip(pool))]
+pub async fn cancel_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    change_state(
+        &pool,
+        newsletter_issue_id.into_inner(),
+        IssueState::Cancelled,
+        "The issue has been cancelled.",
+    )
+    .await
+}
+
+async fn change_state(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    next: IssueState,
+    success_message: &str,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let Some(current) = get_issue_state(&mut transaction, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve the issue state.")
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    match current.transition_to(next) {
+        Ok(next) => {
+            update_issue_state(&mut transaction, newsletter_issue_id, next)
+                .await
+                .context("Failed to update the issue state.")
+                .map_err(e500)?;
+            if next == IssueState::Cancelled {
+                cancel_remaining_deliveries(&mut transaction, newsletter_issue_id)
+                    .await
+                    .context("Failed to cancel the remaining deliveries.")
+                    .map_err(e500)?;
+            }
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit SQL transaction to update an issue state.")
+                .map_err(e500)?;
+            FlashMessage::info(success_message).send();
+        }
+        Err(e) => FlashMessage::error(e).send(),
+    }
+    Ok(see_other(&format!(
+        "/admin/newsletters/{newsletter_issue_id}"
+    )))
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn get_issue_state(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<IssueState>, sqlx::Error> {
+    let r = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT state AS "state: IssueState"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        FOR UPDATE
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(r.map(|r| r.state))
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn update_issue_state(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    state: IssueState,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"UPDATE newsletter_issues SET state = $2 WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id,
+        state as IssueState,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// Takes the tasks the worker has not picked up yet off the queue, keeping a record of them.
+#[tracing::instrument(skip(transaction))]
+async fn cancel_remaining_deliveries(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        WITH removed AS (
+            DELETE FROM issue_delivery_queue
+            WHERE newsletter_issue_id = $1
+            RETURNING subscriber_id
+        )
+        INSERT INTO cancelled_deliveries (newsletter_issue_id, subscriber_id, cancelled_at)
+        SELECT $1, subscriber_id, now()
+        FROM removed
+        "#,
+        newsletter_issue_id,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
@@ -0,0 +1,261 @@
+use crate::authentication::{
+    generate_token, hash_token, validate_credentials, AuthError, Credentials,
+    PasswordHashingSettings, UserId,
+};
+use crate::domain::{SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::routes::admin::dashboard::get_username;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::SecretString;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// Confirmation links are short-lived: whoever follows one controls password resets.
+const EMAIL_TOKEN_VALIDITY_MINUTES: i32 = 60;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+    current_password: SecretString,
+}
+
+/// Password reset links are sent to this address, so changing it takes the
+/// current password, and only happens once the new address has been confirmed.
+#[tracing::instrument(name = "Change account email", skip_all, fields(user_id=%&*user_id))]
+pub async fn change_account_email(
+    form: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    password_hashing: web::Data<PasswordHashingSettings>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = **user_id;
+    let FormData {
+        email,
+        current_password,
+    } = form.0;
+    let username = get_username(user_id, &pool).await.map_err(e500)?;
+    let credentials = Credentials {
+        username: username.clone(),
+        password: current_password,
+    };
+    if let Err(e) = validate_credentials(credentials, &pool, &password_hashing).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                FlashMessage::error("The current password is incorrect.").send();
+                Ok(see_other("/admin/password"))
+            }
+            AuthError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+    let Ok(email) = SubscriberEmail::parse(email) else {
+        FlashMessage::error("Please enter a valid email address.").send();
+        return Ok(see_other("/admin/password"));
+    };
+    if is_email_taken(&pool, user_id, &email).await.map_err(e500)? {
+        FlashMessage::error("That email address belongs to another user.").send();
+        return Ok(see_other("/admin/password"));
+    }
+
+    let token = generate_token();
+    store_email_token(&pool, user_id, &hash_token(&token), &email)
+        .await
+        .map_err(e500)?;
+    send_email_confirmation(&email_client, &email, &username, &base_url.0, &token)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info(
+        "We have sent a confirmation link to your new address. \
+        Your address will change once you click it.",
+    )
+    .send();
+    Ok(see_other("/admin/password"))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn is_email_taken(
+    pool: &PgPool,
+    user_id: Uuid,
+    email: &SubscriberEmail,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM users WHERE lower(email) = lower($2) AND user_id <> $1
+        ) AS "taken!"
+        "#,
+        user_id,
+        email.as_ref(),
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to check whether an email address is taken.")?;
+    Ok(row.taken)
+}
+
+#[tracing::instrument(skip(pool, token_hash))]
+async fn store_email_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    token_hash: &str,
+    new_email: &SubscriberEmail,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO account_email_tokens (token_hash, user_id, new_email, created_at, expires_at)
+        VALUES ($1, $2, $3, now(), now() + make_interval(mins => $4))
+        "#,
+        token_hash,
+        user_id,
+        new_email.as_ref(),
+        EMAIL_TOKEN_VALIDITY_MINUTES,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store an account email token.")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_email_confirmation(
+    email_client: &EmailClient,
+    new_email: &SubscriberEmail,
+    username: &str,
+    base_url: &str,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    let name = SubscriberName::parse(username.to_string()).map_err(|e| anyhow::anyhow!(e))?;
+    let confirmation_link = format!("{base_url}/account-email/confirm?token={token}");
+    let plain_body = format!(
+        "You asked to receive password reset links for your newsletter admin account \
+        at this address.\n\
+        Visit {confirmation_link} within {EMAIL_TOKEN_VALIDITY_MINUTES} minutes \
+        to confirm the change."
+    );
+    let html_body = format!(
+        "You asked to receive password reset links for your newsletter admin account \
+        at this address.<br />\
+        Click <a href=\"{confirmation_link}\">here</a> within {EMAIL_TOKEN_VALIDITY_MINUTES} \
+        minutes to confirm the change."
+    );
+    email_client
+        .send_email(
+            new_email,
+            &name,
+            "Confirm your new email address",
+            &html_body,
+            &plain_body,
+        )
+        .await
+        .context("Failed to send an account email confirmation.")?;
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmParameters {
+    token: String,
+}
+
+#[tracing::instrument(name = "Confirm account email", skip_all)]
+pub async fn confirm_account_email(
+    parameters: web::Query<ConfirmParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let Some((user_id, new_email)) = use_email_token(&mut transaction, &parameters.token)
+        .await
+        .map_err(e500)?
+    else {
+        FlashMessage::error("The confirmation link is invalid or has expired.").send();
+        return Ok(see_other("/login"));
+    };
+    match update_email(&mut transaction, user_id, &new_email).await {
+        Ok(()) => {}
+        Err(e) if is_unique_violation(&e) => {
+            FlashMessage::error("That email address belongs to another user.").send();
+            return Ok(see_other("/admin/password"));
+        }
+        Err(e) => return Err(e500(e)),
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to change an account email.")
+        .map_err(e500)?;
+    FlashMessage::info("Your email address has been changed.").send();
+    Ok(see_other("/admin/password"))
+}
+
+/// Tokens are single use: a confirmed one is deleted.
+#[tracing::instrument(skip_all)]
+async fn use_email_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+) -> Result<Option<(Uuid, String)>, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"
+        DELETE FROM account_email_tokens
+        WHERE token_hash = $1 AND expires_at > now()
+        RETURNING user_id, new_email
+        "#,
+        hash_token(token),
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to use up an account email token.")?;
+    Ok(row.map(|r| (r.user_id, r.new_email)))
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn update_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    email: &str,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"UPDATE users SET email = $2 WHERE user_id = $1"#,
+        user_id,
+        email,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to update the email address of a user.")?;
+    Ok(())
+}
+
+fn is_unique_violation(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<sqlx::Error>()
+        .and_then(|e| e.as_database_error())
+        .is_some_and(|e| e.is_unique_violation())
+}
+
+/// The address password reset links for `user_id` are sent to, if any.
+#[tracing::instrument(skip(pool))]
+pub async fn get_account_email(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"SELECT email FROM users WHERE user_id = $1"#,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the email address of a user.")?;
+    Ok(row.email)
+}
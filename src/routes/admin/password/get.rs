@@ -1,15 +1,31 @@
+use super::email::get_account_email;
+use crate::authentication::{PasswordPolicy, UserId};
+use crate::utils::e500;
 use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
 use std::fmt::Write;
 
 pub async fn change_password_form(
     flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    password_policy: web::Data<PasswordPolicy>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
+    let email = get_account_email(&pool, **user_id)
+        .await
+        .map_err(e500)?
+        .unwrap_or_default();
+    let email = html_escape::encode_double_quoted_attribute(&email);
+    let PasswordPolicy {
+        min_length,
+        max_length,
+    } = password_policy.get_ref();
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -23,6 +39,8 @@ pub async fn change_password_form(
 </head>
 <body>
     {msg_html}
+    <p>New passwords must be {min_length} to {max_length} characters long, must not be
+        a common password and must not contain your username.</p>
     <form action="/admin/password" method="post">
         <label>Current password
             <input
@@ -50,6 +68,26 @@ pub async fn change_password_form(
         <br>
         <button type="submit">Change password</button>
     </form>
+    <form action="/admin/password/email" method="post">
+        <label>Email address for password resets
+            <input
+                type="email"
+                placeholder="Enter your email address"
+                name="email"
+                value="{email}"
+            >
+        </label>
+        <br>
+        <label>Current password
+            <input
+                type="password"
+                placeholder="Enter current password"
+                name="current_password"
+            >
+        </label>
+        <br>
+        <button type="submit">Save email address</button>
+    </form>
     <p><a href="/admin/dashboard">&lt;- Back</a></p>
 </body>
 </html>"#,
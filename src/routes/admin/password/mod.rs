@@ -1,5 +1,7 @@
+mod email;
 mod get;
 mod post;
 
+pub use email::{change_account_email, confirm_account_email};
 pub use get::change_password_form;
 pub use post::change_password;
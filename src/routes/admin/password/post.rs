@@ -1,5 +1,9 @@
-use crate::authentication::{validate_credentials, AuthError, Credentials, UserId};
+use crate::authentication::{
+    validate_credentials, AuthError, Credentials, NewPassword, PasswordHashingSettings,
+    PasswordPolicy, UserId,
+};
 use crate::routes::admin::dashboard::get_username;
+use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
@@ -17,6 +21,9 @@ pub async fn change_password(
     form: web::Form<FormData>,
     user_id: web::ReqData<UserId>,
     pool: web::Data<PgPool>,
+    password_policy: web::Data<PasswordPolicy>,
+    password_hashing: web::Data<PasswordHashingSettings>,
+    session: TypedSession,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
 
@@ -29,22 +36,35 @@ pub async fn change_password(
     }
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
     let credentials = Credentials {
-        username,
+        username: username.clone(),
         password: form.0.current_password,
     };
-    if let Err(e) = validate_credentials(credentials, &pool).await {
+    if let Err(e) = validate_credentials(credentials, &pool, &password_hashing).await {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password is incorrect.").send();
                 Ok(see_other("/admin/password"))
             }
-            AuthError::UnexpectedError(_) => Err(e500(e).into()),
+            AuthError::UnexpectedError(_) => Err(e500(e)),
         };
     }
 
-    crate::authentication::change_password(*user_id, form.0.new_password, &pool)
-        .await
-        .map_err(e500)?;
+    let new_password = match NewPassword::parse(form.0.new_password, &username, &password_policy) {
+        Ok(new_password) => new_password,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/password"));
+        }
+    };
+    crate::authentication::change_password(
+        *user_id,
+        new_password,
+        session.get_session_id().map_err(e500)?,
+        &pool,
+        &password_hashing,
+    )
+    .await
+    .map_err(e500)?;
     FlashMessage::info("Your password has been changed.").send();
     Ok(see_other("/admin/password"))
 }
@@ -0,0 +1,86 @@
+use crate::lists::{get_lists, list_options_html};
+use crate::segments::get_segments;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn segments_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut segments_html = String::new();
+    for segment in get_segments(&pool).await.map_err(e500)? {
+        writeln!(
+            segments_html,
+            "<li>{}: {}</li>",
+            html_escape::encode_text(&segment.name),
+            html_escape::encode_text(&segment.describe()),
+        )
+        .unwrap();
+    }
+    let lists = get_lists(&pool).await.map_err(e500)?;
+    let list_options = list_options_html(&lists, None);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Segments</title>
+</head>
+<body>
+    {msg_html}
+    <ul>
+        {segments_html}
+    </ul>
+    <form action="/admin/segments" method="post">
+        <label>Name
+            <input
+                type="text"
+                placeholder="Enter the segment name"
+                name="name"
+            >
+        </label>
+        <br>
+        <p>Leave a rule empty to match everyone.</p>
+        <label>Subscribed on or after
+            <input type="date" name="subscribed_after">
+        </label>
+        <br>
+        <label>Subscribed before
+            <input type="date" name="subscribed_before">
+        </label>
+        <br>
+        <label>Also a member of
+            <select name="list_id">
+                <option value="">Any list</option>
+                {list_options}
+            </select>
+        </label>
+        <br>
+        <label>Opened one of the last
+            <input type="number" min="1" name="opened_in_last_issues">
+            issues
+        </label>
+        <br>
+        <label>Email domain
+            <input type="text" placeholder="example.com" name="email_domain">
+        </label>
+        <br>
+        <button type="submit">Create segment</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
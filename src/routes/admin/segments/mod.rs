@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::segments_form;
+pub use post::create_segment;
@@ -0,0 +1,58 @@
+use crate::segments::{insert_segment, NewSegment, SegmentForm};
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    #[serde(default)]
+    subscribed_after: String,
+    #[serde(default)]
+    subscribed_before: String,
+    #[serde(default)]
+    list_id: String,
+    #[serde(default)]
+    opened_in_last_issues: String,
+    #[serde(default)]
+    email_domain: String,
+}
+
+#[tracing::instrument(name = "Create a segment", skip_all)]
+pub async fn create_segment(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let segment = match NewSegment::parse(SegmentForm {
+        name: &form.name,
+        subscribed_after: &form.subscribed_after,
+        subscribed_before: &form.subscribed_before,
+        list_id: &form.list_id,
+        opened_in_last_issues: &form.opened_in_last_issues,
+        email_domain: &form.email_domain,
+    }) {
+        Ok(segment) => segment,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/segments"));
+        }
+    };
+    match insert_segment(&pool, &segment).await {
+        Ok(Some(_)) => FlashMessage::info("The segment has been created."),
+        Ok(None) => FlashMessage::error("A segment with that name already exists."),
+        // The list was deleted in the meantime, or was never valid to begin with.
+        Err(e) if is_foreign_key_violation(&e) => {
+            FlashMessage::error("Please pick a known mailing list.")
+        }
+        Err(e) => return Err(e500(e)),
+    }
+    .send();
+    Ok(see_other("/admin/segments"))
+}
+
+fn is_foreign_key_violation(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<sqlx::Error>()
+        .and_then(|e| e.as_database_error())
+        .is_some_and(|e| e.is_foreign_key_violation())
+}
@@ -0,0 +1,66 @@
+use crate::authentication::{get_active_sessions, UserId};
+use crate::session_state::TypedSession;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn sessions_list(
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    let mut sessions_html = String::new();
+    for s in get_active_sessions(&pool, **user_id).await.map_err(e500)? {
+        let session_id = s.session_id;
+        let action = if Some(session_id) == current_session_id {
+            "This session".to_string()
+        } else {
+            format!(
+                r#"<form action="/admin/sessions/{session_id}/revoke" method="post"><button type="submit">Log out</button></form>"#
+            )
+        };
+        writeln!(
+            sessions_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{action}</td></tr>",
+            html_escape::encode_text(&s.user_agent),
+            html_escape::encode_text(&s.ip_address),
+            s.created_at.format("%Y-%m-%d %H:%M"),
+            s.last_seen_at.format("%Y-%m-%d %H:%M"),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Active sessions</title>
+</head>
+<body>
+    {msg_html}
+    <p>You are logged in on these devices. Changing your password logs out all the others.</p>
+    <table>
+        <tr><th>Device</th><th>IP address</th><th>Logged in at</th><th>Last seen at</th><th></th></tr>
+        {sessions_html}
+    </table>
+    <form action="/admin/sessions/revoke-others" method="post">
+        <button type="submit">Log out all other sessions</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::sessions_list;
+pub use post::{log_out_other_sessions, log_out_session};
@@ -0,0 +1,37 @@
+use crate::authentication::{revoke_other_sessions, revoke_session, UserId};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "Log out a session", skip(pool))]
+pub async fn log_out_session(
+    session_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !revoke_session(&pool, **user_id, session_id.into_inner())
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    FlashMessage::info("The session has been logged out.").send();
+    Ok(see_other("/admin/sessions"))
+}
+
+#[tracing::instrument(name = "Log out all other sessions", skip(session, pool))]
+pub async fn log_out_other_sessions(
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    revoke_other_sessions(pool.get_ref(), **user_id, current_session_id)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("All your other sessions have been logged out.").send();
+    Ok(see_other("/admin/sessions"))
+}
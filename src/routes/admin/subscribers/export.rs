@@ -0,0 +1,118 @@
+use crate::domain::SubscriptionStatus;
+use crate::utils::e400;
+use actix_web::http::header::{ContentDisposition, ContentType};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(serde::Deserialize)]
+pub struct QueryParameters {
+    format: ExportFormat,
+    #[serde(default)]
+    status: String,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: SubscriptionStatus,
+    subscribed_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+}
+
+/// Streams the subscribers straight from Postgres, one row at a time,
+/// so that exporting a large list does not require holding it in memory.
+#[tracing::instrument(name = "Export subscribers", skip_all)]
+pub async fn export_subscribers(
+    parameters: web::Query<QueryParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let status = match parameters.status.as_str() {
+        "" => None,
+        s => Some(SubscriptionStatus::try_from(s.to_string()).map_err(e400)?),
+    };
+    let rows = stream_subscribers(pool.into_inner(), status)
+        .inspect_err(|e| tracing::error!(error.message = %e, "Failed to stream subscribers."));
+
+    let (content_type, filename, body) = match parameters.format {
+        ExportFormat::Csv => {
+            let header = stream::once(async {
+                Ok(Bytes::from_static(
+                    b"id,email,name,status,subscribed_at,confirmed_at\n",
+                ))
+            });
+            let rows = rows.map_ok(|row| Bytes::from(csv_line(&row)));
+            (
+                "text/csv",
+                "subscribers.csv",
+                header.chain(rows).boxed_local(),
+            )
+        }
+        ExportFormat::Json => {
+            let rows = rows.enumerate().map(|(i, row)| {
+                row.map(|row| {
+                    let separator = if i == 0 { "\n" } else { ",\n" };
+                    let json = serde_json::to_string(&row).unwrap();
+                    Bytes::from(format!("{separator}{json}"))
+                })
+            });
+            let body = stream::once(async { Ok(Bytes::from_static(b"[")) })
+                .chain(rows)
+                .chain(stream::once(async { Ok(Bytes::from_static(b"\n]\n")) }));
+            ("application/json", "subscribers.json", body.boxed_local())
+        }
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType(content_type.parse().unwrap()))
+        .insert_header(ContentDisposition::attachment(filename))
+        .streaming(body))
+}
+
+fn stream_subscribers(
+    pool: std::sync::Arc<PgPool>,
+    status: Option<SubscriptionStatus>,
+) -> impl Stream<Item = Result<ExportedSubscriber, sqlx::Error>> {
+    async_stream::try_stream! {
+        let mut rows = sqlx::query_as!(
+            ExportedSubscriber,
+            // language=SQL
+            r#"
+            SELECT id, email, name, status AS "status: SubscriptionStatus", subscribed_at,
+                (
+                    SELECT max(changed_at)
+                    FROM subscription_status_changes
+                    WHERE subscriber_id = subscriptions.id AND status = $2
+                ) AS confirmed_at
+            FROM subscriptions
+            WHERE ($1::subscription_status IS NULL OR status = $1)
+            ORDER BY subscribed_at, id
+            "#,
+            status as Option<SubscriptionStatus>,
+            SubscriptionStatus::Confirmed as SubscriptionStatus,
+        )
+        .fetch(pool.as_ref());
+        while let Some(row) = rows.try_next().await? {
+            yield row;
+        }
+    }
+}
+
+fn csv_line(row: &ExportedSubscriber) -> Vec<u8> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(vec![]);
+    writer.serialize(row).unwrap();
+    writer.into_inner().unwrap()
+}
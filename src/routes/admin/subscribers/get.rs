@@ -0,0 +1,420 @@
+use crate::domain::SubscriptionStatus;
+use crate::utils::{e400, e500};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(serde::Deserialize, serde::Serialize, Default)]
+pub struct QueryParameters {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    search: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    page: Option<u32>,
+}
+
+struct SubscriberSummary {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: SubscriptionStatus,
+    subscribed_at: DateTime<Utc>,
+}
+
+pub async fn subscribers_list(
+    parameters: web::Query<QueryParameters>,
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let search = parameters.search.trim();
+    let status = match parameters.status.as_str() {
+        "" => None,
+        s => Some(SubscriptionStatus::try_from(s.to_string()).map_err(e400)?),
+    };
+    let page = parameters.page.unwrap_or(1).max(1);
+    let mut subscribers = search_subscribers(&pool, search, status, page)
+        .await
+        .map_err(e500)?;
+    let has_next_page = subscribers.len() as i64 > PAGE_SIZE;
+    subscribers.truncate(PAGE_SIZE as usize);
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut status_options_html = String::from(r#"<option value="">Any status</option>"#);
+    for s in SubscriptionStatus::ALL {
+        let selected = if Some(s) == status { "selected" } else { "" };
+        write!(
+            status_options_html,
+            r#"<option value="{s}" {selected}>{s}</option>"#
+        )
+        .unwrap();
+    }
+    let mut rows_html = String::new();
+    for s in &subscribers {
+        writeln!(
+            rows_html,
+            r#"<tr><td><a href="/admin/subscribers/{}">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+            s.id,
+            html_escape::encode_text(&s.email),
+            html_escape::encode_text(&s.name),
+            s.status,
+            s.subscribed_at.format("%Y-%m-%d %H:%M"),
+        )
+        .unwrap();
+    }
+    if subscribers.is_empty() {
+        rows_html.push_str(r#"<tr><td colspan="4">No subscribers found.</td></tr>"#);
+    }
+    let mut pagination_html = String::new();
+    if page > 1 {
+        writeln!(
+            pagination_html,
+            r#"<a href="{}">Previous page</a>"#,
+            page_link(&parameters, page - 1)
+        )
+        .unwrap();
+    }
+    if has_next_page {
+        writeln!(
+            pagination_html,
+            r#"<a href="{}">Next page</a>"#,
+            page_link(&parameters, page + 1)
+        )
+        .unwrap();
+    }
+    let status_filter = status
+        .map(|s| format!("&amp;status={s}"))
+        .unwrap_or_default();
+    let search = html_escape::encode_double_quoted_attribute(search);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscribers</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/admin/subscribers" method="get">
+        <label>Search
+            <input
+                type="text"
+                placeholder="Email or name"
+                name="search"
+                value="{search}"
+            >
+        </label>
+        <label>Status
+            <select name="status">{status_options_html}</select>
+        </label>
+        <button type="submit">Filter</button>
+    </form>
+    <table>
+        <tr><th>Email</th><th>Name</th><th>Status</th><th>Subscribed at</th></tr>
+        {rows_html}
+    </table>
+    <p>Page {page} {pagination_html}</p>
+    <p>
+        Export as
+        <a href="/admin/subscribers/export?format=csv{status_filter}">CSV</a>
+        or
+        <a href="/admin/subscribers/export?format=json{status_filter}">JSON</a>
+    </p>
+    <p><a href="/admin/subscribers/import">Import subscribers from CSV</a></p>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+fn page_link(parameters: &QueryParameters, page: u32) -> String {
+    let query = serde_html_form::to_string(QueryParameters {
+        search: parameters.search.clone(),
+        status: parameters.status.clone(),
+        page: Some(page),
+    })
+    .unwrap();
+    html_escape::encode_double_quoted_attribute(&format!("/admin/subscribers?{query}")).into()
+}
+
+#[tracing::instrument(name = "Search subscribers", skip(pool))]
+async fn search_subscribers(
+    pool: &PgPool,
+    search: &str,
+    status: Option<SubscriptionStatus>,
+    page: u32,
+) -> Result<Vec<SubscriberSummary>, anyhow::Error> {
+    let pattern = format!(
+        "%{}%",
+        search
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    // One extra row tells us whether there is a next page.
+    let subscribers = sqlx::query_as!(
+        SubscriberSummary,
+        // language=SQL
+        r#"
+        SELECT id, email, name, status AS "status: SubscriptionStatus", subscribed_at
+        FROM subscriptions
+        WHERE (email ILIKE $1 OR name ILIKE $1)
+          AND ($2::subscription_status IS NULL OR status = $2)
+        ORDER BY subscribed_at DESC, id
+        LIMIT $3 OFFSET $4
+        "#,
+        pattern,
+        status as Option<SubscriptionStatus>,
+        PAGE_SIZE + 1,
+        (i64::from(page) - 1) * PAGE_SIZE,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to search subscribers.")?;
+    Ok(subscribers)
+}
+
+struct SubscriberDetails {
+    email: String,
+    name: String,
+    status: SubscriptionStatus,
+    subscribed_at: DateTime<Utc>,
+}
+
+pub async fn subscriber_details(
+    subscriber_id: web::Path<Uuid>,
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = get_subscriber_details(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut lists_html = String::new();
+    for (name, subscribed_at) in get_list_memberships(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+    {
+        writeln!(
+            lists_html,
+            "<li>{} (since {})</li>",
+            html_escape::encode_text(&name),
+            subscribed_at.format("%Y-%m-%d %H:%M")
+        )
+        .unwrap();
+    }
+    let mut history_html = String::new();
+    for (status, changed_at) in get_status_history(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+    {
+        writeln!(
+            history_html,
+            "<tr><td>{}</td><td>{status}</td></tr>",
+            changed_at.format("%Y-%m-%d %H:%M")
+        )
+        .unwrap();
+    }
+    let mut deliveries_html = String::new();
+    for (title, delivered_at, succeeded) in
+        get_deliveries(&pool, subscriber_id).await.map_err(e500)?
+    {
+        let outcome = if succeeded { "sent" } else { "failed" };
+        writeln!(
+            deliveries_html,
+            "<tr><td>{}</td><td>{}</td><td>{outcome}</td></tr>",
+            delivered_at.format("%Y-%m-%d %H:%M"),
+            html_escape::encode_text(&title),
+        )
+        .unwrap();
+    }
+    if deliveries_html.is_empty() {
+        deliveries_html.push_str(r#"<tr><td colspan="3">No issues delivered yet.</td></tr>"#);
+    }
+    let mut actions_html = String::new();
+    if subscriber
+        .status
+        .can_transition_to(SubscriptionStatus::Confirmed)
+    {
+        writeln!(
+            actions_html,
+            r#"<form action="/admin/subscribers/{subscriber_id}/confirm" method="post"><button type="submit">Confirm</button></form>"#
+        )
+        .unwrap();
+    }
+    if subscriber
+        .status
+        .can_transition_to(SubscriptionStatus::Unsubscribed)
+    {
+        writeln!(
+            actions_html,
+            r#"<form action="/admin/subscribers/{subscriber_id}/unsubscribe" method="post"><button type="submit">Unsubscribe</button></form>"#
+        )
+        .unwrap();
+    }
+    writeln!(
+        actions_html,
+        r#"<form action="/admin/subscribers/{subscriber_id}/delete" method="post"><button type="submit">Delete</button></form>"#
+    )
+    .unwrap();
+    let SubscriberDetails {
+        email,
+        name,
+        status,
+        subscribed_at,
+    } = subscriber;
+    let email = html_escape::encode_text(&email);
+    let name = html_escape::encode_text(&name);
+    let subscribed_at = subscribed_at.format("%Y-%m-%d %H:%M");
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscriber</title>
+</head>
+<body>
+    {msg_html}
+    <p>Email: {email}</p>
+    <p>Name: {name}</p>
+    <p>Status: {status}</p>
+    <p>Subscribed at: {subscribed_at}</p>
+    <p>Mailing lists:</p>
+    <ul>
+        {lists_html}
+    </ul>
+    <p>Subscription history:</p>
+    <table>
+        <tr><th>Date</th><th>Status</th></tr>
+        {history_html}
+    </table>
+    <p>Deliveries:</p>
+    <table>
+        <tr><th>Date</th><th>Issue</th><th>Outcome</th></tr>
+        {deliveries_html}
+    </table>
+    {actions_html}
+    <p><a href="/admin/subscribers">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Get subscriber details", skip(pool))]
+async fn get_subscriber_details(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberDetails>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        SubscriberDetails,
+        // language=SQL
+        r#"
+        SELECT email, name, status AS "status: SubscriptionStatus", subscribed_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscriber.")?;
+    Ok(subscriber)
+}
+
+#[tracing::instrument(name = "Get list memberships", skip(pool))]
+async fn get_list_memberships(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<(String, DateTime<Utc>)>, anyhow::Error> {
+    let rows = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT lists.name, list_subscriptions.subscribed_at
+        FROM list_subscriptions
+        JOIN lists ON lists.list_id = list_subscriptions.list_id
+        WHERE list_subscriptions.subscriber_id = $1
+        ORDER BY list_subscriptions.subscribed_at, lists.name
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve list memberships.")?;
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.name, r.subscribed_at))
+        .collect())
+}
+
+#[tracing::instrument(name = "Get subscription status history", skip(pool))]
+async fn get_status_history(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<(SubscriptionStatus, DateTime<Utc>)>, anyhow::Error> {
+    let rows = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT status AS "status: SubscriptionStatus", changed_at
+        FROM subscription_status_changes
+        WHERE subscriber_id = $1
+        ORDER BY changed_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscription history.")?;
+    Ok(rows.into_iter().map(|r| (r.status, r.changed_at)).collect())
+}
+
+#[tracing::instrument(name = "Get issue deliveries", skip(pool))]
+async fn get_deliveries(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<(String, DateTime<Utc>, bool)>, anyhow::Error> {
+    let rows = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT newsletter_issues.title, issue_deliveries.delivered_at, issue_deliveries.succeeded
+        FROM issue_deliveries
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_deliveries.newsletter_issue_id
+        WHERE issue_deliveries.subscriber_id = $1
+        ORDER BY issue_deliveries.delivered_at DESC
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve issue deliveries.")?;
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.title, r.delivered_at, r.succeeded))
+        .collect())
+}
@@ -0,0 +1,151 @@
+use crate::authentication::UserId;
+use crate::lists::{get_lists, list_options_html, DEFAULT_LIST_ID};
+use crate::routes::admin::dashboard::get_username;
+use crate::subscriber_import::{import_subscribers, ImportError, ImportMode};
+use crate::utils::{e500, see_other};
+use actix_multipart::form::{bytes::Bytes, text::Text, MultipartForm};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+pub async fn import_subscribers_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let lists = get_lists(&pool).await.map_err(e500)?;
+    let list_options = list_options_html(&lists, None);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Import subscribers</title>
+</head>
+<body>
+    {msg_html}
+    <p>Upload a CSV file with an <code>email</code> and a <code>name</code> column.</p>
+    <form action="/admin/subscribers/import" method="post" enctype="multipart/form-data">
+        <label>File
+            <input type="file" name="file" accept=".csv,text/csv">
+        </label>
+        <br>
+        <label>Mailing list
+            <select name="list_id">{list_options}</select>
+        </label>
+        <br>
+        <label>
+            <input type="radio" name="mode" value="send_confirmation" checked>
+            Send confirmation emails
+        </label>
+        <br>
+        <label>
+            <input type="radio" name="mode" value="confirmed">
+            Import as confirmed
+        </label>
+        <br>
+        <label>Attestation (required to import as confirmed)
+            <textarea
+                name="attestation"
+                placeholder="Where and how did these subscribers consent to receive our newsletter?"
+                rows="4"
+                cols="50"
+            ></textarea>
+        </label>
+        <br>
+        <button type="submit">Import</button>
+    </form>
+    <p><a href="/admin/subscribers">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(MultipartForm)]
+pub struct UploadForm {
+    file: Bytes,
+    list_id: Option<Text<Uuid>>,
+    mode: Option<Text<String>>,
+    attestation: Option<Text<String>>,
+}
+
+#[tracing::instrument(name = "Import subscribers from CSV", skip_all)]
+pub async fn import_subscribers_from_csv(
+    MultipartForm(form): MultipartForm<UploadForm>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mode = match form.mode.as_deref().map(String::as_str) {
+        Some("confirmed") => ImportMode::Confirmed {
+            attestation: form.attestation.map(|a| a.0).unwrap_or_default(),
+        },
+        _ => ImportMode::SendConfirmationEmails,
+    };
+    let list_id = form.list_id.map(|l| l.0).unwrap_or(DEFAULT_LIST_ID);
+    let username = get_username(*user_id.into_inner(), &pool)
+        .await
+        .map_err(e500)?;
+    let report = match import_subscribers(&pool, &form.file.data, list_id, mode, &username).await {
+        Ok(report) => report,
+        Err(ImportError::ValidationError(e)) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+        Err(e) => return Err(e500(e)),
+    };
+
+    let mut errors_html = String::new();
+    for error in &report.errors {
+        writeln!(
+            errors_html,
+            "<li>Line {}: {}</li>",
+            error.line,
+            html_escape::encode_text(&error.message)
+        )
+        .unwrap();
+    }
+    let queued_emails_html = if report.queued_confirmation_emails > 0 {
+        format!(
+            "<p>Confirmation emails queued: {}</p>",
+            report.queued_confirmation_emails
+        )
+    } else {
+        String::new()
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Import report</title>
+</head>
+<body>
+    <p>Imported: {}</p>
+    <p>Already subscribed: {}</p>
+    <p>Rejected: {}</p>
+    {queued_emails_html}
+    <ul>
+        {errors_html}
+    </ul>
+    <p><a href="/admin/subscribers">&lt;- Back</a></p>
+</body>
+</html>"#,
+            report.imported,
+            report.duplicates,
+            report.errors.len(),
+        )))
+}
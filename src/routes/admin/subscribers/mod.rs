@@ -0,0 +1,9 @@
+mod export;
+mod get;
+mod import;
+mod post;
+
+pub use export::export_subscribers;
+pub use get::{subscriber_details, subscribers_list};
+pub use import::{import_subscribers_form, import_subscribers_from_csv};
+pub use post::{confirm_subscriber_manually, delete_subscriber, unsubscribe_subscriber};
@@ -0,0 +1,106 @@
+use crate::authentication::UserId;
+use crate::domain::SubscriptionStatus;
+use crate::routes::admin::dashboard::get_username;
+use crate::routes::{update_subscription_status, UpdateStatusError};
+use crate::subscriber_data::erase_subscriber;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "Confirm a subscriber manually", skip(pool))]
+pub async fn confirm_subscriber_manually(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    change_status(
+        &pool,
+        subscriber_id.into_inner(),
+        SubscriptionStatus::Confirmed,
+        "The subscriber has been confirmed.",
+    )
+    .await
+}
+
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(pool))]
+pub async fn unsubscribe_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    change_status(
+        &pool,
+        subscriber_id.into_inner(),
+        SubscriptionStatus::Unsubscribed,
+        "The subscriber has been unsubscribed.",
+    )
+    .await
+}
+
+async fn change_status(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    next: SubscriptionStatus,
+    success_message: &str,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    if !subscriber_exists(&mut transaction, subscriber_id)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    match update_subscription_status(&mut transaction, subscriber_id, next).await {
+        Ok(()) => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit SQL transaction to update a subscription status.")
+                .map_err(e500)?;
+            FlashMessage::info(success_message).send();
+        }
+        Err(UpdateStatusError::InvalidTransition(e)) => FlashMessage::error(e).send(),
+        Err(e) => return Err(e500(e)),
+    }
+    Ok(see_other(&format!("/admin/subscribers/{subscriber_id}")))
+}
+
+#[tracing::instrument(name = "Delete a subscriber", skip(pool))]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let username = get_username(*user_id.into_inner(), &pool)
+        .await
+        .map_err(e500)?;
+    if !erase_subscriber(&pool, *subscriber_id, &username)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    FlashMessage::info("The subscriber has been deleted.").send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn subscriber_exists(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    subscriber_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"SELECT id FROM subscriptions WHERE id = $1"#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to look up the subscriber.")?;
+    Ok(row.is_some())
+}
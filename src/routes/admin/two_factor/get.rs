@@ -0,0 +1,98 @@
+use crate::authentication::{
+    generate_totp_secret, get_totp_secret, is_two_factor_required, qr_code_svg,
+    totp_provisioning_uri, UserId,
+};
+use crate::routes::admin::dashboard::get_username;
+use crate::session_state::TypedSession;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn two_factor_form(
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = *user_id.into_inner();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let required = is_two_factor_required(&pool).await.map_err(e500)?;
+
+    let body_html = if get_totp_secret(&pool, user_id)
+        .await
+        .map_err(e500)?
+        .is_some()
+    {
+        let disable_html = if required {
+            "<p>Two-factor authentication is required for all users.</p>".to_string()
+        } else {
+            // language=HTML
+            r#"<form action="/admin/two-factor/disable" method="post">
+        <label>Current code or a recovery code
+            <input type="text" autocomplete="one-time-code" placeholder="Enter code" name="code">
+        </label>
+        <button type="submit">Turn off two-factor authentication</button>
+    </form>"#
+                .to_string()
+        };
+        format!(
+            // language=HTML
+            r#"<p>Two-factor authentication is on.</p>
+    {disable_html}"#
+        )
+    } else {
+        // The secret is kept until it is confirmed, so reloading the page does not
+        // invalidate a QR code that was already scanned.
+        let secret = match session.get_pending_totp_secret().map_err(e500)? {
+            Some(secret) => secret,
+            None => {
+                let secret = generate_totp_secret();
+                session.insert_pending_totp_secret(&secret).map_err(e500)?;
+                secret
+            }
+        };
+        let username = get_username(user_id, &pool).await.map_err(e500)?;
+        let qr_code = qr_code_svg(&totp_provisioning_uri(&secret, &username));
+        let required_html = if required {
+            "<p>You must set up two-factor authentication before you can continue.</p>"
+        } else {
+            ""
+        };
+        format!(
+            // language=HTML
+            r#"{required_html}
+    <p>Scan this QR code with your authenticator app, or enter the secret <code>{secret}</code> by hand.</p>
+    {qr_code}
+    <form action="/admin/two-factor" method="post">
+        <label>Code shown by the app
+            <input type="text" autocomplete="one-time-code" placeholder="Enter code" name="code">
+        </label>
+        <button type="submit">Turn on two-factor authentication</button>
+    </form>"#
+        )
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Two-factor authentication</title>
+</head>
+<body>
+    {msg_html}
+    {body_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
@@ -0,0 +1,64 @@
+use crate::authentication::{
+    disable_two_factor, enable_two_factor, generate_recovery_codes, is_two_factor_required,
+    verify_second_factor, verify_totp_code, UserId,
+};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(name = "Enrol in two-factor authentication", skip(form, session, pool))]
+pub async fn enrol_two_factor(
+    form: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(secret) = session.get_pending_totp_secret().map_err(e500)? else {
+        return Ok(see_other("/admin/two-factor"));
+    };
+    let Some(step) = verify_totp_code(&secret, &form.code) else {
+        FlashMessage::error("The code is not valid.").send();
+        return Ok(see_other("/admin/two-factor"));
+    };
+    let recovery_codes = generate_recovery_codes();
+    enable_two_factor(&pool, **user_id, &secret, step, &recovery_codes)
+        .await
+        .map_err(e500)?;
+    session.remove_pending_totp_secret();
+    FlashMessage::info(format!(
+        "Two-factor authentication is on. Keep these recovery codes somewhere safe, \
+        each of them lets you log in once without your app: {}",
+        recovery_codes.join(" ")
+    ))
+    .send();
+    Ok(see_other("/admin/two-factor"))
+}
+
+#[tracing::instrument(name = "Turn off two-factor authentication", skip(form, pool))]
+pub async fn remove_two_factor(
+    form: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if is_two_factor_required(&pool).await.map_err(e500)? {
+        FlashMessage::error("Two-factor authentication is required for all users.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+    if !verify_second_factor(&pool, **user_id, &form.code)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("The code is not valid.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+    disable_two_factor(&pool, **user_id).await.map_err(e500)?;
+    FlashMessage::info("Two-factor authentication is off.").send();
+    Ok(see_other("/admin/two-factor"))
+}
@@ -0,0 +1,204 @@
+use crate::authentication::{get_login_locks, is_two_factor_required, Role, UserId};
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct User {
+    user_id: Uuid,
+    username: String,
+    role: Role,
+    two_factor: bool,
+}
+
+struct Invitation {
+    email: String,
+    role: Role,
+    expires_at: DateTime<Utc>,
+}
+
+pub async fn users_list(
+    current_user_id: ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let current_user_id = *current_user_id.into_inner();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut users_html = String::new();
+    for user in get_users(&pool).await.map_err(e500)? {
+        let user_id = user.user_id;
+        let mut role_options = String::new();
+        for role in Role::ALL {
+            let selected = if role == user.role { " selected" } else { "" };
+            write!(
+                role_options,
+                r#"<option value="{role}"{selected}>{role}</option>"#
+            )
+            .unwrap();
+        }
+        let (you, remove_button) = if user_id == current_user_id {
+            (" (you)", String::new())
+        } else {
+            (
+                "",
+                format!(
+                    r#"<form action="/admin/users/{user_id}/delete" method="post"><button type="submit">Remove</button></form>"#
+                ),
+            )
+        };
+        let two_factor = if user.two_factor { "on" } else { "off" };
+        writeln!(
+            users_html,
+            r#"<tr><td>{}{you}</td><td><form action="/admin/users/{user_id}/role" method="post"><select name="role">{role_options}</select><button type="submit">Save</button></form></td><td>{two_factor}</td><td>{remove_button}</td></tr>"#,
+            html_escape::encode_text(&user.username),
+        )
+        .unwrap();
+    }
+    let two_factor_html = if is_two_factor_required(&pool).await.map_err(e500)? {
+        // language=HTML
+        r#"<form action="/admin/users/two-factor" method="post">
+        <p>Every user must use two-factor authentication.</p>
+        <input type="hidden" name="required" value="false">
+        <button type="submit">Make two-factor authentication optional</button>
+    </form>"#
+    } else {
+        // language=HTML
+        r#"<form action="/admin/users/two-factor" method="post">
+        <input type="hidden" name="required" value="true">
+        <button type="submit">Require two-factor authentication for every user</button>
+    </form>"#
+    };
+    let mut invitations_html = String::new();
+    for invitation in get_pending_invitations(&pool).await.map_err(e500)? {
+        writeln!(
+            invitations_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            html_escape::encode_text(&invitation.email),
+            invitation.role,
+            invitation.expires_at.format("%Y-%m-%d %H:%M"),
+        )
+        .unwrap();
+    }
+    if invitations_html.is_empty() {
+        invitations_html.push_str(r#"<tr><td colspan="3">No pending invitations.</td></tr>"#);
+    }
+    let mut locks_html = String::new();
+    for lock in get_login_locks(&pool).await.map_err(e500)? {
+        writeln!(
+            locks_html,
+            r#"<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><form action="/admin/users/locks/clear" method="post"><input type="hidden" name="kind" value="{}"><input type="hidden" name="key" value="{}"><button type="submit">Clear</button></form></td></tr>"#,
+            lock.kind,
+            html_escape::encode_text(&lock.key),
+            lock.failures,
+            lock.locked_until.format("%Y-%m-%d %H:%M:%S"),
+            lock.kind,
+            html_escape::encode_double_quoted_attribute(&lock.key),
+        )
+        .unwrap();
+    }
+    if locks_html.is_empty() {
+        locks_html.push_str(r#"<tr><td colspan="5">Nobody is locked out.</td></tr>"#);
+    }
+    let mut invite_role_options = String::new();
+    for role in Role::ALL {
+        let selected = if role == Role::Editor {
+            " selected"
+        } else {
+            ""
+        };
+        write!(
+            invite_role_options,
+            r#"<option value="{role}"{selected}>{role}</option>"#
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Users</title>
+</head>
+<body>
+    {msg_html}
+    <p>Owners can do everything, editors can publish issues and manage the audience,
+        viewers can only look around.</p>
+    <table>
+        <tr><th>Username</th><th>Role</th><th>Two-factor</th><th></th></tr>
+        {users_html}
+    </table>
+    {two_factor_html}
+    <h2>Invite a user</h2>
+    <form action="/admin/users/invitations" method="post">
+        <label>Email
+            <input type="email" placeholder="Enter their email address" name="email">
+        </label>
+        <label>Role
+            <select name="role">{invite_role_options}</select>
+        </label>
+        <button type="submit">Send invitation</button>
+    </form>
+    <h2>Pending invitations</h2>
+    <table>
+        <tr><th>Email</th><th>Role</th><th>Expires at</th></tr>
+        {invitations_html}
+    </table>
+    <h2>Login locks</h2>
+    <p>Logins are slowed down and then locked after repeated failures, per username and per IP address.</p>
+    <table>
+        <tr><th>Kind</th><th>Username or IP address</th><th>Failures</th><th>Locked until</th><th></th></tr>
+        {locks_html}
+    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Get users", skip(pool))]
+async fn get_users(pool: &PgPool) -> Result<Vec<User>, anyhow::Error> {
+    let users = sqlx::query_as!(
+        User,
+        // language=SQL
+        r#"
+        SELECT user_id, username, role AS "role: Role", totp_secret IS NOT NULL AS "two_factor!"
+        FROM users
+        ORDER BY username
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve users.")?;
+    Ok(users)
+}
+
+#[tracing::instrument(name = "Get pending invitations", skip(pool))]
+async fn get_pending_invitations(pool: &PgPool) -> Result<Vec<Invitation>, anyhow::Error> {
+    let invitations = sqlx::query_as!(
+        Invitation,
+        // language=SQL
+        r#"
+        SELECT email, role AS "role: Role", expires_at
+        FROM admin_invitations
+        WHERE accepted_at IS NULL AND expires_at > now()
+        ORDER BY created_at
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve pending invitations.")?;
+    Ok(invitations)
+}
@@ -0,0 +1,119 @@
+use crate::authentication::{generate_token, hash_token, Role, UserId};
+use crate::domain::{SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e400, e500, see_other};
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+
+/// How long an invitation link can be used for.
+const INVITATION_VALIDITY_DAYS: i32 = 7;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+    role: String,
+}
+
+#[tracing::instrument(name = "Invite a user", skip_all, fields(user_id=%&*user_id))]
+pub async fn invite_user(
+    form: web::Form<FormData>,
+    user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let role = Role::try_from(form.role.as_str()).map_err(e400)?;
+    let Ok(email) = SubscriberEmail::parse(form.0.email) else {
+        FlashMessage::error("Please enter a valid email address.").send();
+        return Ok(see_other("/admin/users"));
+    };
+    if email_is_taken(&pool, &email).await.map_err(e500)? {
+        FlashMessage::error("There already is a user with that email address.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let token = generate_token();
+    store_invitation(&pool, &hash_token(&token), &email, role, **user_id)
+        .await
+        .map_err(e500)?;
+    send_invitation(&email_client, &email, role, &base_url.0, &token)
+        .await
+        .context("Failed to send an invitation email.")
+        .map_err(e500)?;
+    FlashMessage::info("The invitation has been sent.").send();
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn email_is_taken(pool: &PgPool, email: &SubscriberEmail) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"SELECT user_id FROM users WHERE lower(email) = lower($1)"#,
+        email.as_ref(),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up a user by email.")?;
+    Ok(row.is_some())
+}
+
+#[tracing::instrument(skip(pool, token_hash))]
+async fn store_invitation(
+    pool: &PgPool,
+    token_hash: &str,
+    email: &SubscriberEmail,
+    role: Role,
+    invited_by: uuid::Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO admin_invitations (token_hash, email, role, invited_by, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, now(), now() + make_interval(days => $5))
+        "#,
+        token_hash,
+        email.as_ref(),
+        role as Role,
+        invited_by,
+        INVITATION_VALIDITY_DAYS,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store an invitation.")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_invitation(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    role: Role,
+    base_url: &str,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    // We do not know the name of the invitee yet.
+    let name = SubscriberName::parse(email.as_ref().to_string()).map_err(|e| anyhow::anyhow!(e))?;
+    let invitation_link = format!("{base_url}/invitations?token={token}");
+    let plain_body = format!(
+        "You have been invited to help run our newsletter as {role}.\n\
+        Visit {invitation_link} within {INVITATION_VALIDITY_DAYS} days to create your account."
+    );
+    let html_body = format!(
+        "You have been invited to help run our newsletter as {role}.<br />\
+        Click <a href=\"{invitation_link}\">here</a> within {INVITATION_VALIDITY_DAYS} days \
+        to create your account."
+    );
+    email_client
+        .send_email(
+            email,
+            &name,
+            "You have been invited to the newsletter admin",
+            &html_body,
+            &plain_body,
+        )
+        .await?;
+    Ok(())
+}
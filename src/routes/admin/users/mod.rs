@@ -0,0 +1,7 @@
+mod get;
+mod invite;
+mod post;
+
+pub use get::users_list;
+pub use invite::invite_user;
+pub use post::{change_user_role, clear_login_lock, remove_user, require_two_factor};
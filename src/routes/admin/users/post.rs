@@ -0,0 +1,182 @@
+use crate::authentication::{
+    clear_login_failures, set_two_factor_required, Role, ThrottleKind, UserId,
+};
+use crate::utils::{e400, e500, see_other};
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct RoleFormData {
+    role: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TwoFactorFormData {
+    required: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct LockFormData {
+    kind: String,
+    key: String,
+}
+
+#[tracing::instrument(name = "Change the role of a user", skip(form, pool))]
+pub async fn change_user_role(
+    user_id: web::Path<Uuid>,
+    form: web::Form<RoleFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let role = Role::try_from(form.role.as_str()).map_err(e400)?;
+    let mut transaction = begin(&pool).await?;
+    let Some(current_role) = lock_user(&mut transaction, user_id).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    if current_role == Role::Owner && role != Role::Owner && is_last_owner(&mut transaction).await?
+    {
+        FlashMessage::error("There must always be at least one owner.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let query = sqlx::query!(
+        // language=SQL
+        r#"UPDATE users SET role = $2 WHERE user_id = $1"#,
+        user_id,
+        role as Role,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to update the role of a user.")
+        .map_err(e500)?;
+    commit(transaction).await?;
+    FlashMessage::info("The role has been changed.").send();
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(name = "Require two-factor authentication", skip(form, pool))]
+pub async fn require_two_factor(
+    form: web::Form<TwoFactorFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    set_two_factor_required(&pool, form.required)
+        .await
+        .map_err(e500)?;
+    if form.required {
+        FlashMessage::info("Every user must now use two-factor authentication.").send();
+    } else {
+        FlashMessage::info("Two-factor authentication is now optional.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(name = "Clear a login lock", skip(form, pool))]
+pub async fn clear_login_lock(
+    form: web::Form<LockFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let kind = ThrottleKind::try_from(form.kind.as_str()).map_err(e400)?;
+    clear_login_failures(&pool, kind, &form.key)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The lock has been cleared.").send();
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(name = "Remove a user", skip(pool, current_user_id))]
+pub async fn remove_user(
+    user_id: web::Path<Uuid>,
+    current_user_id: ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    if user_id == *current_user_id.into_inner() {
+        FlashMessage::error("You cannot remove yourself.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let mut transaction = begin(&pool).await?;
+    let Some(current_role) = lock_user(&mut transaction, user_id).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    if current_role == Role::Owner && is_last_owner(&mut transaction).await? {
+        FlashMessage::error("There must always be at least one owner.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let query = sqlx::query!(
+        // language=SQL
+        r#"DELETE FROM users WHERE user_id = $1"#,
+        user_id,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to remove a user.")
+        .map_err(e500)?;
+    commit(transaction).await?;
+    FlashMessage::info("The user has been removed.").send();
+    Ok(see_other("/admin/users"))
+}
+
+async fn begin(pool: &PgPool) -> Result<Transaction<'static, Postgres>, actix_web::Error> {
+    pool.begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)
+}
+
+async fn commit(transaction: Transaction<'static, Postgres>) -> Result<(), actix_web::Error> {
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a user.")
+        .map_err(e500)
+}
+
+/// Returns the role of the user, if they exist.
+/// Owners are locked as a group, so that two concurrent demotions cannot remove the last one.
+#[tracing::instrument(skip(transaction))]
+async fn lock_user(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<Option<Role>, actix_web::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"SELECT user_id FROM users WHERE role = $1 FOR UPDATE"#,
+        Role::Owner as Role,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to lock the owners.")
+        .map_err(e500)?;
+    let row = sqlx::query!(
+        // language=SQL
+        r#"SELECT role AS "role: Role" FROM users WHERE user_id = $1 FOR UPDATE"#,
+        user_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to retrieve a user.")
+    .map_err(e500)?;
+    Ok(row.map(|r| r.role))
+}
+
+#[tracing::instrument(skip_all)]
+async fn is_last_owner(
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<bool, actix_web::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"SELECT count(*) AS "n!" FROM users WHERE role = $1"#,
+        Role::Owner as Role,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to count owners.")
+    .map_err(e500)?;
+    Ok(row.n <= 1)
+}
@@ -0,0 +1,109 @@
+use crate::authentication::{hash_token, Role};
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::{Executor, Postgres};
+use std::fmt::Write;
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+pub async fn invitation_form(
+    parameters: web::Query<Parameters>,
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<sqlx::PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(invitation) = get_pending_invitation(&**pool, &parameters.token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let email = html_escape::encode_text(&invitation.email);
+    let role = invitation.role;
+    let token = html_escape::encode_double_quoted_attribute(&parameters.token);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Create your account</title>
+</head>
+<body>
+    {msg_html}
+    <p>{email} has been invited to join as {role}.</p>
+    <form action="/invitations" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <label>Username
+            <input
+                type="text"
+                placeholder="Choose a username"
+                name="username"
+            >
+        </label>
+        <br>
+        <label>Password
+            <input
+                type="password"
+                placeholder="Choose a password"
+                name="password"
+            >
+        </label>
+        <br>
+        <label>Confirm password
+            <input
+                type="password"
+                placeholder="Type the password again"
+                name="password_check"
+            >
+        </label>
+        <br>
+        <button type="submit">Create account</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
+
+pub struct PendingInvitation {
+    pub email: String,
+    pub role: Role,
+}
+
+/// Expired and already accepted invitations are not pending anymore.
+#[tracing::instrument(skip_all)]
+pub async fn get_pending_invitation<'c, E>(
+    executor: E,
+    token: &str,
+) -> Result<Option<PendingInvitation>, anyhow::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let invitation = sqlx::query_as!(
+        PendingInvitation,
+        // language=SQL
+        r#"
+        SELECT email, role AS "role: Role"
+        FROM admin_invitations
+        WHERE token_hash = $1 AND accepted_at IS NULL AND expires_at > now()
+        FOR UPDATE
+        "#,
+        hash_token(token),
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to look up an invitation.")?;
+    Ok(invitation)
+}
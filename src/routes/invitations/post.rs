@@ -0,0 +1,109 @@
+use super::get::get_pending_invitation;
+use crate::authentication::{
+    create_user, hash_token, NewPassword, PasswordHashingSettings, PasswordPolicy,
+};
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{Executor, PgPool};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    token: String,
+    username: String,
+    password: SecretString,
+    password_check: SecretString,
+}
+
+#[tracing::instrument(name = "Accept an invitation", skip_all)]
+pub async fn accept_invitation(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    password_policy: web::Data<PasswordPolicy>,
+    password_hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        token,
+        username,
+        password,
+        password_check,
+    } = form.0;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let Some(invitation) = get_pending_invitation(&mut *transaction, &token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    // Only tokens we generated ourselves get this far, so they are safe to put in a URL.
+    let invitation_page = format!("/invitations?token={token}");
+    let username = username.trim();
+    if let Err(e) = validate_username(username) {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&invitation_page));
+    }
+    if password.expose_secret() != password_check.expose_secret() {
+        FlashMessage::error("You entered two different passwords - the field values must match.")
+            .send();
+        return Ok(see_other(&invitation_page));
+    }
+    let password = match NewPassword::parse(password, username, &password_policy) {
+        Ok(password) => password,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&invitation_page));
+        }
+    };
+    let Some(user_id) = create_user(
+        &mut transaction,
+        username,
+        &invitation.email,
+        password,
+        invitation.role,
+        &password_hashing,
+    )
+    .await
+    .map_err(e500)?
+    else {
+        FlashMessage::error("That username or email address is already in use.").send();
+        return Ok(see_other(&invitation_page));
+    };
+    tracing::info!(%user_id, "An invitation has been accepted.");
+    let query = sqlx::query!(
+        // language=SQL
+        r#"UPDATE admin_invitations SET accepted_at = now() WHERE token_hash = $1"#,
+        hash_token(&token),
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to mark an invitation as accepted.")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to accept an invitation.")
+        .map_err(e500)?;
+    FlashMessage::info("Your account has been created - you can now log in.").send();
+    Ok(see_other("/login"))
+}
+
+fn validate_username(username: &str) -> Result<(), &'static str> {
+    if username.is_empty() || username.graphemes(true).count() > 64 {
+        Err("Please choose a username of at most 64 characters.")
+    } else if username
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control())
+    {
+        Err("Usernames cannot contain spaces.")
+    } else {
+        Ok(())
+    }
+}
@@ -37,6 +37,7 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
         </label>
         <button type="submit">Login</button>
     </form>
+    <p><a href="/password-reset">Forgot your password?</a></p>
 </body>
 </html>"#,
         ))
@@ -1,12 +1,18 @@
-use crate::authentication::{validate_credentials, AuthError, Credentials};
+use crate::authentication::{
+    clear_login_failures, get_totp_secret, is_login_locked, record_login_failure, record_session,
+    validate_credentials, AuthError, Credentials, PasswordHashingSettings, ThrottleKind,
+};
+use crate::client_ip::TrustedProxies;
 use crate::routes::error_chain_fmt;
 use crate::session_state::TypedSession;
 use crate::utils::see_other;
 use actix_web::error::InternalError;
-use actix_web::{web, HttpResponse};
+use actix_web::http::header::USER_AGENT;
+use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::SecretString;
 use sqlx::PgPool;
+use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
@@ -15,31 +21,62 @@ pub struct FormData {
 }
 
 #[tracing::instrument(
-    skip(form, pool, session),
+    skip(form, pool, password_hashing, trusted_proxies, session, request),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
+    password_hashing: web::Data<PasswordHashingSettings>,
+    trusted_proxies: web::Data<TrustedProxies>,
     session: TypedSession,
+    request: HttpRequest,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
+    let username = form.0.username;
+    let client_ip = trusted_proxies.client_ip(&request);
+    tracing::Span::current().record("username", tracing::field::display(&username));
+    // Unknown usernames are locked just like known ones, so a lock gives nothing away.
+    if is_login_locked(&pool, &username, &client_ip)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        return Err(login_redirect(LoginError::TooManyAttempts));
+    }
     let credentials = Credentials {
-        username: form.0.username,
+        username: username.clone(),
         password: form.0.password,
     };
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
-    match validate_credentials(credentials, &pool).await {
+    match validate_credentials(credentials, &pool, &password_hashing).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            let has_two_factor = get_totp_secret(&pool, user_id)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+                .is_some();
             session.renew();
-            session
-                .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            // With two-factor authentication, failures are only cleared once the code is right.
+            if has_two_factor {
+                session
+                    .insert_pending_user_id(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                return Ok(see_other("/login/two-factor"));
+            }
+            clear_login_failures(&pool, ThrottleKind::Username, &username)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            start_session(&pool, &session, user_id, &request, &client_ip)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             Ok(see_other("/admin/dashboard"))
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    record_login_failure(&pool, &username, &client_ip)
+                        .await
+                        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                    LoginError::AuthError(e.into())
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
             Err(login_redirect(e))
@@ -47,6 +84,25 @@ pub async fn login(
     }
 }
 
+/// Logs the user in, recording the session so that it can be listed and revoked.
+pub(super) async fn start_session(
+    pool: &PgPool,
+    session: &TypedSession,
+    user_id: Uuid,
+    request: &HttpRequest,
+    client_ip: &str,
+) -> Result<(), anyhow::Error> {
+    let user_agent = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("Unknown device");
+    let session_id = record_session(pool, user_id, client_ip, user_agent).await?;
+    session.insert_user_id(user_id)?;
+    session.insert_session_id(session_id)?;
+    Ok(())
+}
+
 fn login_redirect(e: LoginError) -> InternalError<LoginError> {
     FlashMessage::error(e.to_string()).send();
     InternalError::from_response(e, see_other("/login"))
@@ -56,6 +112,8 @@ fn login_redirect(e: LoginError) -> InternalError<LoginError> {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed attempts - please try again later")]
+    TooManyAttempts,
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -0,0 +1,111 @@
+use super::post::start_session;
+use crate::authentication::{
+    clear_login_failures, is_login_locked, record_login_failure, verify_second_factor, ThrottleKind,
+};
+use crate::client_ip::TrustedProxies;
+use crate::routes::get_username;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+/// Wrong codes allowed for one password login before the password has to be entered again.
+const MAX_WRONG_CODES: u32 = 5;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+pub async fn login_two_factor_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_pending_user_id().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+    let mut error_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Login</title>
+</head>
+<body>
+    {error_html}
+    <form action="/login/two-factor" method="post">
+        <label>Code from your authenticator app, or a recovery code
+            <input
+                type="text"
+                autocomplete="one-time-code"
+                placeholder="Enter code"
+                name="code"
+            >
+        </label>
+        <button type="submit">Verify</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(
+    skip(form, pool, trusted_proxies, session, request),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn login_two_factor(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    trusted_proxies: web::Data<TrustedProxies>,
+    session: TypedSession,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(user_id) = session.get_pending_user_id().map_err(e500)? else {
+        return Ok(see_other("/login"));
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    // Wrong codes count as failed logins, otherwise codes could be guessed at leisure.
+    let username = get_username(user_id, &pool).await.map_err(e500)?;
+    let client_ip = trusted_proxies.client_ip(&request);
+    if is_login_locked(&pool, &username, &client_ip)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("Too many failed attempts - please try again later").send();
+        return Ok(see_other("/login/two-factor"));
+    }
+    if !verify_second_factor(&pool, user_id, &form.code)
+        .await
+        .map_err(e500)?
+    {
+        record_login_failure(&pool, &username, &client_ip)
+            .await
+            .map_err(e500)?;
+        if session.record_wrong_code().map_err(e500)? >= MAX_WRONG_CODES {
+            session.remove_pending_user_id();
+            FlashMessage::error("Too many wrong codes - please log in again.").send();
+            return Ok(see_other("/login"));
+        }
+        FlashMessage::error("The code is not valid.").send();
+        return Ok(see_other("/login/two-factor"));
+    }
+    clear_login_failures(&pool, ThrottleKind::Username, &username)
+        .await
+        .map_err(e500)?;
+    session.renew();
+    start_session(&pool, &session, user_id, &request, &client_ip)
+        .await
+        .map_err(e500)?;
+    Ok(see_other("/admin/dashboard"))
+}
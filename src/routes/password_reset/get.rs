@@ -0,0 +1,128 @@
+use crate::authentication::hash_token;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres};
+use std::fmt::Write;
+use uuid::Uuid;
+
+pub async fn password_reset_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Forgot your password?</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/password-reset" method="post">
+        <label>Email
+            <input
+                type="email"
+                placeholder="Enter the email address of your account"
+                name="email"
+            >
+        </label>
+        <button type="submit">Send me a reset link</button>
+    </form>
+    <p><a href="/login">&lt;- Back</a></p>
+</body>
+</html>"#,
+        ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+pub async fn reset_password_form(
+    parameters: web::Query<Parameters>,
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if get_user_id_from_reset_token(&**pool, &parameters.token)
+        .await
+        .map_err(e500)?
+        .is_none()
+    {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let token = html_escape::encode_double_quoted_attribute(&parameters.token);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Reset your password</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/password-reset/confirm" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <label>New password
+            <input
+                type="password"
+                placeholder="Enter new password"
+                name="new_password"
+            >
+        </label>
+        <br>
+        <label>Confirm new password
+            <input
+                type="password"
+                placeholder="Type the new password again"
+                name="new_password_check"
+            >
+        </label>
+        <br>
+        <button type="submit">Reset password</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
+
+/// Used and expired tokens are not valid anymore.
+#[tracing::instrument(skip_all)]
+pub async fn get_user_id_from_reset_token<'c, E>(
+    executor: E,
+    token: &str,
+) -> Result<Option<Uuid>, anyhow::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let row = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT user_id
+        FROM password_reset_tokens
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        FOR UPDATE
+        "#,
+        hash_token(token),
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to look up a password reset token.")?;
+    Ok(row.map(|r| r.user_id))
+}
@@ -0,0 +1,232 @@
+use super::get::get_user_id_from_reset_token;
+use crate::authentication::{
+    change_password_in_transaction, generate_token, hash_token, revoke_other_sessions, NewPassword,
+    PasswordHashingSettings, PasswordPolicy,
+};
+use crate::domain::{SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::routes::get_username;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Reset links are short-lived: they are as good as the password itself.
+const RESET_TOKEN_VALIDITY_MINUTES: i32 = 60;
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    email: String,
+}
+
+#[tracing::instrument(name = "Request a password reset", skip_all)]
+pub async fn request_password_reset(
+    form: web::Form<RequestFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // We answer the same way whether or not there is an account with that address,
+    // so the form cannot be used to find out who the admins are. That includes
+    // timing: storing the token and calling the email API happen off the request path.
+    if let Ok(email) = SubscriberEmail::parse(form.0.email) {
+        if let Some(user) = get_user_by_email(&pool, &email).await.map_err(e500)? {
+            let pool = pool.clone();
+            let email_client = email_client.clone();
+            let base_url = base_url.0.clone();
+            tokio::spawn(
+                async move {
+                    if let Err(e) =
+                        issue_reset_link(&pool, &email_client, &email, &user, &base_url).await
+                    {
+                        tracing::error!(
+                            error.cause_chain = ?e,
+                            error.message = %e,
+                            "Failed to send a password reset link.",
+                        );
+                    }
+                }
+                .instrument(tracing::Span::current()),
+            );
+        }
+    }
+    FlashMessage::info(
+        "If an account uses that address, we have sent it a link to reset the password.",
+    )
+    .send();
+    Ok(see_other("/login"))
+}
+
+struct User {
+    user_id: Uuid,
+    username: String,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_user_by_email(
+    pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<Option<User>, anyhow::Error> {
+    let user = sqlx::query_as!(
+        User,
+        // language=SQL
+        r#"SELECT user_id, username FROM users WHERE lower(email) = lower($1)"#,
+        email.as_ref(),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up a user by email.")?;
+    Ok(user)
+}
+
+#[tracing::instrument(skip_all)]
+async fn issue_reset_link(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    user: &User,
+    base_url: &str,
+) -> Result<(), anyhow::Error> {
+    let token = generate_token();
+    store_reset_token(pool, user.user_id, &hash_token(&token)).await?;
+    send_reset_link(email_client, email, &user.username, base_url, &token).await
+}
+
+#[tracing::instrument(skip(pool, token_hash))]
+async fn store_reset_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    token_hash: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, now(), now() + make_interval(mins => $3))
+        "#,
+        token_hash,
+        user_id,
+        RESET_TOKEN_VALIDITY_MINUTES,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store a password reset token.")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_reset_link(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    username: &str,
+    base_url: &str,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    let name = SubscriberName::parse(username.to_string()).map_err(|e| anyhow::anyhow!(e))?;
+    let reset_link = format!("{base_url}/password-reset/confirm?token={token}");
+    let plain_body = format!(
+        "Somebody asked to reset the password of your newsletter admin account.\n\
+        Visit {reset_link} within {RESET_TOKEN_VALIDITY_MINUTES} minutes to choose a new one.\n\
+        If it was not you, you can ignore this email."
+    );
+    let html_body = format!(
+        "Somebody asked to reset the password of your newsletter admin account.<br />\
+        Click <a href=\"{reset_link}\">here</a> within {RESET_TOKEN_VALIDITY_MINUTES} minutes \
+        to choose a new one.<br />\
+        If it was not you, you can ignore this email."
+    );
+    email_client
+        .send_email(email, &name, "Reset your password", &html_body, &plain_body)
+        .await?;
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetFormData {
+    token: String,
+    new_password: SecretString,
+    new_password_check: SecretString,
+}
+
+#[tracing::instrument(name = "Reset a password", skip_all)]
+pub async fn reset_password(
+    form: web::Form<ResetFormData>,
+    pool: web::Data<PgPool>,
+    password_policy: web::Data<PasswordPolicy>,
+    password_hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ResetFormData {
+        token,
+        new_password,
+        new_password_check,
+    } = form.0;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let Some(user_id) = get_user_id_from_reset_token(&mut *transaction, &token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    // Only tokens we generated ourselves get this far, so they are safe to put in a URL.
+    let reset_page = format!("/password-reset/confirm?token={token}");
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        FlashMessage::error(
+            "You entered two different new passwords - the field values must match.",
+        )
+        .send();
+        return Ok(see_other(&reset_page));
+    }
+    let username = get_username(user_id, &pool).await.map_err(e500)?;
+    let new_password = match NewPassword::parse(new_password, &username, &password_policy) {
+        Ok(new_password) => new_password,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&reset_page));
+        }
+    };
+    use_reset_tokens(&mut transaction, user_id)
+        .await
+        .context("Failed to use up the password reset tokens.")
+        .map_err(e500)?;
+    change_password_in_transaction(&mut transaction, user_id, new_password, &password_hashing)
+        .await
+        .map_err(e500)?;
+    revoke_other_sessions(&mut *transaction, user_id, None)
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to reset a password.")
+        .map_err(e500)?;
+    FlashMessage::info("Your password has been reset - you can now log in.").send();
+    Ok(see_other("/login"))
+}
+
+/// Once a link has been used, none of the other links sent to the user work either.
+#[tracing::instrument(skip(transaction))]
+async fn use_reset_tokens(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        UPDATE password_reset_tokens
+        SET used_at = now()
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
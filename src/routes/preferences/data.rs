@@ -0,0 +1,60 @@
+use super::get::get_subscriber_id_from_preference_token;
+use crate::subscriber_data::{erase_subscriber, get_subscriber_data};
+use crate::utils::e500;
+use actix_web::http::header::{ContentDisposition, ContentType};
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+#[tracing::instrument(name = "Download subscriber data", skip_all)]
+pub async fn download_data(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(subscriber_id) = get_subscriber_id_from_preference_token(&pool, &parameters.token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    let data = get_subscriber_data(&pool, subscriber_id)
+        .await
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition::attachment("subscriber-data.json"))
+        .json(data))
+}
+
+#[tracing::instrument(name = "Erase subscriber data", skip_all)]
+pub async fn erase_data(
+    form: web::Form<Parameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(subscriber_id) = get_subscriber_id_from_preference_token(&pool, &form.token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    erase_subscriber(&pool, subscriber_id, "subscriber")
+        .await
+        .map_err(e500)?;
+    // The preferences page went away with the rest of the data, so there is nowhere to redirect to.
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        // language=HTML
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Data erased</title>
+</head>
+<body>
+    <p>All your data has been erased. You will not hear from us again.</p>
+</body>
+</html>"#,
+    ))
+}
@@ -0,0 +1,151 @@
+use super::get::get_subscriber_id_from_preference_token;
+use crate::domain::{SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::routes::{generate_subscription_token, get_subscriber_by_email};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    token: String,
+    new_email: String,
+}
+
+#[tracing::instrument(name = "Request an email change", skip_all)]
+pub async fn request_email_change(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(subscriber_id) = get_subscriber_id_from_preference_token(&pool, &form.token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    let preferences_page = format!("/preferences?token={}", form.token);
+    let Ok(new_email) = SubscriberEmail::parse(form.0.new_email) else {
+        FlashMessage::error("Please enter a valid email address.").send();
+        return Ok(see_other(&preferences_page));
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    // We answer the same way whether or not the address is taken, so the form
+    // cannot be used to find out who else is subscribed.
+    let is_taken = get_subscriber_by_email(&mut transaction, &new_email)
+        .await
+        .context("Failed to look up an existing subscriber.")
+        .map_err(e500)?
+        .is_some();
+    if !is_taken {
+        let name = get_subscriber_name(&mut transaction, subscriber_id)
+            .await
+            .map_err(e500)?;
+        let subscription_token = generate_subscription_token();
+        store_email_change_token(
+            &mut transaction,
+            subscriber_id,
+            &subscription_token,
+            &new_email,
+        )
+        .await
+        .context("Failed to store the email change token.")
+        .map_err(e500)?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to store an email change token.")
+            .map_err(e500)?;
+        send_email_change_confirmation(
+            &email_client,
+            &new_email,
+            &name,
+            &base_url.0,
+            &subscription_token,
+        )
+        .await
+        .context("Failed to send an email change confirmation.")
+        .map_err(e500)?;
+    }
+
+    FlashMessage::info(
+        "We have sent a confirmation link to your new address. \
+        Your address will change once you click it.",
+    )
+    .send();
+    Ok(see_other(&preferences_page))
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn get_subscriber_name(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<SubscriberName, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"SELECT name FROM subscriptions WHERE id = $1"#,
+        subscriber_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to retrieve the subscriber name.")?;
+    SubscriberName::parse(row.name).map_err(|e| anyhow::anyhow!(e))
+}
+
+#[tracing::instrument(skip(transaction, subscription_token, new_email))]
+async fn store_email_change_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    subscription_token: &str,
+    new_email: &SubscriberEmail,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id, new_email)
+        VALUES ($1, $2, $3)"#,
+        subscription_token,
+        subscriber_id,
+        new_email.as_ref(),
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_email_change_confirmation(
+    email_client: &EmailClient,
+    new_email: &SubscriberEmail,
+    name: &SubscriberName,
+    base_url: &str,
+    subscription_token: &str,
+) -> Result<(), reqwest::Error> {
+    let confirmation_link =
+        format!("{base_url}/subscriptions/confirm?subscription_token={subscription_token}");
+    let plain_body = format!(
+        "You asked to receive our newsletter at this address.\n\
+        Visit {confirmation_link} to confirm the change."
+    );
+    let html_body = format!(
+        "You asked to receive our newsletter at this address.<br />\
+        Click <a href=\"{confirmation_link}\">here</a> to confirm the change."
+    );
+    email_client
+        .send_email(
+            new_email,
+            name,
+            "Confirm your new email address",
+            &html_body,
+            &plain_body,
+        )
+        .await
+}
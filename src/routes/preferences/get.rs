@@ -0,0 +1,175 @@
+use crate::domain::SubscriptionStatus;
+use crate::lists::get_lists;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+pub async fn preferences_form(
+    parameters: web::Query<Parameters>,
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = &parameters.token;
+    let Some(subscriber_id) = get_subscriber_id_from_preference_token(&pool, token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    let preferences = get_preferences(&pool, subscriber_id).await.map_err(e500)?;
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut lists_html = String::new();
+    for list in get_lists(&pool).await.map_err(e500)? {
+        let checked = if preferences.list_ids.contains(&list.list_id) {
+            "checked"
+        } else {
+            ""
+        };
+        writeln!(
+            lists_html,
+            r#"<label><input type="checkbox" name="list_id" value="{}" {checked}> {}</label><br>"#,
+            list.list_id, list.name
+        )
+        .unwrap();
+    }
+    let plain_text_only = if preferences.plain_text_only {
+        "checked"
+    } else {
+        ""
+    };
+    let unsubscribe_html = if preferences.status == SubscriptionStatus::Unsubscribed {
+        "<p>You have unsubscribed and will not receive any further issues.</p>".to_string()
+    } else {
+        format!(
+            // language=HTML
+            r#"<form action="/preferences/unsubscribe" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <button type="submit">Unsubscribe from everything</button>
+    </form>"#
+        )
+    };
+    let name = preferences.name;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscription preferences</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/preferences" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <label>Name
+            <input
+                type="text"
+                placeholder="Enter your name"
+                name="name"
+                value="{name}"
+            >
+        </label>
+        <br>
+        <p>Mailing lists:</p>
+        {lists_html}
+        <label>
+            <input type="checkbox" name="plain_text_only" value="true" {plain_text_only}>
+            Send me plain-text emails only
+        </label>
+        <br>
+        <button type="submit">Save preferences</button>
+    </form>
+    <form action="/preferences/email" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <label>New email address
+            <input
+                type="email"
+                placeholder="Enter your new email address"
+                name="new_email"
+            >
+        </label>
+        <button type="submit">Change email address</button>
+    </form>
+    {unsubscribe_html}
+    <p><a href="/preferences/data?token={token}">Download my data</a></p>
+    <form action="/preferences/erase" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <button type="submit">Erase all my data</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
+
+struct Preferences {
+    name: String,
+    status: SubscriptionStatus,
+    plain_text_only: bool,
+    list_ids: Vec<Uuid>,
+}
+
+#[tracing::instrument(name = "Get subscriber preferences", skip(pool))]
+async fn get_preferences(pool: &PgPool, subscriber_id: Uuid) -> Result<Preferences, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT name, status AS "status: SubscriptionStatus", plain_text_only
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve subscriber preferences.")?;
+    let list_ids = sqlx::query!(
+        // language=SQL
+        r#"SELECT list_id FROM list_subscriptions WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscriber lists.")?
+    .into_iter()
+    .map(|r| r.list_id)
+    .collect();
+    Ok(Preferences {
+        name: row.name,
+        status: row.status,
+        plain_text_only: row.plain_text_only,
+        list_ids,
+    })
+}
+
+#[tracing::instrument(name = "Get subscriber_id from preference token", skip_all)]
+pub async fn get_subscriber_id_from_preference_token(
+    pool: &PgPool,
+    preference_token: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let result = sqlx::query!(
+        // language=SQL
+        r#"SELECT subscriber_id FROM preference_tokens WHERE preference_token = $1"#,
+        preference_token,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up a preference token.")?;
+    Ok(result.map(|r| r.subscriber_id))
+}
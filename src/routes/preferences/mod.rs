@@ -0,0 +1,9 @@
+mod data;
+mod email;
+mod get;
+mod post;
+
+pub use data::{download_data, erase_data};
+pub use email::request_email_change;
+pub use get::preferences_form;
+pub use post::{unsubscribe, update_preferences};
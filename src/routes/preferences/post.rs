@@ -0,0 +1,161 @@
+use super::get::get_subscriber_id_from_preference_token;
+use crate::domain::{SubscriberName, SubscriptionStatus};
+use crate::routes::{update_subscription_status, UpdateStatusError};
+use crate::utils::{e400, e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    token: String,
+    name: String,
+    #[serde(default)]
+    list_id: Vec<Uuid>,
+    #[serde(default)]
+    plain_text_only: bool,
+}
+
+// Checkboxes submit one `list_id` pair per ticked list, which `web::Form` cannot collect.
+#[tracing::instrument(name = "Update subscriber preferences", skip_all)]
+pub async fn update_preferences(
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form: FormData = serde_html_form::from_bytes(&body).map_err(e400)?;
+    let Some(subscriber_id) = get_subscriber_id_from_preference_token(&pool, &form.token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    let preferences_page = format!("/preferences?token={}", form.token);
+    let Ok(name) = SubscriberName::parse(form.name) else {
+        FlashMessage::error("Please enter a valid name.").send();
+        return Ok(see_other(&preferences_page));
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    update_subscriber_details(&mut transaction, subscriber_id, &name, form.plain_text_only)
+        .await
+        .context("Failed to update subscriber details.")
+        .map_err(e500)?;
+    replace_list_subscriptions(&mut transaction, subscriber_id, &form.list_id)
+        .await
+        .context("Failed to update subscriber lists.")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update subscriber preferences.")
+        .map_err(e500)?;
+
+    FlashMessage::info("Your preferences have been updated.").send();
+    Ok(see_other(&preferences_page))
+}
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeFormData {
+    token: String,
+}
+
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip_all)]
+pub async fn unsubscribe(
+    form: web::Form<UnsubscribeFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(subscriber_id) = get_subscriber_id_from_preference_token(&pool, &form.token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    let preferences_page = format!("/preferences?token={}", form.token);
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    match update_subscription_status(
+        &mut transaction,
+        subscriber_id,
+        SubscriptionStatus::Unsubscribed,
+    )
+    .await
+    {
+        Ok(()) => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit SQL transaction to unsubscribe a subscriber.")
+                .map_err(e500)?;
+            FlashMessage::info("You have been unsubscribed.").send();
+        }
+        Err(UpdateStatusError::InvalidTransition(_)) => {
+            FlashMessage::error("Only confirmed subscriptions can be cancelled.").send();
+        }
+        Err(e @ UpdateStatusError::UnexpectedError(_)) => return Err(e500(e)),
+    }
+    Ok(see_other(&preferences_page))
+}
+
+#[tracing::instrument(skip(transaction, name))]
+async fn update_subscriber_details(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    name: &SubscriberName,
+    plain_text_only: bool,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        UPDATE subscriptions
+        SET name = $2, plain_text_only = $3
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        name.as_ref(),
+        plain_text_only,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn replace_list_subscriptions(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        DELETE FROM list_subscriptions
+        WHERE subscriber_id = $1 AND NOT (list_id = ANY($2))
+        "#,
+        subscriber_id,
+        list_ids,
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO list_subscriptions (list_id, subscriber_id, subscribed_at)
+        SELECT list_id, $1, now()
+        FROM lists
+        WHERE list_id = ANY($2)
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        list_ids,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
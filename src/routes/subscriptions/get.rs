@@ -0,0 +1,50 @@
+use crate::lists::{get_lists, list_options_html};
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+pub async fn subscribe_form(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let lists = get_lists(&pool).await.map_err(e500)?;
+    let list_options = list_options_html(&lists, None);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            // language=HTML
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscribe</title>
+</head>
+<body>
+    <form action="/subscriptions" method="post">
+        <label>Name
+            <input
+                type="text"
+                placeholder="Enter your name"
+                name="name"
+            >
+        </label>
+        <br>
+        <label>Email
+            <input
+                type="email"
+                placeholder="Enter your email"
+                name="email"
+            >
+        </label>
+        <br>
+        <label>Mailing list
+            <select name="list_id">
+                {list_options}
+            </select>
+        </label>
+        <br>
+        <button type="submit">Subscribe</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::subscribe_form;
+pub use post::*;
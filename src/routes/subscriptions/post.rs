@@ -1,5 +1,6 @@
-use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus};
 use crate::email_client::EmailClient;
+use crate::lists::{is_subscribed_to_list, list_exists, DEFAULT_LIST_ID};
 use crate::startup::ApplicationBaseUrl;
 use actix_web::http::StatusCode;
 use actix_web::{web, HttpResponse, Responder, ResponseError};
@@ -15,6 +16,7 @@ use uuid::Uuid;
 pub struct FormData {
     email: String,
     name: String,
+    list_id: Option<Uuid>,
 }
 
 impl TryFrom<FormData> for NewSubscriber {
@@ -41,18 +43,45 @@ pub async fn subscribe(
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
 ) -> Result<impl Responder, SubscribeError> {
-    let new_subscriber = form.0.try_into()?;
+    let list_id = form.list_id.unwrap_or(DEFAULT_LIST_ID);
+    let new_subscriber: NewSubscriber = form.0.try_into()?;
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    if !list_exists(&mut transaction, list_id).await? {
+        return Err(SubscribeError::ValidationError(format!(
+            "{list_id} is not a known mailing list."
+        )));
+    }
+    let subscriber_id = match get_subscriber_by_email(&mut transaction, &new_subscriber.email)
         .await
-        .context("Failed to insert new subscriber in the database.")?;
+        .context("Failed to look up an existing subscriber.")?
+    {
+        None => insert_subscriber(&mut transaction, &new_subscriber)
+            .await
+            .context("Failed to insert new subscriber in the database.")?,
+        Some((subscriber_id, SubscriptionStatus::PendingConfirmation)) => subscriber_id,
+        Some((subscriber_id, SubscriptionStatus::Confirmed))
+            if !is_subscribed_to_list(&mut transaction, list_id, subscriber_id)
+                .await
+                .context("Failed to look up a list membership.")? =>
+        {
+            subscriber_id
+        }
+        // Already on the list, or no longer accepting emails: answer the same way
+        // without telling the requester anything about the address.
+        Some(_) => return Ok(HttpResponse::Ok().finish()),
+    };
     let subscription_token = generate_subscription_token();
-    store_token(&mut transaction, subscriber_id, &subscription_token)
-        .await
-        .context("Failed to store the confirmation token for a new subscriber.")?;
+    store_token(
+        &mut transaction,
+        subscriber_id,
+        &subscription_token,
+        Some(list_id),
+    )
+    .await
+    .context("Failed to store the confirmation token for a new subscriber.")?;
     transaction
         .commit()
         .await
@@ -106,13 +135,15 @@ pub async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
     subscription_token: &str,
+    list_id: Option<Uuid>,
 ) -> Result<(), StoreTokenError> {
     let query = sqlx::query!(
         // language=SQL
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-        VALUES ($1, $2)"#,
+        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        VALUES ($1, $2, $3)"#,
         subscription_token,
-        subscriber_id
+        subscriber_id,
+        list_id,
     );
     transaction.execute(query).await.map_err(StoreTokenError)?;
     Ok(())
@@ -141,6 +172,26 @@ impl std::error::Error for StoreTokenError {
     }
 }
 
+#[tracing::instrument(name = "Get subscriber by email", skip(email, transaction))]
+pub async fn get_subscriber_by_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<Option<(Uuid, SubscriptionStatus)>, sqlx::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT id, status AS "status: SubscriptionStatus"
+        FROM subscriptions
+        WHERE email = $1
+        FOR UPDATE
+        "#,
+        email.as_ref(),
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(row.map(|r| (r.id, r.status)))
+}
+
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(new_subscriber, transaction)
@@ -153,16 +204,108 @@ pub async fn insert_subscriber(
     let query = sqlx::query!(
         // language=SQL
         r#"INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')"#,
+        VALUES ($1, $2, $3, $4, $5)"#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
         Utc::now(),
+        SubscriptionStatus::PendingConfirmation as SubscriptionStatus,
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"INSERT INTO preference_tokens (preference_token, subscriber_id)
+        VALUES ($1, $2)"#,
+        generate_subscription_token(),
+        subscriber_id,
     );
     transaction.execute(query).await?;
+    record_status_change(
+        transaction,
+        subscriber_id,
+        SubscriptionStatus::PendingConfirmation,
+    )
+    .await?;
     Ok(subscriber_id)
 }
 
+#[derive(thiserror::Error)]
+pub enum UpdateStatusError {
+    #[error("{0}")]
+    InvalidTransition(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for UpdateStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Moves a subscriber to `next`, rejecting transitions that `SubscriptionStatus` does not allow.
+/// Setting the status a subscriber already has is a no-op.
+#[tracing::instrument(name = "Update subscription status", skip(transaction))]
+pub async fn update_subscription_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    next: SubscriptionStatus,
+) -> Result<(), UpdateStatusError> {
+    let current = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT status AS "status: SubscriptionStatus"
+        FROM subscriptions
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        subscriber_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to retrieve the current subscription status.")?
+    .status;
+    if current == next {
+        return Ok(());
+    }
+    current
+        .transition_to(next)
+        .map_err(UpdateStatusError::InvalidTransition)?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"UPDATE subscriptions SET status = $2 WHERE id = $1"#,
+        subscriber_id,
+        next as SubscriptionStatus,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to update the subscription status.")?;
+    record_status_change(transaction, subscriber_id, next)
+        .await
+        .context("Failed to record the subscription status change.")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Record subscription status change", skip(transaction))]
+async fn record_status_change(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscription_status_changes (subscriber_id, status, changed_at)
+        VALUES ($1, $2, now())
+        "#,
+        subscriber_id,
+        status as SubscriptionStatus,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
     skip(email_client, new_subscriber, base_url, subscription_token)
@@ -188,7 +331,7 @@ pub async fn send_confirmation_email(
         .await
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = rand::rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
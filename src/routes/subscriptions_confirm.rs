@@ -1,5 +1,13 @@
-use actix_web::{web, HttpResponse};
-use sqlx::PgPool;
+use crate::domain::{SubscriberEmail, SubscriberName, SubscriptionStatus};
+use crate::email_client::EmailClient;
+use crate::lists::add_subscriber_to_list;
+use crate::routes::{
+    error_chain_fmt, get_subscriber_by_email, update_subscription_status, UpdateStatusError,
+};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::{Executor, PgPool};
 use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
@@ -7,48 +15,212 @@ pub struct Parameters {
     subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
-pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>) -> HttpResponse {
-    let id = match get_subscriber_id_from_token(&pool, &parameters.subscription_token).await {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
-    };
-    match id {
-        // Non-existing token!
-        None => HttpResponse::Unauthorized().finish(),
-        Some(subscriber_id) => {
-            if confirm_subscriber(&pool, subscriber_id).await.is_err() {
-                return HttpResponse::InternalServerError().finish();
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(parameters, pool, email_client)
+)]
+pub async fn confirm(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+) -> Result<HttpResponse, ConfirmError> {
+    let token = get_subscriber_id_from_token(&pool, &parameters.subscription_token)
+        .await
+        .context("Failed to retrieve the subscriber id associated with the provided token.")?
+        .ok_or(ConfirmError::UnknownToken)?;
+    let subscriber_id = token.subscriber_id;
+    match token.new_email {
+        None => confirm_subscriber(&pool, subscriber_id, token.list_id).await?,
+        Some(new_email) => {
+            change_subscriber_email(
+                &pool,
+                &email_client,
+                subscriber_id,
+                &parameters.subscription_token,
+                new_email,
+            )
+            .await?
+        }
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(thiserror::Error)]
+pub enum ConfirmError {
+    #[error("There is no subscriber associated with the provided token.")]
+    UnknownToken,
+    #[error("{0}")]
+    InvalidTransition(String),
+    #[error("The new email address is already in use by another subscriber.")]
+    EmailAlreadyInUse,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ConfirmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ConfirmError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfirmError::UnknownToken => StatusCode::UNAUTHORIZED,
+            ConfirmError::InvalidTransition(_) | ConfirmError::EmailAlreadyInUse => {
+                StatusCode::CONFLICT
             }
-            HttpResponse::Ok().finish()
+            ConfirmError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
+impl From<UpdateStatusError> for ConfirmError {
+    fn from(e: UpdateStatusError) -> Self {
+        match e {
+            UpdateStatusError::InvalidTransition(e) => Self::InvalidTransition(e),
+            UpdateStatusError::UnexpectedError(e) => Self::UnexpectedError(e),
+        }
+    }
+}
+
+/// Confirms the subscriber, if they are not already, and adds them to the list
+/// the token was issued for.
 #[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
+pub async fn confirm_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+) -> Result<(), ConfirmError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    update_subscription_status(
+        &mut transaction,
+        subscriber_id,
+        SubscriptionStatus::Confirmed,
+    )
+    .await?;
+    if let Some(list_id) = list_id {
+        add_subscriber_to_list(&mut transaction, list_id, subscriber_id)
+            .await
+            .context("Failed to add the subscriber to the mailing list.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to confirm a subscriber.")?;
+    Ok(())
+}
+
+/// Switches a subscriber to the address carried by an email change token.
+/// The token is consumed, and the previous address is told about the change.
+#[tracing::instrument(
+    name = "Change subscriber email",
+    skip(pool, email_client, subscription_token, new_email)
+)]
+pub async fn change_subscriber_email(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    subscriber_id: Uuid,
+    subscription_token: &str,
+    new_email: String,
+) -> Result<(), ConfirmError> {
+    let new_email = SubscriberEmail::parse(new_email).map_err(|e| anyhow::anyhow!(e))?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let current = sqlx::query!(
         // language=SQL
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+        r#"SELECT email, name FROM subscriptions WHERE id = $1 FOR UPDATE"#,
         subscriber_id,
     )
-    .execute(pool)
+    .fetch_one(&mut *transaction)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
-    })?;
+    .context("Failed to retrieve the current subscriber details.")?;
+    // Someone else may have subscribed with this address since the change was requested.
+    match get_subscriber_by_email(&mut transaction, &new_email)
+        .await
+        .context("Failed to look up an existing subscriber.")?
+    {
+        Some((id, _)) if id != subscriber_id => return Err(ConfirmError::EmailAlreadyInUse),
+        _ => {}
+    }
+    let query = sqlx::query!(
+        // language=SQL
+        r#"UPDATE subscriptions SET email = $2 WHERE id = $1"#,
+        subscriber_id,
+        new_email.as_ref(),
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to update the subscriber email.")?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"DELETE FROM subscription_tokens WHERE subscription_token = $1"#,
+        subscription_token,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete the email change token.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to change a subscriber email.")?;
+
+    if let Err(e) = send_email_changed_notice(email_client, current.email, current.name).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to notify the previous address of an email change.",
+        );
+    }
     Ok(())
 }
 
+#[tracing::instrument(skip_all)]
+async fn send_email_changed_notice(
+    email_client: &EmailClient,
+    old_email: String,
+    name: String,
+) -> Result<(), anyhow::Error> {
+    let old_email = SubscriberEmail::parse(old_email).map_err(|e| anyhow::anyhow!(e))?;
+    let name = SubscriberName::parse(name).map_err(|e| anyhow::anyhow!(e))?;
+    let plain_body = "The email address of your newsletter subscription has been changed.\n\
+        You will no longer receive our newsletter at this address.";
+    let html_body = "The email address of your newsletter subscription has been changed.<br />\
+        You will no longer receive our newsletter at this address.";
+    email_client
+        .send_email(
+            &old_email,
+            &name,
+            "Your email address has been changed",
+            html_body,
+            plain_body,
+        )
+        .await?;
+    Ok(())
+}
+
+pub struct TokenDetails {
+    pub subscriber_id: Uuid,
+    pub new_email: Option<String>,
+    pub list_id: Option<Uuid>,
+}
+
 #[tracing::instrument(name = "Get subscriber_id from token", skip(subscription_token, pool))]
 pub async fn get_subscriber_id_from_token(
     pool: &PgPool,
     subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
+) -> Result<Option<TokenDetails>, sqlx::Error> {
+    sqlx::query_as!(
+        TokenDetails,
         // language=SQL
-        "SELECT subscriber_id FROM subscription_tokens \
+        "SELECT subscriber_id, new_email, list_id FROM subscription_tokens \
         WHERE subscription_token = $1",
         subscription_token,
     )
@@ -57,6 +229,5 @@ pub async fn get_subscriber_id_from_token(
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         e
-    })?;
-    Ok(result.map(|r| r.subscriber_id))
+    })
 }
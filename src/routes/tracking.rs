@@ -0,0 +1,108 @@
+use crate::tracking::{ClickToken, OpenToken, TrackingKey};
+use crate::utils::e500;
+use actix_web::http::header::{CacheControl, CacheDirective, LOCATION};
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A transparent 1x1 GIF.
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x01, 0x44, 0x00, 0x3b,
+];
+
+#[tracing::instrument(name = "Track an open", skip_all)]
+pub async fn track_open(
+    token: web::Path<String>,
+    pool: web::Data<PgPool>,
+    key: web::Data<TrackingKey>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(token) = OpenToken::decode(&token, &key) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    record_open(&pool, token.newsletter_issue_id, token.subscriber_id)
+        .await
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/gif")
+        // Every fetch is an open: make sure mail clients come back to us.
+        .insert_header(CacheControl(vec![
+            CacheDirective::NoStore,
+            CacheDirective::Private,
+        ]))
+        .body(PIXEL))
+}
+
+#[tracing::instrument(name = "Track a click", skip_all)]
+pub async fn track_click(
+    token: web::Path<String>,
+    pool: web::Data<PgPool>,
+    key: web::Data<TrackingKey>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(token) = ClickToken::decode(&token, &key) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    // Failing to record a click must not stand between the reader and the link.
+    if let Err(e) = record_click(&pool, &token).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to record a click.",
+        );
+    }
+    Ok(HttpResponse::Found()
+        .insert_header((LOCATION, token.url))
+        .finish())
+}
+
+#[tracing::instrument(skip(pool))]
+async fn record_open(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO issue_opens (
+            newsletter_issue_id, subscriber_id, first_opened_at, last_opened_at, open_count
+        )
+        SELECT newsletter_issue_id, $2, now(), now(), 1
+        FROM newsletter_issues
+        WHERE
+            newsletter_issue_id = $1 AND
+            -- Opens of emails sent to since-erased subscribers are not recorded.
+            EXISTS (SELECT 1 FROM subscriptions WHERE id = $2)
+        ON CONFLICT (newsletter_issue_id, subscriber_id) DO UPDATE
+        SET last_opened_at = now(), open_count = issue_opens.open_count + 1
+        "#,
+        newsletter_issue_id,
+        subscriber_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool, token), fields(url = %token.url))]
+async fn record_click(pool: &PgPool, token: &ClickToken) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO issue_clicks (newsletter_issue_id, subscriber_id, url, clicked_at)
+        SELECT newsletter_issue_id, $2, $3, now()
+        FROM newsletter_issues
+        WHERE
+            newsletter_issue_id = $1 AND
+            -- Clicks in emails sent to since-erased subscribers are not recorded.
+            EXISTS (SELECT 1 FROM subscriptions WHERE id = $2)
+        "#,
+        token.newsletter_issue_id,
+        token.subscriber_id,
+        token.url,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
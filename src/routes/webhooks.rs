@@ -0,0 +1,125 @@
+use crate::domain::SubscriberEmail;
+use crate::email_events::{EmailEvent, EventWebhookKey, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::routes::{
+    error_chain_fmt, get_subscriber_by_email, update_subscription_status, UpdateStatusError,
+};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error("The request signature is missing or invalid.")]
+    InvalidSignature,
+    #[error("{0}")]
+    InvalidPayload(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            WebhookError::InvalidSignature => StatusCode::UNAUTHORIZED,
+            WebhookError::InvalidPayload(_) => StatusCode::BAD_REQUEST,
+            WebhookError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Receive email events", skip_all)]
+pub async fn receive_email_events(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    key: web::Data<EventWebhookKey>,
+) -> Result<HttpResponse, WebhookError> {
+    let header = |name| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|h| h.to_str().ok())
+            .ok_or(WebhookError::InvalidSignature)
+    };
+    if !key.verify(header(TIMESTAMP_HEADER)?, &body, header(SIGNATURE_HEADER)?) {
+        return Err(WebhookError::InvalidSignature);
+    }
+    let events: Vec<EmailEvent> =
+        serde_json::from_slice(&body).map_err(|e| WebhookError::InvalidPayload(e.to_string()))?;
+
+    for event in events {
+        process_event(&pool, event).await?;
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(
+    skip(pool, event),
+    fields(event_id = %event.sg_event_id, event_type = %event.event)
+)]
+async fn process_event(pool: &PgPool, event: EmailEvent) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let subscriber_id = match SubscriberEmail::parse(event.email.clone()) {
+        Ok(email) => get_subscriber_by_email(&mut transaction, &email)
+            .await
+            .context("Failed to look up the subscriber.")?
+            .map(|(id, _)| id),
+        Err(_) => None,
+    };
+    if !record_event(&mut transaction, subscriber_id, &event)
+        .await
+        .context("Failed to record the email event.")?
+    {
+        tracing::info!("Skipping an email event we have already processed.");
+        return Ok(());
+    }
+    if let (Some(subscriber_id), Some(next)) = (subscriber_id, event.subscription_status()) {
+        match update_subscription_status(&mut transaction, subscriber_id, next).await {
+            Ok(()) => {}
+            Err(UpdateStatusError::InvalidTransition(e)) => {
+                tracing::info!("Not applying email event: {e}");
+            }
+            Err(UpdateStatusError::UnexpectedError(e)) => return Err(e),
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to record an email event.")?;
+    Ok(())
+}
+
+/// Returns `false` if the event had already been recorded.
+async fn record_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Option<Uuid>,
+    event: &EmailEvent,
+) -> Result<bool, sqlx::Error> {
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO email_events (
+            event_id, subscriber_id, event_type, reason, occurred_at, received_at
+        )
+        VALUES ($1, $2, $3, $4, $5, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        event.sg_event_id,
+        subscriber_id,
+        event.event,
+        event.reason,
+        event.occurred_at(),
+    );
+    Ok(transaction.execute(query).await?.rows_affected() == 1)
+}
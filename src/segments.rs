@@ -0,0 +1,354 @@
+use crate::domain::SubscriptionStatus;
+use anyhow::Context;
+use chrono::NaiveDate;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::fmt::Write;
+use unicode_segmentation::UnicodeSegmentation;
+use uuid::Uuid;
+
+/// A saved audience. Every rule that is set must match for a subscriber to be included;
+/// membership itself is decided in the database by `segment_members`.
+pub struct Segment {
+    pub segment_id: Uuid,
+    pub name: String,
+    pub subscribed_after: Option<NaiveDate>,
+    pub subscribed_before: Option<NaiveDate>,
+    pub list_name: Option<String>,
+    pub opened_in_last_issues: Option<i32>,
+    pub email_domain: Option<String>,
+}
+
+impl Segment {
+    /// A human-readable summary of the rules, for admin pages. Not HTML-escaped.
+    pub fn describe(&self) -> String {
+        let mut rules = vec![];
+        if let Some(date) = self.subscribed_after {
+            rules.push(format!("subscribed on or after {date}"));
+        }
+        if let Some(date) = self.subscribed_before {
+            rules.push(format!("subscribed before {date}"));
+        }
+        if let Some(list_name) = &self.list_name {
+            rules.push(format!("member of {list_name}"));
+        }
+        if let Some(n) = self.opened_in_last_issues {
+            rules.push(format!("opened one of the last {n} issues"));
+        }
+        if let Some(domain) = &self.email_domain {
+            rules.push(format!("email address at {domain}"));
+        }
+        rules.join(", ")
+    }
+}
+
+#[derive(Debug)]
+pub struct NewSegment {
+    pub name: String,
+    pub subscribed_after: Option<NaiveDate>,
+    pub subscribed_before: Option<NaiveDate>,
+    pub list_id: Option<Uuid>,
+    pub opened_in_last_issues: Option<i32>,
+    pub email_domain: Option<String>,
+}
+
+/// The segment form as submitted: empty fields mean "any".
+pub struct SegmentForm<'a> {
+    pub name: &'a str,
+    pub subscribed_after: &'a str,
+    pub subscribed_before: &'a str,
+    pub list_id: &'a str,
+    pub opened_in_last_issues: &'a str,
+    pub email_domain: &'a str,
+}
+
+impl NewSegment {
+    pub fn parse(form: SegmentForm<'_>) -> Result<NewSegment, String> {
+        let name = form.name.trim();
+        if name.is_empty() || name.graphemes(true).count() > 100 {
+            return Err("Please enter a segment name of at most 100 characters.".into());
+        }
+        let subscribed_after = parse_optional(form.subscribed_after, |s| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| "Please enter valid dates.")
+        })?;
+        let subscribed_before = parse_optional(form.subscribed_before, |s| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| "Please enter valid dates.")
+        })?;
+        if let (Some(after), Some(before)) = (subscribed_after, subscribed_before) {
+            if after >= before {
+                return Err("The subscription date range is empty.".into());
+            }
+        }
+        let list_id = parse_optional(form.list_id, |s| {
+            Uuid::parse_str(s).map_err(|_| "Please pick a known mailing list.")
+        })?;
+        let opened_in_last_issues = parse_optional(form.opened_in_last_issues, |s| {
+            s.parse::<i32>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or("The number of issues must be a positive whole number.")
+        })?;
+        let email_domain = parse_optional(form.email_domain, |s| {
+            let domain = s.trim_start_matches('@').to_lowercase();
+            let is_valid = !domain.is_empty()
+                && domain
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '.' || c == '-');
+            if is_valid {
+                Ok(domain)
+            } else {
+                Err("Please enter an email domain such as example.com.")
+            }
+        })?;
+        if subscribed_after.is_none()
+            && subscribed_before.is_none()
+            && list_id.is_none()
+            && opened_in_last_issues.is_none()
+            && email_domain.is_none()
+        {
+            return Err("A segment needs at least one rule.".into());
+        }
+        Ok(NewSegment {
+            name: name.to_string(),
+            subscribed_after,
+            subscribed_before,
+            list_id,
+            opened_in_last_issues,
+            email_domain,
+        })
+    }
+}
+
+fn parse_optional<T>(
+    s: &str,
+    parse: impl FnOnce(&str) -> Result<T, &'static str>,
+) -> Result<Option<T>, String> {
+    match s.trim() {
+        "" => Ok(None),
+        s => parse(s).map(Some).map_err(String::from),
+    }
+}
+
+#[tracing::instrument(name = "Get segments", skip(pool))]
+pub async fn get_segments(pool: &PgPool) -> Result<Vec<Segment>, anyhow::Error> {
+    let segments = sqlx::query_as!(
+        Segment,
+        // language=SQL
+        r#"
+        SELECT
+            segment_id,
+            segments.name,
+            subscribed_after,
+            subscribed_before,
+            lists.name AS "list_name?",
+            opened_in_last_issues,
+            email_domain
+        FROM segments
+        LEFT JOIN lists ON lists.list_id = segments.list_id
+        ORDER BY segments.created_at, segments.name
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve segments.")?;
+    Ok(segments)
+}
+
+#[tracing::instrument(name = "Check that a segment exists", skip(transaction))]
+pub async fn segment_exists(
+    transaction: &mut Transaction<'_, Postgres>,
+    segment_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"SELECT segment_id FROM segments WHERE segment_id = $1"#,
+        segment_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to look up a segment.")?;
+    Ok(row.is_some())
+}
+
+/// Returns `None` if a segment with the same name already exists.
+#[tracing::instrument(name = "Insert a segment", skip(pool))]
+pub async fn insert_segment(
+    pool: &PgPool,
+    segment: &NewSegment,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let segment_id = Uuid::new_v4();
+    let n_inserted_rows = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO segments (
+            segment_id,
+            name,
+            subscribed_after,
+            subscribed_before,
+            list_id,
+            opened_in_last_issues,
+            email_domain,
+            created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, now())
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        segment_id,
+        segment.name,
+        segment.subscribed_after,
+        segment.subscribed_before,
+        segment.list_id,
+        segment.opened_in_last_issues,
+        segment.email_domain,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert a segment.")?
+    .rows_affected();
+    Ok((n_inserted_rows > 0).then_some(segment_id))
+}
+
+/// How many subscribers an issue published to `list_id` and `segment_id` would go out to.
+#[tracing::instrument(name = "Count recipients", skip(pool))]
+pub async fn count_recipients(
+    pool: &PgPool,
+    list_id: Uuid,
+    segment_id: Option<Uuid>,
+) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT count(*) AS "n!"
+        FROM subscriptions
+        JOIN list_subscriptions
+            ON list_subscriptions.subscriber_id = subscriptions.id
+        WHERE
+            list_subscriptions.list_id = $1 AND
+            subscriptions.status = $2 AND
+            ($3::uuid IS NULL OR subscriptions.id IN (SELECT subscriber_id FROM segment_members($3)))
+        "#,
+        list_id,
+        SubscriptionStatus::Confirmed as SubscriptionStatus,
+        segment_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count recipients.")?;
+    Ok(row.n)
+}
+
+/// Renders `<option>` elements for a segment selector, starting with "everyone".
+pub fn segment_options_html(segments: &[Segment], selected: Option<Uuid>) -> String {
+    let mut html = String::from(r#"<option value="">All subscribers</option>"#);
+    for segment in segments {
+        let is_selected = if Some(segment.segment_id) == selected {
+            " selected"
+        } else {
+            ""
+        };
+        writeln!(
+            html,
+            r#"<option value="{}"{is_selected}>{}</option>"#,
+            segment.segment_id,
+            html_escape::encode_text(&segment.name)
+        )
+        .unwrap();
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NewSegment, SegmentForm};
+    use chrono::NaiveDate;
+    use claims::{assert_err, assert_ok};
+
+    fn form() -> SegmentForm<'static> {
+        SegmentForm {
+            name: "Recent",
+            subscribed_after: "",
+            subscribed_before: "",
+            list_id: "",
+            opened_in_last_issues: "",
+            email_domain: "",
+        }
+    }
+
+    #[test]
+    fn a_segment_needs_at_least_one_rule() {
+        assert_err!(NewSegment::parse(form()));
+    }
+
+    #[test]
+    fn a_segment_needs_a_name() {
+        assert_err!(NewSegment::parse(SegmentForm {
+            name: " ",
+            email_domain: "example.com",
+            ..form()
+        }));
+    }
+
+    #[test]
+    fn a_date_range_is_parsed() {
+        let segment = assert_ok!(NewSegment::parse(SegmentForm {
+            subscribed_after: "2024-01-01",
+            subscribed_before: "2024-02-01",
+            ..form()
+        }));
+
+        assert_eq!(
+            segment.subscribed_after,
+            NaiveDate::from_ymd_opt(2024, 1, 1)
+        );
+        assert_eq!(
+            segment.subscribed_before,
+            NaiveDate::from_ymd_opt(2024, 2, 1)
+        );
+    }
+
+    #[test]
+    fn an_empty_date_range_is_rejected() {
+        assert_err!(NewSegment::parse(SegmentForm {
+            subscribed_after: "2024-02-01",
+            subscribed_before: "2024-02-01",
+            ..form()
+        }));
+    }
+
+    #[test]
+    fn invalid_dates_are_rejected() {
+        assert_err!(NewSegment::parse(SegmentForm {
+            subscribed_after: "01/02/2024",
+            ..form()
+        }));
+    }
+
+    #[test]
+    fn the_number_of_issues_must_be_positive() {
+        for n in ["0", "-1", "two"] {
+            assert_err!(NewSegment::parse(SegmentForm {
+                opened_in_last_issues: n,
+                ..form()
+            }));
+        }
+    }
+
+    #[test]
+    fn email_domains_are_normalized() {
+        let segment = assert_ok!(NewSegment::parse(SegmentForm {
+            email_domain: " @Example.COM ",
+            ..form()
+        }));
+
+        assert_eq!(segment.email_domain.as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn email_domains_cannot_contain_special_characters() {
+        for domain in ["a@b.com", "exa mple.com", "<b>"] {
+            assert_err!(NewSegment::parse(SegmentForm {
+                email_domain: domain,
+                ..form()
+            }));
+        }
+    }
+}
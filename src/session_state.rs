@@ -9,12 +9,18 @@ pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const SESSION_ID_KEY: &'static str = "session_id";
+    const PENDING_USER_ID_KEY: &'static str = "pending_user_id";
+    const PENDING_TOTP_SECRET_KEY: &'static str = "pending_totp_secret";
+    const WRONG_CODES_KEY: &'static str = "wrong_codes";
 
     pub fn renew(&self) {
         self.0.renew();
     }
 
     pub fn insert_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+        self.0.remove(Self::WRONG_CODES_KEY);
         self.0.insert(Self::USER_ID_KEY, user_id)
     }
 
@@ -22,6 +28,53 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    /// Identifies the login in the `user_sessions` table, so that it can be revoked.
+    pub fn insert_session_id(&self, session_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::SESSION_ID_KEY, session_id)
+    }
+
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+
+    /// Half-authenticated: the password was right, but the second factor
+    /// has not been checked yet. The user is not logged in until then.
+    pub fn insert_pending_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.remove(Self::USER_ID_KEY);
+        self.0.remove(Self::WRONG_CODES_KEY);
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)
+    }
+
+    pub fn get_pending_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    /// Gives up on the half-authenticated login: the password has to be entered again.
+    pub fn remove_pending_user_id(&self) {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+        self.0.remove(Self::WRONG_CODES_KEY);
+    }
+
+    /// Counts a wrong second-factor code against the pending login and returns the total so far.
+    pub fn record_wrong_code(&self) -> Result<u32, anyhow::Error> {
+        let wrong_codes = self.0.get::<u32>(Self::WRONG_CODES_KEY)?.unwrap_or(0) + 1;
+        self.0.insert(Self::WRONG_CODES_KEY, wrong_codes)?;
+        Ok(wrong_codes)
+    }
+
+    /// The secret shown during enrolment, kept until the user confirms it with a code.
+    pub fn insert_pending_totp_secret(&self, secret: &str) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_TOTP_SECRET_KEY, secret)
+    }
+
+    pub fn get_pending_totp_secret(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::PENDING_TOTP_SECRET_KEY)
+    }
+
+    pub fn remove_pending_totp_secret(&self) {
+        self.0.remove(Self::PENDING_TOTP_SECRET_KEY);
+    }
+
     pub fn log_out(self) {
         self.0.purge()
     }
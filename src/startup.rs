@@ -1,12 +1,40 @@
-use crate::authentication::reject_anonymous_users;
+use crate::authentication::{
+    reject_anonymous_users, require_permission, session_lifecycle, PasswordHashingSettings,
+    PasswordPolicy, Permission,
+};
+use crate::client_ip::TrustedProxies;
 use crate::configuration::{DatabaseSettings, Settings};
 use crate::email_client::EmailClient;
+use crate::email_events::EventWebhookKey;
 use crate::routes::admin_dashboard;
-use crate::routes::{change_password, change_password_form};
-use crate::routes::{confirm, subscribe};
-use crate::routes::{health_check, home};
-use crate::routes::{log_out, login, login_form};
-use crate::routes::{publish_newsletter, publish_newsletter_form};
+use crate::routes::{accept_invitation, invitation_form};
+use crate::routes::{cancel_issue, pause_issue, resume_issue};
+use crate::routes::{
+    change_account_email, change_password, change_password_form, confirm_account_email,
+};
+use crate::routes::{change_user_role, clear_login_lock, invite_user, remove_user};
+use crate::routes::{confirm, subscribe, subscribe_form};
+use crate::routes::{
+    confirm_subscriber_manually, delete_subscriber, subscriber_details, subscribers_list,
+    unsubscribe_subscriber,
+};
+use crate::routes::{create_list, lists_form};
+use crate::routes::{create_segment, segments_form};
+use crate::routes::{download_data, erase_data};
+use crate::routes::{enrol_two_factor, remove_two_factor, two_factor_form};
+use crate::routes::{export_subscribers, import_subscribers_form, import_subscribers_from_csv};
+use crate::routes::{health_check, home, receive_email_events};
+use crate::routes::{issue_analytics, open_rates, publish_newsletter, publish_newsletter_form};
+use crate::routes::{log_out, login, login_form, login_two_factor, login_two_factor_form};
+use crate::routes::{log_out_other_sessions, log_out_session, sessions_list};
+use crate::routes::{
+    password_reset_form, request_password_reset, reset_password, reset_password_form,
+};
+use crate::routes::{preferences_form, request_email_change, unsubscribe, update_preferences};
+use crate::routes::{require_two_factor, users_list};
+use crate::routes::{track_click, track_open};
+use crate::tracking::TrackingKey;
+use actix_multipart::form::MultipartFormConfig;
 use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
 use actix_web::cookie::Key;
@@ -31,6 +59,9 @@ pub struct Application {
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         let connection_pool = get_connection_pool(&configuration.database);
+        let event_webhook_key = configuration.email_client.event_webhook_key()?;
+        // Fail at startup rather than on the first login.
+        configuration.password_hashing.params()?;
         let email_client = configuration.email_client.client();
 
         let address = format!(
@@ -43,9 +74,13 @@ impl Application {
             listener,
             connection_pool,
             email_client,
+            event_webhook_key,
             configuration.application.base_url,
             configuration.application.hmac_secret,
+            configuration.application.trusted_proxies,
             configuration.redis_uri,
+            configuration.password_policy,
+            configuration.password_hashing,
         )
         .await?;
 
@@ -67,48 +102,237 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
 
 pub struct ApplicationBaseUrl(pub String);
 
+const MAX_UPLOAD_SIZE: usize = 20 * 1024 * 1024;
+
+#[allow(clippy::too_many_arguments)]
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
+    event_webhook_key: EventWebhookKey,
     base_url: String,
     hmac_secret: SecretString,
+    trusted_proxies: TrustedProxies,
     redis_uri: SecretString,
+    password_policy: PasswordPolicy,
+    password_hashing: PasswordHashingSettings,
 ) -> Result<Server, anyhow::Error> {
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
     let db_pool = Data::new(db_pool);
     let email_client = Data::new(email_client);
+    let event_webhook_key = Data::new(event_webhook_key);
     let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let tracking_key = Data::new(TrackingKey::new(hmac_secret.clone()));
+    let password_policy = Data::new(password_policy);
+    let password_hashing = Data::new(password_hashing);
+    let trusted_proxies = Data::new(trusted_proxies);
     let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
     let server = HttpServer::new(move || {
         App::new()
             .wrap(message_framework.clone())
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
-            ))
+            .wrap(
+                SessionMiddleware::builder(redis_store.clone(), secret_key.clone())
+                    .session_lifecycle(session_lifecycle())
+                    .build(),
+            )
             .wrap(TracingLogger::default())
             .route("/", web::get().to(home))
             .service(
                 web::scope("/admin")
                     .wrap(from_fn(reject_anonymous_users))
+                    // Subscriber imports are held in memory while they are processed.
+                    .app_data(
+                        MultipartFormConfig::default()
+                            .total_limit(MAX_UPLOAD_SIZE)
+                            .memory_limit(MAX_UPLOAD_SIZE),
+                    )
                     .route("/dashboard", web::get().to(admin_dashboard))
+                    .route("/lists", web::get().to(lists_form))
+                    .route(
+                        "/lists",
+                        web::post()
+                            .to(create_list)
+                            .wrap(require_permission(Permission::ManageAudience)),
+                    )
                     .route("/logout", web::post().to(log_out))
-                    .route("/newsletters", web::get().to(publish_newsletter_form))
-                    .route("/newsletters", web::post().to(publish_newsletter))
+                    .route(
+                        "/newsletters",
+                        web::get()
+                            .to(publish_newsletter_form)
+                            .wrap(require_permission(Permission::PublishIssues)),
+                    )
+                    .route(
+                        "/newsletters",
+                        web::post()
+                            .to(publish_newsletter)
+                            .wrap(require_permission(Permission::PublishIssues)),
+                    )
+                    .route("/newsletters/opens", web::get().to(open_rates))
+                    .route(
+                        "/newsletters/{newsletter_issue_id}",
+                        web::get().to(issue_analytics),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/pause",
+                        web::post()
+                            .to(pause_issue)
+                            .wrap(require_permission(Permission::PublishIssues)),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/resume",
+                        web::post()
+                            .to(resume_issue)
+                            .wrap(require_permission(Permission::PublishIssues)),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/cancel",
+                        web::post()
+                            .to(cancel_issue)
+                            .wrap(require_permission(Permission::PublishIssues)),
+                    )
                     .route("/password", web::get().to(change_password_form))
-                    .route("/password", web::post().to(change_password)),
+                    .route("/password", web::post().to(change_password))
+                    .route("/password/email", web::post().to(change_account_email))
+                    .route("/segments", web::get().to(segments_form))
+                    .route(
+                        "/segments",
+                        web::post()
+                            .to(create_segment)
+                            .wrap(require_permission(Permission::ManageAudience)),
+                    )
+                    .route("/sessions", web::get().to(sessions_list))
+                    .route(
+                        "/sessions/revoke-others",
+                        web::post().to(log_out_other_sessions),
+                    )
+                    .route(
+                        "/sessions/{session_id}/revoke",
+                        web::post().to(log_out_session),
+                    )
+                    .route("/subscribers", web::get().to(subscribers_list))
+                    .route(
+                        "/subscribers/export",
+                        web::get()
+                            .to(export_subscribers)
+                            .wrap(require_permission(Permission::ManageAudience)),
+                    )
+                    .route(
+                        "/subscribers/import",
+                        web::get()
+                            .to(import_subscribers_form)
+                            .wrap(require_permission(Permission::ManageAudience)),
+                    )
+                    .route(
+                        "/subscribers/import",
+                        web::post()
+                            .to(import_subscribers_from_csv)
+                            .wrap(require_permission(Permission::ManageAudience)),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::get().to(subscriber_details),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/confirm",
+                        web::post()
+                            .to(confirm_subscriber_manually)
+                            .wrap(require_permission(Permission::ManageAudience)),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/unsubscribe",
+                        web::post()
+                            .to(unsubscribe_subscriber)
+                            .wrap(require_permission(Permission::ManageAudience)),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/delete",
+                        web::post()
+                            .to(delete_subscriber)
+                            .wrap(require_permission(Permission::ManageAudience)),
+                    )
+                    .route("/two-factor", web::get().to(two_factor_form))
+                    .route("/two-factor", web::post().to(enrol_two_factor))
+                    .route("/two-factor/disable", web::post().to(remove_two_factor))
+                    .route(
+                        "/users",
+                        web::get()
+                            .to(users_list)
+                            .wrap(require_permission(Permission::ManageUsers)),
+                    )
+                    .route(
+                        "/users/invitations",
+                        web::post()
+                            .to(invite_user)
+                            .wrap(require_permission(Permission::ManageUsers)),
+                    )
+                    .route(
+                        "/users/locks/clear",
+                        web::post()
+                            .to(clear_login_lock)
+                            .wrap(require_permission(Permission::ManageUsers)),
+                    )
+                    .route(
+                        "/users/two-factor",
+                        web::post()
+                            .to(require_two_factor)
+                            .wrap(require_permission(Permission::ManageUsers)),
+                    )
+                    .route(
+                        "/users/{user_id}/role",
+                        web::post()
+                            .to(change_user_role)
+                            .wrap(require_permission(Permission::ManageUsers)),
+                    )
+                    .route(
+                        "/users/{user_id}/delete",
+                        web::post()
+                            .to(remove_user)
+                            .wrap(require_permission(Permission::ManageUsers)),
+                    ),
+            )
+            .route(
+                "/account-email/confirm",
+                web::get().to(confirm_account_email),
             )
             .route("/health_check", web::get().to(health_check))
+            .route("/invitations", web::get().to(invitation_form))
+            .route("/invitations", web::post().to(accept_invitation))
             .route("/login", web::get().to(login_form))
             .route("/login", web::post().to(login))
+            .route("/login/two-factor", web::get().to(login_two_factor_form))
+            .route("/login/two-factor", web::post().to(login_two_factor))
+            .route("/o/{token}", web::get().to(track_open))
+            .route("/password-reset", web::get().to(password_reset_form))
+            .route("/password-reset", web::post().to(request_password_reset))
+            .route(
+                "/password-reset/confirm",
+                web::get().to(reset_password_form),
+            )
+            .route("/password-reset/confirm", web::post().to(reset_password))
+            .route("/preferences", web::get().to(preferences_form))
+            .route("/preferences", web::post().to(update_preferences))
+            .route("/preferences/data", web::get().to(download_data))
+            .route("/preferences/email", web::post().to(request_email_change))
+            .route("/preferences/erase", web::post().to(erase_data))
+            .route("/preferences/unsubscribe", web::post().to(unsubscribe))
+            .route("/r/{token}", web::get().to(track_click))
+            .route("/subscriptions", web::get().to(subscribe_form))
             .route("/subscriptions", web::post().to(subscribe))
             .route("/subscriptions/confirm", web::get().to(confirm))
+            .route(
+                "/webhooks/email-events",
+                web::post().to(receive_email_events),
+            )
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
+            .app_data(event_webhook_key.clone())
             .app_data(base_url.clone())
+            .app_data(tracking_key.clone())
+            .app_data(password_policy.clone())
+            .app_data(password_hashing.clone())
+            .app_data(trusted_proxies.clone())
     })
     .listen(listener)?
     .run();
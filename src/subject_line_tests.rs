@@ -0,0 +1,435 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "subject_line_test_metric", rename_all = "snake_case")]
+pub enum Metric {
+    OpenRate,
+    ClickRate,
+}
+
+impl Metric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Metric::OpenRate => "open_rate",
+            Metric::ClickRate => "click_rate",
+        }
+    }
+}
+
+impl std::fmt::Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Metric::OpenRate => "open rate",
+            Metric::ClickRate => "click rate",
+        }
+        .fmt(f)
+    }
+}
+
+impl TryFrom<&str> for Metric {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "open_rate" => Ok(Metric::OpenRate),
+            "click_rate" => Ok(Metric::ClickRate),
+            other => Err(format!("{other} is not a known way to pick a winner.")),
+        }
+    }
+}
+
+/// Sends each subject variant to its share of a random sample of the audience, then the
+/// best performing one to everybody else once `wait_hours` have passed.
+#[derive(Debug)]
+pub struct SubjectLineTest {
+    pub subjects: Vec<String>,
+    pub sample_percent: i32,
+    pub wait_hours: i32,
+    pub metric: Metric,
+}
+
+impl SubjectLineTest {
+    /// Returns `None` when no variants were entered: the issue goes out with its title as subject.
+    pub fn parse(
+        subject_variants: &str,
+        sample_percent: &str,
+        wait_hours: &str,
+        metric: &str,
+    ) -> Result<Option<SubjectLineTest>, String> {
+        let subjects: Vec<String> = subject_variants
+            .lines()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect();
+        if subjects.is_empty() {
+            return Ok(None);
+        }
+        if subjects.len() < 2 {
+            return Err("Please enter at least two subject variants, one per line.".into());
+        }
+        let sample_percent = sample_percent
+            .trim()
+            .parse::<i32>()
+            .ok()
+            .filter(|p| (1..=100).contains(p))
+            .ok_or("The test audience must be between 1 and 100 percent.")?;
+        let wait_hours = wait_hours
+            .trim()
+            .parse::<i32>()
+            .ok()
+            .filter(|h| (0..=24 * 7).contains(h))
+            .ok_or("The waiting period must be between 0 and 168 hours.")?;
+        let metric = Metric::try_from(metric)?;
+        Ok(Some(SubjectLineTest {
+            subjects,
+            sample_percent,
+            wait_hours,
+            metric,
+        }))
+    }
+}
+
+pub struct VariantResult {
+    pub variant_id: Uuid,
+    pub position: i32,
+    pub subject: String,
+    pub sent: i64,
+    pub opened: i64,
+    pub clicked: i64,
+}
+
+impl VariantResult {
+    pub fn rate(&self, metric: Metric) -> f64 {
+        let successes = match metric {
+            Metric::OpenRate => self.opened,
+            Metric::ClickRate => self.clicked,
+        };
+        if self.sent == 0 {
+            0.0
+        } else {
+            successes as f64 / self.sent as f64
+        }
+    }
+}
+
+/// The variant with the best rate; ties go to the variant that was entered first.
+pub fn pick_winner(results: &[VariantResult], metric: Metric) -> Option<&VariantResult> {
+    results.iter().fold(None, |best, candidate| match best {
+        Some(best)
+            if best.rate(metric) > candidate.rate(metric)
+                || (best.rate(metric) == candidate.rate(metric)
+                    && best.position < candidate.position) =>
+        {
+            Some(best)
+        }
+        _ => Some(candidate),
+    })
+}
+
+/// Stores the variants and decides which queued tasks make up the test sample.
+/// Expects every task of the issue to have been enqueued as held.
+#[tracing::instrument(skip(transaction, test))]
+pub async fn start_subject_line_test(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    test: &SubjectLineTest,
+) -> Result<(), sqlx::Error> {
+    let variant_ids: Vec<Uuid> = test.subjects.iter().map(|_| Uuid::new_v4()).collect();
+    let positions: Vec<i32> = (1..=test.subjects.len() as i32).collect();
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO issue_subject_variants (variant_id, newsletter_issue_id, position, subject)
+        SELECT variant_id, $1, position, subject
+        FROM UNNEST($2::uuid[], $3::integer[], $4::text[]) AS v (variant_id, position, subject)
+        "#,
+        newsletter_issue_id,
+        &variant_ids,
+        &positions,
+        &test.subjects,
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subject_line_tests (newsletter_issue_id, sample_percent, metric, decide_at)
+        VALUES ($1, $2, $3, now() + make_interval(hours => $4))
+        "#,
+        newsletter_issue_id,
+        test.sample_percent,
+        test.metric as Metric,
+        test.wait_hours,
+    );
+    transaction.execute(query).await?;
+    // Every variant gets at least one recipient, audience permitting.
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        WITH shuffled AS (
+            SELECT
+                subscriber_id,
+                row_number() OVER (ORDER BY random()) AS n,
+                count(*) OVER () AS total
+            FROM issue_delivery_queue
+            WHERE newsletter_issue_id = $1
+        )
+        UPDATE issue_delivery_queue
+        SET held = FALSE, variant_id = ($2::uuid[])[1 + (shuffled.n - 1) % cardinality($2::uuid[])]
+        FROM shuffled
+        WHERE
+            issue_delivery_queue.newsletter_issue_id = $1 AND
+            issue_delivery_queue.subscriber_id = shuffled.subscriber_id AND
+            shuffled.n <= greatest(ceil(shuffled.total * $3::integer / 100.0), cardinality($2::uuid[]))
+        "#,
+        newsletter_issue_id,
+        &variant_ids,
+        test.sample_percent,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(executor))]
+pub async fn get_variant_results<'c, E>(
+    executor: E,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<VariantResult>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as!(
+        VariantResult,
+        // language=SQL
+        r#"
+        SELECT
+            variant_id,
+            position,
+            subject,
+            (
+                SELECT count(*)
+                FROM issue_deliveries
+                WHERE issue_deliveries.variant_id = issue_subject_variants.variant_id AND succeeded
+            ) AS "sent!",
+            (
+                SELECT count(*)
+                FROM issue_opens
+                JOIN issue_deliveries
+                    ON issue_deliveries.newsletter_issue_id = issue_opens.newsletter_issue_id AND
+                       issue_deliveries.subscriber_id = issue_opens.subscriber_id
+                WHERE issue_deliveries.variant_id = issue_subject_variants.variant_id
+            ) AS "opened!",
+            (
+                SELECT count(DISTINCT issue_clicks.subscriber_id)
+                FROM issue_clicks
+                JOIN issue_deliveries
+                    ON issue_deliveries.newsletter_issue_id = issue_clicks.newsletter_issue_id AND
+                       issue_deliveries.subscriber_id = issue_clicks.subscriber_id
+                WHERE issue_deliveries.variant_id = issue_subject_variants.variant_id
+            ) AS "clicked!"
+        FROM issue_subject_variants
+        WHERE newsletter_issue_id = $1
+        ORDER BY position
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+pub struct SubjectLineTestStatus {
+    pub sample_percent: i32,
+    pub metric: Metric,
+    pub decide_at: DateTime<Utc>,
+    pub winning_variant_id: Option<Uuid>,
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_subject_line_test(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<SubjectLineTestStatus>, sqlx::Error> {
+    sqlx::query_as!(
+        SubjectLineTestStatus,
+        // language=SQL
+        r#"
+        SELECT sample_percent, metric AS "metric: Metric", decide_at, winning_variant_id
+        FROM subject_line_tests
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Picks a winner for every test whose waiting period is over and releases the held tasks
+/// of its issue with the winning subject.
+#[tracing::instrument(skip_all)]
+pub async fn decide_due_subject_line_tests(pool: &PgPool) -> Result<(), anyhow::Error> {
+    loop {
+        let mut transaction = pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?;
+        let Some(test) = sqlx::query!(
+            // language=SQL
+            r#"
+            SELECT newsletter_issue_id, metric AS "metric: Metric"
+            FROM subject_line_tests
+            WHERE winning_variant_id IS NULL AND decide_at <= now()
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .context("Failed to look for subject line tests to decide.")?
+        else {
+            return Ok(());
+        };
+        let results = get_variant_results(&mut *transaction, test.newsletter_issue_id)
+            .await
+            .context("Failed to compute subject line test results.")?;
+        let winner =
+            pick_winner(&results, test.metric).context("A subject line test has no variants.")?;
+        tracing::info!(
+            newsletter_issue_id = %test.newsletter_issue_id,
+            subject = %winner.subject,
+            "Picked the winner of a subject line test."
+        );
+        let query = sqlx::query!(
+            // language=SQL
+            r#"
+            UPDATE subject_line_tests
+            SET winning_variant_id = $2, decided_at = now()
+            WHERE newsletter_issue_id = $1
+            "#,
+            test.newsletter_issue_id,
+            winner.variant_id,
+        );
+        transaction
+            .execute(query)
+            .await
+            .context("Failed to record the winner of a subject line test.")?;
+        let query = sqlx::query!(
+            // language=SQL
+            r#"
+            UPDATE issue_delivery_queue
+            SET held = FALSE, variant_id = $2
+            WHERE newsletter_issue_id = $1 AND held
+            "#,
+            test.newsletter_issue_id,
+            winner.variant_id,
+        );
+        transaction
+            .execute(query)
+            .await
+            .context("Failed to release the tasks held for a subject line test.")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to decide a subject line test.")?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pick_winner, Metric, SubjectLineTest, VariantResult};
+    use claims::{assert_err, assert_none, assert_ok, assert_some};
+    use uuid::Uuid;
+
+    fn result(position: i32, sent: i64, opened: i64, clicked: i64) -> VariantResult {
+        VariantResult {
+            variant_id: Uuid::new_v4(),
+            position,
+            subject: format!("Subject {position}"),
+            sent,
+            opened,
+            clicked,
+        }
+    }
+
+    #[test]
+    fn no_variants_means_no_test() {
+        assert_none!(assert_ok!(SubjectLineTest::parse(" \n", "", "", "")));
+    }
+
+    #[test]
+    fn a_test_needs_at_least_two_variants() {
+        assert_err!(SubjectLineTest::parse("Only one", "20", "4", "open_rate"));
+    }
+
+    #[test]
+    fn a_valid_test_is_parsed() {
+        let test = assert_some!(assert_ok!(SubjectLineTest::parse(
+            "First\r\n\r\n  Second  \n",
+            "20",
+            "4",
+            "click_rate"
+        )));
+
+        assert_eq!(test.subjects, vec!["First", "Second"]);
+        assert_eq!(test.sample_percent, 20);
+        assert_eq!(test.wait_hours, 4);
+        assert_eq!(test.metric, Metric::ClickRate);
+    }
+
+    #[test]
+    fn the_sample_must_be_a_percentage() {
+        for sample_percent in ["0", "101", "", "half"] {
+            assert_err!(SubjectLineTest::parse(
+                "A\nB",
+                sample_percent,
+                "4",
+                "open_rate"
+            ));
+        }
+    }
+
+    #[test]
+    fn the_waiting_period_must_be_reasonable() {
+        for wait_hours in ["-1", "169", ""] {
+            assert_err!(SubjectLineTest::parse(
+                "A\nB",
+                "20",
+                wait_hours,
+                "open_rate"
+            ));
+        }
+    }
+
+    #[test]
+    fn unknown_metrics_are_rejected() {
+        assert_err!(SubjectLineTest::parse("A\nB", "20", "4", "revenue"));
+    }
+
+    #[test]
+    fn the_variant_with_the_best_rate_wins() {
+        let results = [result(1, 10, 2, 5), result(2, 10, 4, 1)];
+
+        assert_eq!(pick_winner(&results, Metric::OpenRate).unwrap().position, 2);
+        assert_eq!(
+            pick_winner(&results, Metric::ClickRate).unwrap().position,
+            1
+        );
+    }
+
+    #[test]
+    fn rates_account_for_how_many_emails_were_sent() {
+        let results = [result(1, 100, 10, 0), result(2, 5, 1, 0)];
+
+        assert_eq!(pick_winner(&results, Metric::OpenRate).unwrap().position, 2);
+    }
+
+    #[test]
+    fn ties_go_to_the_first_variant() {
+        let results = [result(1, 0, 0, 0), result(2, 0, 0, 0), result(3, 0, 0, 0)];
+
+        assert_eq!(pick_winner(&results, Metric::OpenRate).unwrap().position, 1);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::domain::SubscriptionStatus;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+/// Everything we store about a subscriber, as handed over on a data access request.
+#[derive(serde::Serialize)]
+pub struct SubscriberData {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: SubscriptionStatus,
+    pub subscribed_at: DateTime<Utc>,
+    pub plain_text_only: bool,
+    pub lists: Vec<ListMembership>,
+    pub status_history: Vec<StatusChange>,
+    pub deliveries: Vec<Delivery>,
+    pub pending_email_changes: Vec<String>,
+    pub email_events: Vec<EmailEvent>,
+    pub opens: Vec<Open>,
+    pub clicks: Vec<Click>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ListMembership {
+    pub name: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct StatusChange {
+    pub status: SubscriptionStatus,
+    pub changed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Delivery {
+    pub issue_title: String,
+    pub delivered_at: DateTime<Utc>,
+    pub succeeded: bool,
+}
+
+#[derive(serde::Serialize)]
+pub struct EmailEvent {
+    pub event_type: String,
+    pub reason: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Open {
+    pub issue_title: String,
+    pub first_opened_at: DateTime<Utc>,
+    pub last_opened_at: DateTime<Utc>,
+    pub open_count: i32,
+}
+
+#[derive(serde::Serialize)]
+pub struct Click {
+    pub issue_title: String,
+    pub url: String,
+    pub clicked_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Collect subscriber data", skip(pool))]
+pub async fn get_subscriber_data(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<SubscriberData, anyhow::Error> {
+    let subscriber = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT id, email, name, status AS "status: SubscriptionStatus", subscribed_at,
+            plain_text_only
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the subscriber.")?;
+    let lists = sqlx::query_as!(
+        ListMembership,
+        // language=SQL
+        r#"
+        SELECT lists.name, list_subscriptions.subscribed_at
+        FROM list_subscriptions
+        JOIN lists ON lists.list_id = list_subscriptions.list_id
+        WHERE list_subscriptions.subscriber_id = $1
+        ORDER BY list_subscriptions.subscribed_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve list memberships.")?;
+    let status_history = sqlx::query_as!(
+        StatusChange,
+        // language=SQL
+        r#"
+        SELECT status AS "status: SubscriptionStatus", changed_at
+        FROM subscription_status_changes
+        WHERE subscriber_id = $1
+        ORDER BY changed_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscription history.")?;
+    let deliveries = sqlx::query_as!(
+        Delivery,
+        // language=SQL
+        r#"
+        SELECT newsletter_issues.title AS issue_title, issue_deliveries.delivered_at,
+            issue_deliveries.succeeded
+        FROM issue_deliveries
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_deliveries.newsletter_issue_id
+        WHERE issue_deliveries.subscriber_id = $1
+        ORDER BY issue_deliveries.delivered_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve issue deliveries.")?;
+    let pending_email_changes = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT new_email AS "new_email!"
+        FROM subscription_tokens
+        WHERE subscriber_id = $1 AND new_email IS NOT NULL
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve pending email changes.")?
+    .into_iter()
+    .map(|r| r.new_email)
+    .collect();
+    let email_events = sqlx::query_as!(
+        EmailEvent,
+        // language=SQL
+        r#"
+        SELECT event_type, reason, occurred_at
+        FROM email_events
+        WHERE subscriber_id = $1
+        ORDER BY occurred_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve email events.")?;
+    let opens = sqlx::query_as!(
+        Open,
+        // language=SQL
+        r#"
+        SELECT newsletter_issues.title AS issue_title, issue_opens.first_opened_at,
+            issue_opens.last_opened_at, issue_opens.open_count
+        FROM issue_opens
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_opens.newsletter_issue_id
+        WHERE issue_opens.subscriber_id = $1
+        ORDER BY issue_opens.first_opened_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve issue opens.")?;
+    let clicks = sqlx::query_as!(
+        Click,
+        // language=SQL
+        r#"
+        SELECT newsletter_issues.title AS issue_title, issue_clicks.url, issue_clicks.clicked_at
+        FROM issue_clicks
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_clicks.newsletter_issue_id
+        WHERE issue_clicks.subscriber_id = $1
+        ORDER BY issue_clicks.clicked_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve issue clicks.")?;
+
+    Ok(SubscriberData {
+        id: subscriber.id,
+        email: subscriber.email,
+        name: subscriber.name,
+        status: subscriber.status,
+        subscribed_at: subscriber.subscribed_at,
+        plain_text_only: subscriber.plain_text_only,
+        lists,
+        status_history,
+        deliveries,
+        pending_email_changes,
+        email_events,
+        opens,
+        clicks,
+    })
+}
+
+/// Deletes a subscriber together with their tokens, queued deliveries and list memberships.
+/// Past deliveries, opens, clicks, email events and status changes are kept for statistics
+/// but no longer point at anyone.
+/// Returns `false` if there was no such subscriber.
+#[tracing::instrument(name = "Erase subscriber", skip(pool))]
+pub async fn erase_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    requested_by: &str,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"DELETE FROM subscriptions WHERE id = $1"#,
+        subscriber_id,
+    );
+    let n_deleted_rows = transaction
+        .execute(query)
+        .await
+        .context("Failed to delete the subscriber.")?
+        .rows_affected();
+    if n_deleted_rows == 0 {
+        return Ok(false);
+    }
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscriber_erasures (erasure_id, subscriber_id, requested_by, erased_at)
+        VALUES ($1, $2, $3, now())
+        "#,
+        Uuid::new_v4(),
+        subscriber_id,
+        requested_by,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to record the erasure in the audit trail.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase a subscriber.")?;
+    Ok(true)
+}
@@ -0,0 +1,422 @@
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus};
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::lists::list_exists;
+use crate::routes::{error_chain_fmt, generate_subscription_token, send_confirmation_email};
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Subscribers are written in batches of this size, to keep each statement reasonably small.
+const BATCH_SIZE: usize = 1000;
+
+pub enum ImportMode {
+    /// Everyone in the file is imported as confirmed; the attestation records who vouches
+    /// for their consent and how it was collected.
+    Confirmed { attestation: String },
+    /// Everyone in the file is imported as pending and is sent a confirmation email
+    /// by the background worker.
+    SendConfirmationEmails,
+}
+
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub duplicates: usize,
+    pub queued_confirmation_emails: usize,
+    pub errors: Vec<RowError>,
+}
+
+#[derive(thiserror::Error)]
+pub enum ImportError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Row {
+    email: String,
+    name: String,
+}
+
+pub struct ParsedCsv {
+    pub subscribers: Vec<NewSubscriber>,
+    pub duplicates: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Parses a CSV file with `email` and `name` columns. Invalid rows are reported
+/// rather than failing the whole file, and repeated addresses are only kept once.
+pub fn parse_csv(data: &[u8]) -> Result<ParsedCsv, ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(data);
+    let headers = reader
+        .headers()
+        .map_err(|_| ImportError::ValidationError("The file is not a valid CSV file.".into()))?
+        .clone();
+    if !headers.iter().any(|h| h == "email") || !headers.iter().any(|h| h == "name") {
+        return Err(ImportError::ValidationError(
+            "The CSV file must have an email and a name column.".into(),
+        ));
+    }
+
+    let mut parsed = ParsedCsv {
+        subscribers: vec![],
+        duplicates: 0,
+        errors: vec![],
+    };
+    let mut seen = HashSet::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                parsed.errors.push(RowError {
+                    line: e.position().map(|p| p.line()).unwrap_or_default(),
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let subscriber = record
+            .deserialize::<Row>(Some(&headers))
+            .map_err(|e| e.to_string())
+            .and_then(|row| {
+                let email = SubscriberEmail::parse(row.email)?;
+                let name = SubscriberName::parse(row.name)?;
+                Ok(NewSubscriber { email, name })
+            });
+        match subscriber {
+            Ok(subscriber) => {
+                if seen.insert(subscriber.email.as_ref().to_owned()) {
+                    parsed.subscribers.push(subscriber);
+                } else {
+                    parsed.duplicates += 1;
+                }
+            }
+            Err(message) => parsed.errors.push(RowError { line, message }),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Imports the subscribers in `data` into `list_id`. Addresses that are already
+/// subscribed are skipped and left untouched. Subscribers imported as pending
+/// join the list when they confirm.
+#[tracing::instrument(name = "Import subscribers", skip(pool, data, mode))]
+pub async fn import_subscribers(
+    pool: &PgPool,
+    data: &[u8],
+    list_id: Uuid,
+    mode: ImportMode,
+    imported_by: &str,
+) -> Result<ImportReport, ImportError> {
+    let (status, attestation) = match mode {
+        ImportMode::Confirmed { attestation } => {
+            let attestation = attestation.trim().to_owned();
+            if attestation.is_empty() {
+                return Err(ImportError::ValidationError(
+                    "Importing subscribers as confirmed requires an attestation of their consent."
+                        .into(),
+                ));
+            }
+            (SubscriptionStatus::Confirmed, Some(attestation))
+        }
+        ImportMode::SendConfirmationEmails => (SubscriptionStatus::PendingConfirmation, None),
+    };
+    let parsed = parse_csv(data)?;
+    let mut report = ImportReport {
+        duplicates: parsed.duplicates,
+        errors: parsed.errors,
+        ..Default::default()
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    if !list_exists(&mut transaction, list_id).await? {
+        return Err(ImportError::ValidationError(format!(
+            "{list_id} is not a known mailing list."
+        )));
+    }
+    let import_id = Uuid::new_v4();
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscriber_imports (import_id, imported_by, attestation, imported_at)
+        VALUES ($1, $2, $3, now())
+        "#,
+        import_id,
+        imported_by,
+        attestation,
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to record the subscriber import.")?;
+
+    for batch in parsed.subscribers.chunks(BATCH_SIZE) {
+        let inserted = insert_batch(&mut transaction, import_id, list_id, status, batch)
+            .await
+            .context("Failed to insert a batch of imported subscribers.")?;
+        report.imported += inserted.len();
+        report.duplicates += batch.len() - inserted.len();
+        if status == SubscriptionStatus::PendingConfirmation {
+            let ids: Vec<Uuid> = inserted.iter().map(|(id, _)| *id).collect();
+            enqueue_confirmation_emails(&mut transaction, &ids, list_id)
+                .await
+                .context("Failed to queue confirmation emails for imported subscribers.")?;
+            report.queued_confirmation_emails += ids.len();
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to import subscribers.")?;
+
+    Ok(report)
+}
+
+/// Inserts a batch of subscribers, returning the id and email of those that did not exist yet.
+async fn insert_batch(
+    transaction: &mut Transaction<'_, Postgres>,
+    import_id: Uuid,
+    list_id: Uuid,
+    status: SubscriptionStatus,
+    batch: &[NewSubscriber],
+) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+    let ids: Vec<Uuid> = batch.iter().map(|_| Uuid::new_v4()).collect();
+    let emails: Vec<String> = batch.iter().map(|s| s.email.as_ref().to_owned()).collect();
+    let names: Vec<String> = batch.iter().map(|s| s.name.as_ref().to_owned()).collect();
+    let inserted = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, import_id)
+        SELECT id, email, name, now(), $4, $5
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[]) AS batch(id, email, name)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id, email
+        "#,
+        &ids,
+        &emails,
+        &names,
+        status as SubscriptionStatus,
+        import_id,
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    let inserted_ids: Vec<Uuid> = inserted.iter().map(|r| r.id).collect();
+    let preference_tokens: Vec<String> = inserted
+        .iter()
+        .map(|_| generate_subscription_token())
+        .collect();
+
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO preference_tokens (preference_token, subscriber_id)
+        SELECT * FROM UNNEST($1::text[], $2::uuid[])
+        "#,
+        &preference_tokens,
+        &inserted_ids,
+    );
+    transaction.execute(query).await?;
+    // Pending subscribers only join the list once they confirm it.
+    if status == SubscriptionStatus::Confirmed {
+        let query = sqlx::query!(
+            // language=SQL
+            r#"
+            INSERT INTO list_subscriptions (list_id, subscriber_id, subscribed_at)
+            SELECT $1, subscriber_id, now()
+            FROM UNNEST($2::uuid[]) AS batch(subscriber_id)
+            "#,
+            list_id,
+            &inserted_ids,
+        );
+        transaction.execute(query).await?;
+    }
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscription_status_changes (subscriber_id, status, changed_at)
+        SELECT subscriber_id, $2, now()
+        FROM UNNEST($1::uuid[]) AS batch(subscriber_id)
+        "#,
+        &inserted_ids,
+        status as SubscriptionStatus,
+    );
+    transaction.execute(query).await?;
+    Ok(inserted.into_iter().map(|r| (r.id, r.email)).collect())
+}
+
+/// Stores a confirmation token for each subscriber and queues the email carrying it.
+/// Confirming adds the subscriber to `list_id`.
+async fn enqueue_confirmation_emails(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_ids: &[Uuid],
+    list_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let tokens: Vec<String> = subscriber_ids
+        .iter()
+        .map(|_| generate_subscription_token())
+        .collect();
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        SELECT token, subscriber_id, $3
+        FROM UNNEST($1::text[], $2::uuid[]) AS batch(token, subscriber_id)
+        "#,
+        &tokens,
+        subscriber_ids,
+        list_id,
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO confirmation_email_queue (subscription_token, enqueued_at)
+        SELECT token, now() FROM UNNEST($1::text[]) AS batch(token)
+        "#,
+        &tokens,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// Sends one queued confirmation email. Like issue deliveries, a failed attempt is
+/// logged and not retried.
+#[tracing::instrument(skip_all, err)]
+pub async fn try_send_confirmation_email(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT
+            confirmation_email_queue.subscription_token,
+            email,
+            name,
+            status AS "status: SubscriptionStatus"
+        FROM confirmation_email_queue
+        JOIN subscription_tokens
+            ON subscription_tokens.subscription_token = confirmation_email_queue.subscription_token
+        JOIN subscriptions ON subscriptions.id = subscription_tokens.subscriber_id
+        FOR UPDATE OF confirmation_email_queue
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let Some(task) = task else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    if task.status != SubscriptionStatus::PendingConfirmation {
+        tracing::info!("Skipping a subscriber who is no longer pending confirmation.");
+    } else {
+        match (
+            SubscriberEmail::parse(task.email),
+            SubscriberName::parse(task.name),
+        ) {
+            (Ok(email), Ok(name)) => {
+                let subscriber = NewSubscriber { email, name };
+                if let Err(e) = send_confirmation_email(
+                    email_client,
+                    subscriber,
+                    base_url,
+                    &task.subscription_token,
+                )
+                .await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send a confirmation email to an imported subscriber. Skipping.",
+                    );
+                }
+            }
+            _ => tracing::error!(
+                "Skipping an imported subscriber. Their stored contact details are invalid."
+            ),
+        }
+    }
+    let query = sqlx::query!(
+        // language=SQL
+        r#"DELETE FROM confirmation_email_queue WHERE subscription_token = $1"#,
+        task.subscription_token,
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_csv, ImportError};
+
+    #[test]
+    fn valid_rows_are_parsed_regardless_of_column_order() {
+        let parsed = parse_csv(b"name,email\nUrsula,ursula@example.com\n").unwrap();
+
+        assert_eq!(parsed.subscribers.len(), 1);
+        assert_eq!(parsed.subscribers[0].email.as_ref(), "ursula@example.com");
+        assert_eq!(parsed.subscribers[0].name.as_ref(), "Ursula");
+        assert!(parsed.errors.is_empty());
+    }
+
+    #[test]
+    fn invalid_rows_are_reported_with_their_line_number() {
+        let data = b"email,name\n\
+            ursula@example.com,Ursula\n\
+            definitely-not-an-email,Le Guin\n\
+            le.guin@example.com,\n";
+
+        let parsed = parse_csv(data).unwrap();
+
+        assert_eq!(parsed.subscribers.len(), 1);
+        let lines: Vec<u64> = parsed.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![3, 4]);
+        assert!(parsed.errors[0].message.contains("definitely-not-an-email"));
+    }
+
+    #[test]
+    fn repeated_addresses_are_only_kept_once() {
+        let data = b"email,name\n\
+            ursula@example.com,Ursula\n\
+            ursula@example.com,Ursula K.\n";
+
+        let parsed = parse_csv(data).unwrap();
+
+        assert_eq!(parsed.subscribers.len(), 1);
+        assert_eq!(parsed.duplicates, 1);
+    }
+
+    #[test]
+    fn a_file_without_the_expected_columns_is_rejected() {
+        assert!(matches!(
+            parse_csv(b"address,full_name\nursula@example.com,Ursula\n"),
+            Err(ImportError::ValidationError(_))
+        ));
+    }
+}
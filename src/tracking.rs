@@ -0,0 +1,253 @@
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Truncated tags keep tracking URLs short while still being infeasible to forge.
+const TAG_LENGTH: usize = 16;
+
+/// Signs the tokens we embed in outgoing emails, so recipients cannot forge events for
+/// other subscribers or issues.
+#[derive(Clone)]
+pub struct TrackingKey(SecretString);
+
+impl TrackingKey {
+    pub fn new(secret: SecretString) -> Self {
+        Self(secret)
+    }
+
+    fn mac(&self, purpose: &[u8], payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.0.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(purpose);
+        mac.update(payload);
+        mac
+    }
+
+    fn sign(&self, purpose: &[u8], payload: &[u8]) -> String {
+        let tag = self.mac(purpose, payload).finalize().into_bytes();
+        BASE64_URL_SAFE_NO_PAD.encode([payload, &tag[..TAG_LENGTH]].concat())
+    }
+
+    /// Returns the signed payload if `token` was produced by [`TrackingKey::sign`].
+    fn verify(&self, purpose: &[u8], token: &str) -> Option<Vec<u8>> {
+        let mut bytes = BASE64_URL_SAFE_NO_PAD.decode(token).ok()?;
+        let tag_start = bytes.len().checked_sub(TAG_LENGTH)?;
+        let tag = bytes.split_off(tag_start);
+        self.mac(purpose, &bytes)
+            .verify_truncated_left(&tag)
+            .ok()
+            .map(|()| bytes)
+    }
+}
+
+/// Identifies the delivery an open tracking pixel was embedded in.
+#[derive(Debug, PartialEq)]
+pub struct OpenToken {
+    pub newsletter_issue_id: Uuid,
+    pub subscriber_id: Uuid,
+}
+
+impl OpenToken {
+    const PURPOSE: &'static [u8] = b"open";
+
+    pub fn encode(&self, key: &TrackingKey) -> String {
+        let payload = [
+            self.newsletter_issue_id.as_bytes().as_slice(),
+            self.subscriber_id.as_bytes(),
+        ]
+        .concat();
+        key.sign(Self::PURPOSE, &payload)
+    }
+
+    pub fn decode(token: &str, key: &TrackingKey) -> Option<Self> {
+        let payload = key.verify(Self::PURPOSE, token)?;
+        if payload.len() != 32 {
+            return None;
+        }
+        Some(Self {
+            newsletter_issue_id: Uuid::from_slice(&payload[..16]).ok()?,
+            subscriber_id: Uuid::from_slice(&payload[16..]).ok()?,
+        })
+    }
+}
+
+/// Identifies a link in a delivered issue. The target URL is part of the signed payload,
+/// so the redirector only ever sends people to URLs we put in an email ourselves.
+#[derive(Debug, PartialEq)]
+pub struct ClickToken {
+    pub newsletter_issue_id: Uuid,
+    pub subscriber_id: Uuid,
+    pub url: String,
+}
+
+impl ClickToken {
+    const PURPOSE: &'static [u8] = b"click";
+
+    pub fn encode(&self, key: &TrackingKey) -> String {
+        let payload = [
+            self.newsletter_issue_id.as_bytes().as_slice(),
+            self.subscriber_id.as_bytes(),
+            self.url.as_bytes(),
+        ]
+        .concat();
+        key.sign(Self::PURPOSE, &payload)
+    }
+
+    pub fn decode(token: &str, key: &TrackingKey) -> Option<Self> {
+        let payload = key.verify(Self::PURPOSE, token)?;
+        if payload.len() < 32 {
+            return None;
+        }
+        Some(Self {
+            newsletter_issue_id: Uuid::from_slice(&payload[..16]).ok()?,
+            subscriber_id: Uuid::from_slice(&payload[16..32]).ok()?,
+            url: String::from_utf8(payload[32..].to_vec()).ok()?,
+        })
+    }
+}
+
+/// Replaces the target of every `href` pointing to an http(s) URL with `rewrite(url)`.
+/// Other links (`mailto:`, anchors, ...) are left alone.
+pub fn rewrite_links(html: &str, mut rewrite: impl FnMut(&str) -> String) -> String {
+    // ASCII lowercasing keeps byte offsets intact.
+    let lowercase = html.to_ascii_lowercase();
+    let mut output = String::with_capacity(html.len());
+    let mut position = 0;
+    while let Some(offset) = lowercase[position..].find("href=") {
+        let attribute_start = position + offset;
+        let value_start = attribute_start + "href=".len();
+        let quote = html[value_start..]
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'');
+        let is_attribute = html[..attribute_start].ends_with(char::is_whitespace);
+        let (Some(quote), true) = (quote, is_attribute) else {
+            output.push_str(&html[position..value_start]);
+            position = value_start;
+            continue;
+        };
+        let Some(length) = html[value_start + 1..].find(quote) else {
+            break;
+        };
+        let value = &html[value_start + 1..value_start + 1 + length];
+        output.push_str(&html[position..value_start + 1]);
+        let url = html_escape::decode_html_entities(value);
+        let scheme = url
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if scheme == "http" || scheme == "https" {
+            output.push_str(&html_escape::encode_quoted_attribute(&rewrite(&url)));
+        } else {
+            output.push_str(value);
+        }
+        position = value_start + 1 + length;
+    }
+    output.push_str(&html[position..]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rewrite_links, ClickToken, OpenToken, TrackingKey};
+    use uuid::Uuid;
+
+    fn key(secret: &str) -> TrackingKey {
+        TrackingKey::new(secret.to_string().into())
+    }
+
+    fn token() -> OpenToken {
+        OpenToken {
+            newsletter_issue_id: Uuid::new_v4(),
+            subscriber_id: Uuid::new_v4(),
+        }
+    }
+
+    #[test]
+    fn an_open_token_round_trips() {
+        let token = token();
+
+        let encoded = token.encode(&key("secret"));
+
+        assert_eq!(OpenToken::decode(&encoded, &key("secret")), Some(token));
+    }
+
+    #[test]
+    fn an_open_token_signed_with_another_key_is_rejected() {
+        let encoded = token().encode(&key("another secret"));
+
+        assert_eq!(OpenToken::decode(&encoded, &key("secret")), None);
+    }
+
+    #[test]
+    fn a_tampered_open_token_is_rejected() {
+        let mut encoded = token().encode(&key("secret")).into_bytes();
+        encoded[0] = if encoded[0] == b'A' { b'B' } else { b'A' };
+
+        let encoded = String::from_utf8(encoded).unwrap();
+        assert_eq!(OpenToken::decode(&encoded, &key("secret")), None);
+    }
+
+    #[test]
+    fn malformed_open_tokens_are_rejected() {
+        assert_eq!(OpenToken::decode("", &key("secret")), None);
+        assert_eq!(OpenToken::decode("not base64!", &key("secret")), None);
+        assert_eq!(OpenToken::decode("AAAA", &key("secret")), None);
+    }
+
+    #[test]
+    fn a_click_token_round_trips() {
+        let token = ClickToken {
+            newsletter_issue_id: Uuid::new_v4(),
+            subscriber_id: Uuid::new_v4(),
+            url: "https://example.com/post?id=1&ref=newsletter".into(),
+        };
+
+        let encoded = token.encode(&key("secret"));
+
+        assert_eq!(ClickToken::decode(&encoded, &key("secret")), Some(token));
+    }
+
+    #[test]
+    fn an_open_token_is_not_a_valid_click_token() {
+        let encoded = token().encode(&key("secret"));
+
+        assert_eq!(ClickToken::decode(&encoded, &key("secret")), None);
+    }
+
+    #[test]
+    fn http_links_are_rewritten() {
+        let html = r#"<p><a href="https://example.com/a?x=1&amp;y=2">A</a> <A HREF='http://example.com/b'>B</A></p>"#;
+
+        let rewritten = rewrite_links(html, |url| format!("https://tracker/{}", url.len()));
+
+        assert_eq!(
+            rewritten,
+            r#"<p><a href="https://tracker/29">A</a> <A HREF='https://tracker/20'>B</A></p>"#
+        );
+    }
+
+    #[test]
+    fn other_links_are_left_alone() {
+        let html = r##"<a href="mailto:me@example.com">Mail</a><a href="#top">Top</a><a data-href="https://example.com">X</a><a href=https://example.com>Y</a>"##;
+
+        let rewritten = rewrite_links(html, |_| "https://tracker".into());
+
+        assert_eq!(rewritten, html);
+    }
+
+    #[test]
+    fn an_unterminated_attribute_does_not_panic() {
+        let html = r#"<a href="https://example.com">ok</a><a href="https://exa"#;
+
+        let rewritten = rewrite_links(html, |_| "https://tracker".into());
+
+        assert_eq!(
+            rewritten,
+            r#"<a href="https://tracker">ok</a><a href="https://exa"#
+        );
+    }
+}
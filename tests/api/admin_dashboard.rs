@@ -1,4 +1,4 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
@@ -32,3 +32,26 @@ async fn logout_clears_session_state() {
     let response = app.get_admin_dashboard().await;
     assert_is_redirect_to(&response, "/login");
 }
+
+#[tokio::test]
+async fn the_dashboard_shows_list_growth() {
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions LIMIT 1")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.test_user.login(&app).await;
+    app.post_subscriber_action(subscriber_id, "unsubscribe")
+        .await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    let today = chrono::Utc::now().format("%Y-%m-%d");
+    assert!(html_page.contains(&format!(
+        "<tr><td>{today}</td><td>3</td><td>1</td><td>+2</td></tr>"
+    )));
+}
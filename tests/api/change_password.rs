@@ -115,3 +115,63 @@ async fn changing_password_works() {
     let response = app.post_login(&login_body).await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn new_passwords_must_follow_the_password_policy() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let test_cases = [
+        (
+            "short".to_string(),
+            "The new password must be at least 12 characters long.",
+        ),
+        (
+            "a".repeat(129),
+            "The new password must be at most 128 characters long.",
+        ),
+        (
+            "Password1234".to_string(),
+            "The new password is too common - please choose one that is harder to guess.",
+        ),
+        (
+            format!("my-{}-password", app.test_user.username),
+            "The new password must not contain your username.",
+        ),
+    ];
+
+    for (new_password, message) in test_cases {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "current_password": &app.test_user.password,
+                "new_password": &new_password,
+                "new_password_check": &new_password,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/password");
+
+        let html_page = app.get_change_password_html().await;
+        assert!(
+            html_page.contains(&format!("<p><i>{message}</i></p>")),
+            "Expected {message:?}"
+        );
+    }
+    // The old password still works.
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn the_password_form_explains_the_policy() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_change_password_html().await;
+
+    assert!(html_page.contains("New passwords must be 12 to 128 characters long"));
+}
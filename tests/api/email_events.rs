@@ -0,0 +1,247 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    TestApp,
+};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::SubscriptionStatus;
+use zero2prod::subscriber_data::erase_subscriber;
+
+async fn subscriber(app: &TestApp) -> (String, SubscriptionStatus) {
+    let row =
+        sqlx::query!(r#"SELECT email, status AS "status: SubscriptionStatus" FROM subscriptions"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("Failed to fetch saved subscription.");
+    (row.email, row.status)
+}
+
+fn event(email: &str, event: &str, bounce_type: Option<&str>) -> serde_json::Value {
+    let mut event = serde_json::json!({
+        "email": email,
+        "timestamp": 1_700_000_000,
+        "event": event,
+        "sg_event_id": uuid::Uuid::new_v4().to_string(),
+        "sg_message_id": "message-id",
+        "reason": "550 5.1.1 The email account that you tried to reach does not exist.",
+    });
+    if let Some(bounce_type) = bounce_type {
+        event["type"] = bounce_type.into();
+    }
+    event
+}
+
+#[tokio::test]
+async fn a_hard_bounce_marks_the_subscriber_as_bounced() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+
+    let response = app
+        .post_email_events(&serde_json::json!([event(
+            &email,
+            "bounce",
+            Some("bounce")
+        )]))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(subscriber(&app).await.1, SubscriptionStatus::Bounced);
+    let recorded = sqlx::query!("SELECT event_type, reason FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The event was not recorded.");
+    assert_eq!(recorded.event_type, "bounce");
+    assert!(recorded.reason.unwrap().starts_with("550"));
+}
+
+#[tokio::test]
+async fn events_outlive_an_erased_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+    app.post_email_events(&serde_json::json!([event(
+        &email,
+        "bounce",
+        Some("bounce")
+    )]))
+    .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+
+    assert!(erase_subscriber(&app.db_pool, subscriber_id, "admin")
+        .await
+        .unwrap());
+
+    let recorded = sqlx::query!("SELECT event_type, subscriber_id FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The event was erased with the subscriber.");
+    assert_eq!(recorded.event_type, "bounce");
+    assert_eq!(recorded.subscriber_id, None);
+    let bounces = sqlx::query!(
+        r#"SELECT count(*) AS "count!" FROM subscription_status_changes
+        WHERE status = 'bounced' AND subscriber_id IS NULL"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(bounces.count, 1);
+}
+
+#[tokio::test]
+async fn a_spam_report_marks_the_subscriber_as_complained() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+
+    let response = app
+        .post_email_events(&serde_json::json!([event(&email, "spamreport", None)]))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(subscriber(&app).await.1, SubscriptionStatus::Complained);
+}
+
+#[tokio::test]
+async fn other_events_are_recorded_without_changing_the_status() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+
+    let response = app
+        .post_email_events(&serde_json::json!([
+            event(&email, "delivered", None),
+            event(&email, "bounce", Some("blocked")),
+        ]))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(subscriber(&app).await.1, SubscriptionStatus::Confirmed);
+    let n_events = sqlx::query!(r#"SELECT count(*) AS "n!" FROM email_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_events, 2);
+}
+
+#[tokio::test]
+async fn a_bounce_for_a_pending_subscriber_is_recorded_but_not_applied() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+
+    let response = app
+        .post_email_events(&serde_json::json!([event(
+            &email,
+            "bounce",
+            Some("bounce")
+        )]))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        subscriber(&app).await.1,
+        SubscriptionStatus::PendingConfirmation
+    );
+}
+
+#[tokio::test]
+async fn redelivered_events_are_only_recorded_once() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+    let events = serde_json::json!([event(&email, "spamreport", None)]);
+
+    app.post_email_events(&events).await;
+    let response = app.post_email_events(&events).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let n_events = sqlx::query!(r#"SELECT count(*) AS "n!" FROM email_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_events, 1);
+}
+
+#[tokio::test]
+async fn events_with_an_invalid_signature_are_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+    let body = serde_json::to_vec(&serde_json::json!([event(&email, "spamreport", None)])).unwrap();
+
+    let response = app
+        .post_email_events_with_signature(body, "1700000000", "MEUCIQ==")
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(subscriber(&app).await.1, SubscriptionStatus::Confirmed);
+}
+
+#[tokio::test]
+async fn events_without_a_signature_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/webhooks/email-events", &app.address))
+        .json(&serde_json::json!([]))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn a_malformed_payload_is_rejected_with_a_400() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_email_events(&serde_json::json!({"not": "a list"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_bounced_subscribers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber(&app).await;
+    app.post_email_events(&serde_json::json!([event(
+        &email,
+        "bounce",
+        Some("bounce")
+    )]))
+    .await;
+    app.test_user.login(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let n_queued = sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_queued, 0);
+    app.dispatch_all_pending_emails().await;
+}
@@ -1,15 +1,27 @@
+use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
-use zero2prod::issue_delivery_worker:: {try_execute_task, ExecutionOutcome};
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
-use argon2::password_hash::rand_core::OsRng;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use fake::faker::internet::en::SafeEmail;
+use fake::faker::name::en::Name;
+use fake::Fake;
 use once_cell::sync::Lazy;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::pkcs8::EncodePublicKey;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockBuilder, MockServer, ResponseTemplate};
+use zero2prod::authentication::Role;
 use zero2prod::configuration::{get_configuration, DatabaseSettings};
 use zero2prod::email_client::EmailClient;
+use zero2prod::email_events::{SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
 use zero2prod::startup::{get_connection_pool, Application};
+use zero2prod::subscriber_import::try_send_confirmation_email;
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
+use zero2prod::tracking::TrackingKey;
 
 static TRACING: Lazy<()> = Lazy::new(|| {
     let default_filter_level = "debug".to_string();
@@ -36,12 +48,14 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub event_webhook_signing_key: SigningKey,
+    pub tracking_key: TrackingKey,
 }
 
 impl TestApp {
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -53,7 +67,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -65,7 +79,7 @@ impl TestApp {
 
     pub async fn get_publish_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -75,12 +89,431 @@ impl TestApp {
         self.get_publish_newsletter().await.text().await.unwrap()
     }
 
+    pub async fn get_issue_analytics(&self, newsletter_issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{newsletter_issue_id}",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_issue_analytics_html(&self, newsletter_issue_id: Uuid) -> String {
+        self.get_issue_analytics(newsletter_issue_id)
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_issue_action(
+        &self,
+        newsletter_issue_id: Uuid,
+        action: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{newsletter_issue_id}/{action}",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_publish_newsletter_preview_html(&self, query: &str) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters?preview=true&{query}",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_segments_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/segments", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_segment<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/segments", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn create_segment(&self, body: serde_json::Value) -> Uuid {
+        self.post_create_segment(&body).await;
+        sqlx::query!(
+            // language=SQL
+            "SELECT segment_id FROM segments WHERE name = $1",
+            body["name"].as_str().unwrap()
+        )
+        .fetch_one(&self.db_pool)
+        .await
+        .expect("Failed to fetch the created segment.")
+        .segment_id
+    }
+
+    pub async fn get_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users_html(&self) -> String {
+        self.get_users().await.text().await.unwrap()
+    }
+
+    pub async fn post_change_user_role(&self, user_id: Uuid, role: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/users/{user_id}/role", &self.address))
+            .form(&serde_json::json!({ "role": role }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_remove_user(&self, user_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/users/{user_id}/delete", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_invite_user<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/users/invitations", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_accept_invitation<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/invitations", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_lists(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/lists", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_lists_html(&self) -> String {
+        self.get_lists().await.text().await.unwrap()
+    }
+
+    pub async fn post_create_list<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/lists", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn create_list(&self, name: &str) -> Uuid {
+        self.post_create_list(&serde_json::json!({ "name": name }))
+            .await;
+        sqlx::query!(
+            // language=SQL
+            "SELECT list_id FROM lists WHERE name = $1",
+            name
+        )
+        .fetch_one(&self.db_pool)
+        .await
+        .expect("Failed to fetch the created list.")
+        .list_id
+    }
+
+    pub async fn get_subscribers(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers?{query}", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers_html(&self, query: &str) -> String {
+        self.get_subscribers(query).await.text().await.unwrap()
+    }
+
+    pub async fn get_subscribers_export(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/export?{query}",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_details(&self, subscriber_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{subscriber_id}",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_details_html(&self, subscriber_id: Uuid) -> String {
+        self.get_subscriber_details(subscriber_id)
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_subscriber_action(
+        &self,
+        subscriber_id: Uuid,
+        action: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{subscriber_id}/{action}",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_import_subscribers_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/subscribers/import", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Uploads `csv` as a multipart form, alongside the other `fields`.
+    pub async fn post_import_subscribers(
+        &self,
+        csv: &str,
+        fields: &[(&str, &str)],
+    ) -> reqwest::Response {
+        let boundary = "----zero2prod-test-boundary";
+        let mut body = format!(
+            "--{boundary}\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"subscribers.csv\"\r\n\
+            Content-Type: text/csv\r\n\r\n\
+            {csv}\r\n"
+        );
+        for (name, value) in fields {
+            body.push_str(&format!(
+                "--{boundary}\r\n\
+                Content-Disposition: form-data; name=\"{name}\"\r\n\r\n\
+                {value}\r\n"
+            ));
+        }
+        body.push_str(&format!("--{boundary}--\r\n"));
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribe_form_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/subscriptions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_preferences(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/preferences", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_preferences_html(&self, token: &str) -> String {
+        self.get_preferences(token).await.text().await.unwrap()
+    }
+
+    pub async fn post_preferences(&self, body: String) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/preferences", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_data(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/preferences/data?token={token}", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_erase_data(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/preferences/erase", &self.address))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_unsubscribe<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/preferences/unsubscribe", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/preferences/email", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Posts `events` to the email event webhook, signed the way SendGrid does it.
+    pub async fn post_email_events(&self, events: &serde_json::Value) -> reqwest::Response {
+        let body = serde_json::to_vec(events).unwrap();
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let signature: Signature = self
+            .event_webhook_signing_key
+            .sign(&[timestamp.as_bytes(), &body].concat());
+        self.post_email_events_with_signature(
+            body,
+            &timestamp,
+            &BASE64_STANDARD.encode(signature.to_der().as_bytes()),
+        )
+        .await
+    }
+
+    pub async fn post_email_events_with_signature(
+        &self,
+        body: Vec<u8>,
+        timestamp: &str,
+        signature: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/webhooks/email-events", &self.address))
+            .header("Content-Type", "application/json")
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_account_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/password/email", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_request_password_reset<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset/confirm", &self.address))
             .form(body)
             .send()
             .await
@@ -89,7 +522,7 @@ impl TestApp {
 
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -103,16 +536,128 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_login_two_factor(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/login/two-factor", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_revoke_session(&self, session_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/sessions/{session_id}/revoke",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_other_sessions(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/sessions/revoke-others", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Logs `user` in with a client of its own, as if from another device.
+    pub async fn log_in_on_another_device(
+        &self,
+        user: &TestUser,
+        user_agent: &str,
+    ) -> reqwest::Client {
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .cookie_store(true)
+            .user_agent(user_agent)
+            .build()
+            .unwrap();
+        let response = client
+            .post(format!("{}/login", &self.address))
+            .form(&serde_json::json!({
+                "username": &user.username,
+                "password": &user.password
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_is_redirect_to(&response, "/admin/dashboard");
+        client
+    }
+
+    pub async fn get_two_factor(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_two_factor_html(&self) -> String {
+        self.get_two_factor().await.text().await.unwrap()
+    }
+
+    pub async fn post_enrol_two_factor(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/two-factor", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_disable_two_factor(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/two-factor/disable", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_clear_login_lock(&self, kind: &str, key: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/users/locks/clear", &self.address))
+            .form(&serde_json::json!({ "kind": kind, "key": key }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_require_two_factor(&self, required: bool) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/users/two-factor", &self.address))
+            .form(&serde_json::json!({ "required": required }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -120,7 +665,7 @@ impl TestApp {
 
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -133,7 +678,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/admin/newsletters", &self.address))
             .form(&body)
             .send()
             .await
@@ -175,8 +720,24 @@ impl TestApp {
 
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome:: EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.address,
+                &self.tracking_key,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    pub async fn send_all_queued_confirmation_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_send_confirmation_email(&self.db_pool, &self.email_client, &self.address)
                     .await
                     .unwrap()
             {
@@ -186,16 +747,80 @@ impl TestApp {
     }
 }
 
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let email: String = SafeEmail().fake();
+    create_unconfirmed_list_subscriber(app, &email, None).await
+}
+
+pub async fn create_unconfirmed_list_subscriber(
+    app: &TestApp,
+    email: &str,
+    list_id: Option<Uuid>,
+) -> ConfirmationLinks {
+    let name: String = Name().fake();
+    let mut body = serde_json::json!({
+        "name": name,
+        "email": email,
+    });
+    if let Some(list_id) = list_id {
+        body["list_id"] = list_id.to_string().into();
+    }
+    let body = serde_urlencoded::to_string(body).unwrap();
+
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+
+    app.get_confirmation_links(email_request)
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let confirmation_link = create_unconfirmed_subscriber(app).await;
+
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+pub fn when_sending_an_email() -> MockBuilder {
+    Mock::given(path("/v3/mail/send")).and(method("POST"))
+}
+
 pub async fn spawn_app() -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
 
+    let event_webhook_signing_key = SigningKey::random(&mut OsRng);
     let configuration = {
         let mut c = get_configuration().expect("Failed to read configuration");
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        let public_key = event_webhook_signing_key
+            .verifying_key()
+            .to_public_key_der()
+            .unwrap();
+        c.email_client.event_webhook_verification_key =
+            BASE64_STANDARD.encode(public_key.as_bytes());
         c
     };
 
@@ -206,7 +831,7 @@ pub async fn spawn_app() -> TestApp {
         .expect("Failed to build application.");
     let port = application.port();
     let address = format!("http://127.0.0.1:{port}");
-    let _ = tokio::spawn(application.run_until_stopped());
+    tokio::spawn(application.run_until_stopped());
     let client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
         .cookie_store(true)
@@ -221,12 +846,30 @@ pub async fn spawn_app() -> TestApp {
         test_user: TestUser::generate(),
         api_client: client,
         email_client: configuration.email_client.client(),
+        event_webhook_signing_key,
+        tracking_key: TrackingKey::new(configuration.application.hmac_secret.clone()),
     };
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
 }
 
 async fn configure_database(config: &DatabaseSettings) -> PgPool {
+    let connection_pool = create_database(config).await;
+    sqlx::migrate!("./migrations")
+        .run(&connection_pool)
+        .await
+        .expect("Failed to migrate the database");
+
+    connection_pool
+}
+
+pub async fn spawn_empty_database() -> PgPool {
+    let mut configuration = get_configuration().expect("Failed to read configuration");
+    configuration.database.database_name = Uuid::new_v4().to_string();
+    create_database(&configuration.database).await
+}
+
+async fn create_database(config: &DatabaseSettings) -> PgPool {
     let mut connection = PgConnection::connect_with(&config.without_db())
         .await
         .expect("Failed to connect to postgres");
@@ -235,29 +878,29 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
         .await
         .expect("Failed to create database.");
 
-    let connection_pool = PgPool::connect_with(config.with_db())
-        .await
-        .expect("Failed to connect to postgres.");
-    sqlx::migrate!("./migrations")
-        .run(&connection_pool)
+    PgPool::connect_with(config.with_db())
         .await
-        .expect("Failed to migrate the database");
-
-    connection_pool
+        .expect("Failed to connect to postgres.")
 }
 
 pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub role: Role,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::with_role(Role::Owner)
+    }
+
+    pub fn with_role(role: Role) -> Self {
         Self {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            role,
         }
     }
 
@@ -269,8 +912,8 @@ impl TestUser {
         .await;
     }
 
-    async fn store(&self, pool: &PgPool) {
-        let salt = SaltString::generate(&mut OsRng::default());
+    pub async fn store(&self, pool: &PgPool) {
+        let salt = SaltString::generate(&mut OsRng);
         let password_hash = Argon2::new(
             Algorithm::Argon2id,
             Version::V0x13,
@@ -281,11 +924,12 @@ impl TestUser {
         .to_string();
         sqlx::query!(
             // language=SQL
-            "INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, role)
+            VALUES ($1, $2, $3, $4)",
             self.user_id,
             self.username,
             password_hash,
+            self.role as Role,
         )
         .execute(pool)
         .await
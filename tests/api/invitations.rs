@@ -0,0 +1,245 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, when_sending_an_email, TestApp, TestUser};
+use wiremock::ResponseTemplate;
+use zero2prod::authentication::Role;
+
+/// Sends an invitation as the logged-in test user and returns the invitation token.
+async fn invite(app: &TestApp, email: &str, role: &str) -> String {
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .post_invite_user(&serde_json::json!({ "email": email, "role": role }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let link = app.get_confirmation_links(&email_request).html;
+    assert_eq!(link.path(), "/invitations");
+    link.query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+fn accept_body(token: &str, username: &str) -> serde_json::Value {
+    serde_json::json!({
+        "token": token,
+        "username": username,
+        "password": "a-new-password",
+        "password_check": "a-new-password",
+    })
+}
+
+async fn get_invitation(app: &TestApp, token: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/invitations", app.address))
+        .query(&[("token", token)])
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn an_invitation_is_emailed_and_listed_as_pending() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let token = invite(&app, "new-admin@example.com", "viewer").await;
+
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>The invitation has been sent.</i></p>"));
+    assert!(html_page.contains("<tr><td>new-admin@example.com</td><td>viewer</td>"));
+    let stored = sqlx::query!("SELECT token_hash FROM admin_invitations")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.token_hash, token);
+}
+
+#[tokio::test]
+async fn an_invitee_can_create_an_account_and_log_in() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "new-admin@example.com", "editor").await;
+    app.post_logout().await;
+
+    let html_page = get_invitation(&app, &token).await.text().await.unwrap();
+    assert!(html_page.contains("new-admin@example.com has been invited to join as editor."));
+    let response = app
+        .post_accept_invitation(&accept_body(&token, "new-admin"))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(app
+        .get_login_html()
+        .await
+        .contains("<p><i>Your account has been created - you can now log in.</i></p>"));
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "new-admin",
+            "password": "a-new-password",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let user = sqlx::query!(
+        r#"SELECT role AS "role: Role", password_hash FROM users WHERE username = 'new-admin'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(user.role, Role::Editor);
+    assert!(user.password_hash.starts_with("$argon2id$"));
+}
+
+#[tokio::test]
+async fn an_invitation_can_only_be_used_once() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "new-admin@example.com", "editor").await;
+    app.post_accept_invitation(&accept_body(&token, "new-admin"))
+        .await;
+
+    assert_eq!(get_invitation(&app, &token).await.status().as_u16(), 401);
+    let response = app
+        .post_accept_invitation(&accept_body(&token, "another-admin"))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("No pending invitations."));
+}
+
+#[tokio::test]
+async fn expired_invitations_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "new-admin@example.com", "editor").await;
+    sqlx::query!("UPDATE admin_invitations SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert_eq!(get_invitation(&app, &token).await.status().as_u16(), 401);
+    let response = app
+        .post_accept_invitation(&accept_body(&token, "new-admin"))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn unknown_tokens_are_rejected() {
+    let app = spawn_app().await;
+
+    assert_eq!(
+        get_invitation(&app, "not-a-token").await.status().as_u16(),
+        401
+    );
+}
+
+#[tokio::test]
+async fn passwords_must_match() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "new-admin@example.com", "editor").await;
+    let mut body = accept_body(&token, "new-admin");
+    body["password_check"] = "something-else".into();
+
+    let response = app.post_accept_invitation(&body).await;
+
+    assert_is_redirect_to(&response, &format!("/invitations?token={token}"));
+    let html_page = get_invitation(&app, &token).await.text().await.unwrap();
+    assert!(html_page.contains("You entered two different passwords"));
+}
+
+#[tokio::test]
+async fn usernames_must_be_available_and_valid() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "new-admin@example.com", "editor").await;
+    let test_cases = [
+        (
+            app.test_user.username.clone(),
+            "That username or email address is already in use.",
+        ),
+        ("".to_string(), "Please choose a username"),
+        ("new admin".to_string(), "Usernames cannot contain spaces."),
+    ];
+
+    for (username, message) in test_cases {
+        let response = app
+            .post_accept_invitation(&accept_body(&token, &username))
+            .await;
+
+        assert_is_redirect_to(&response, &format!("/invitations?token={token}"));
+        let html_page = get_invitation(&app, &token).await.text().await.unwrap();
+        assert!(html_page.contains(message), "Expected {message:?}");
+    }
+    let n_users = sqlx::query!(r#"SELECT count(*) AS "n!" FROM users"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    // The seeded admin and the test user.
+    assert_eq!(n_users, 2);
+}
+
+#[tokio::test]
+async fn invalid_email_addresses_are_not_invited() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_invite_user(&serde_json::json!({ "email": "not-an-email", "role": "editor" }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>Please enter a valid email address.</i></p>"));
+}
+
+#[tokio::test]
+async fn only_owners_can_invite() {
+    let app = spawn_app().await;
+    let editor = TestUser::with_role(Role::Editor);
+    editor.store(&app.db_pool).await;
+    editor.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_invite_user(&serde_json::json!({ "email": "friend@example.com", "role": "owner" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn invitees_passwords_must_follow_the_password_policy() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "new-admin@example.com", "editor").await;
+    let mut body = accept_body(&token, "new-admin");
+    body["password"] = "new-admin-rocks".into();
+    body["password_check"] = "new-admin-rocks".into();
+
+    let response = app.post_accept_invitation(&body).await;
+
+    assert_is_redirect_to(&response, &format!("/invitations?token={token}"));
+    let html_page = get_invitation(&app, &token).await.text().await.unwrap();
+    assert!(html_page.contains("The new password must not contain your username."));
+}
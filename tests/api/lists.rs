@@ -0,0 +1,81 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_lists_form() {
+    let app = spawn_app().await;
+
+    let response = app.get_lists().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_create_a_list() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_create_list(&serde_json::json!({ "name": "Weekly digest" }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_default_list_is_available_out_of_the_box() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_subscribe_form_html().await;
+
+    assert!(html_page.contains("Newsletter</option>"));
+}
+
+#[tokio::test]
+async fn created_lists_can_be_subscribed_to() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_create_list(&serde_json::json!({ "name": "Weekly digest" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/lists");
+
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("<p><i>The list Weekly digest has been created.</i></p>"));
+    assert!(html_page.contains("<li>Weekly digest</li>"));
+
+    let html_page = app.get_subscribe_form_html().await;
+    assert!(html_page.contains("Weekly digest</option>"));
+}
+
+#[tokio::test]
+async fn list_names_must_be_unique() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_create_list(&serde_json::json!({ "name": "Weekly digest" }))
+        .await;
+    let response = app
+        .post_create_list(&serde_json::json!({ "name": "Weekly digest" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/lists");
+
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("<p><i>A list named Weekly digest already exists.</i></p>"));
+}
+
+#[tokio::test]
+async fn invalid_list_names_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_create_list(&serde_json::json!({ "name": "<script>" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/lists");
+
+    let html_page = app.get_lists_html().await;
+    assert!(
+        html_page.contains("<p><i>Please enter a list name without special characters.</i></p>")
+    );
+    assert!(!html_page.contains("<li><script></li>"));
+}
@@ -1,4 +1,7 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+use argon2::password_hash::{rand_core::OsRng, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+use zero2prod::authentication::Role;
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -34,3 +37,298 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+const LOCKED: &str = "<p><i>Too many failed attempts - please try again later</i></p>";
+
+async fn fail_login(app: &TestApp, username: &str, times: usize) {
+    for _ in 0..times {
+        let response = app
+            .post_login(&serde_json::json!({
+                "username": username,
+                "password": "wrong-password"
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+async fn log_in(app: &TestApp, user: &TestUser) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "username": &user.username,
+        "password": &user.password
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn repeated_failures_lock_the_username_even_with_the_right_password() {
+    let app = spawn_app().await;
+    fail_login(&app, &app.test_user.username, 3).await;
+
+    let response = log_in(&app, &app.test_user).await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert!(app.get_login_html().await.contains(LOCKED));
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn unknown_usernames_are_locked_the_same_way() {
+    let app = spawn_app().await;
+    fail_login(&app, "nobody", 3).await;
+
+    app.post_login(&serde_json::json!({
+        "username": "nobody",
+        "password": "wrong-password"
+    }))
+    .await;
+
+    assert!(app.get_login_html().await.contains(LOCKED));
+}
+
+#[tokio::test]
+async fn a_few_failures_do_not_lock_the_username() {
+    let app = spawn_app().await;
+    fail_login(&app, &app.test_user.username, 2).await;
+
+    let response = log_in(&app, &app.test_user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    // Logging in successfully starts the count again.
+    fail_login(&app, &app.test_user.username, 2).await;
+    let response = log_in(&app, &app.test_user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn delays_grow_until_the_username_is_locked_out() {
+    let app = spawn_app().await;
+    let mut lock_seconds = Vec::new();
+
+    for failures in [3, 4, 10] {
+        sqlx::query!("DELETE FROM login_failures")
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+        for _ in 0..failures {
+            // Attempts made while locked are refused without being counted.
+            sqlx::query!("UPDATE login_failures SET locked_until = NULL")
+                .execute(&app.db_pool)
+                .await
+                .unwrap();
+            fail_login(&app, "nobody", 1).await;
+        }
+        let seconds = sqlx::query!(
+            r#"SELECT extract(epoch FROM locked_until - last_failure_at)::float8 AS "seconds!"
+            FROM login_failures WHERE kind = 'username'"#
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .seconds;
+        lock_seconds.push(seconds.round());
+    }
+
+    assert_eq!(lock_seconds, [5.0, 10.0, 15.0 * 60.0]);
+}
+
+#[tokio::test]
+async fn locks_expire() {
+    let app = spawn_app().await;
+    fail_login(&app, &app.test_user.username, 3).await;
+
+    sqlx::query!("UPDATE login_failures SET locked_until = now() - interval '1 second'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = log_in(&app, &app.test_user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_locked_ip_address_cannot_log_in_as_anybody() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        "INSERT INTO login_failures (kind, key, failures, last_failure_at, locked_until)
+        VALUES ('client_ip', '127.0.0.1', 100, now(), now() + interval '15 minutes')"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = log_in(&app, &app.test_user).await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert!(app.get_login_html().await.contains(LOCKED));
+}
+
+#[tokio::test]
+async fn a_spoofed_forwarded_for_header_does_not_escape_an_ip_lock() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        "INSERT INTO login_failures (kind, key, failures, last_failure_at, locked_until)
+        VALUES ('client_ip', '127.0.0.1', 100, now(), now() + interval '15 minutes')"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app
+        .api_client
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", "198.51.100.1")
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+    assert!(app.get_login_html().await.contains(LOCKED));
+}
+
+#[tokio::test]
+async fn owners_can_see_and_clear_locks() {
+    let app = spawn_app().await;
+    let owner = TestUser::with_role(Role::Owner);
+    owner.store(&app.db_pool).await;
+    fail_login(&app, &owner.username, 3).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains(&format!(
+        "<tr><td>username</td><td>{}</td><td>3</td>",
+        owner.username
+    )));
+    let response = app.post_clear_login_lock("username", &owner.username).await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>The lock has been cleared.</i></p>"));
+    assert!(html_page.contains("Nobody is locked out."));
+    app.post_logout().await;
+    let response = log_in(&app, &owner).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn editors_cannot_clear_locks() {
+    let app = spawn_app().await;
+    let editor = TestUser::with_role(Role::Editor);
+    editor.store(&app.db_pool).await;
+    editor.login(&app).await;
+
+    let response = app.post_clear_login_lock("username", "nobody").await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+async fn stored_password_hash(app: &TestApp) -> String {
+    sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .password_hash
+}
+
+#[tokio::test]
+async fn outdated_password_hashes_are_upgraded_on_login() {
+    let app = spawn_app().await;
+    let salt = SaltString::generate(&mut OsRng);
+    let outdated_hash = Argon2::new(
+        Algorithm::Argon2i,
+        Version::V0x13,
+        Params::new(4096, 1, 1, None).unwrap(),
+    )
+    .hash_password(app.test_user.password.as_bytes(), &salt)
+    .unwrap()
+    .to_string();
+    sqlx::query!(
+        "UPDATE users SET password_hash = $2 WHERE user_id = $1",
+        app.test_user.user_id,
+        outdated_hash,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = log_in(&app, &app.test_user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let upgraded_hash = stored_password_hash(&app).await;
+    assert!(
+        upgraded_hash.starts_with("$argon2id$v=19$m=15000,t=2,p=1$"),
+        "{upgraded_hash}"
+    );
+    app.post_logout().await;
+    let response = log_in(&app, &app.test_user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn current_password_hashes_are_left_alone() {
+    let app = spawn_app().await;
+    let hash = stored_password_hash(&app).await;
+
+    log_in(&app, &app.test_user).await;
+
+    assert_eq!(stored_password_hash(&app).await, hash);
+}
+
+#[tokio::test]
+async fn failed_logins_do_not_rehash() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        "UPDATE users SET password_hash = replace(password_hash, 'm=15000,t=2', 'm=15000,t=3')
+        WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let hash = stored_password_hash(&app).await;
+
+    fail_login(&app, &app.test_user.username, 1).await;
+
+    assert_eq!(stored_password_hash(&app).await, hash);
+}
+
+#[tokio::test]
+async fn stale_login_failures_are_pruned() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        r#"
+        INSERT INTO login_failures (kind, key, failures, last_failure_at, locked_until)
+        VALUES
+            ('username', 'sprayed', 1, now() - interval '2 hours', NULL),
+            ('username', 'still-locked', 10, now() - interval '2 hours', now() + interval '1 hour')
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    app.post_login(&serde_json::json!({
+        "username": "random-username",
+        "password": "random-password"
+    }))
+    .await;
+
+    let keys: Vec<String> = sqlx::query!("SELECT key FROM login_failures ORDER BY key")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.key)
+        .collect();
+    assert!(!keys.contains(&"sprayed".to_string()));
+    assert!(keys.contains(&"still-locked".to_string()));
+    assert!(keys.contains(&"random-username".to_string()));
+}
@@ -2,8 +2,23 @@ mod helpers;
 
 mod admin_dashboard;
 mod change_password;
+mod email_events;
 mod health_check;
+mod invitations;
+mod lists;
 mod login;
+mod migrations;
+mod newsletter_analytics;
 mod newsletters;
+mod password_reset;
+mod preferences;
+mod segments;
+mod sessions;
+mod subject_line_tests;
+mod subscriber_import;
+mod subscribers;
 mod subscriptions;
 mod subscriptions_confirm;
+mod tracking;
+mod two_factor;
+mod users;
@@ -0,0 +1,332 @@
+use crate::helpers::spawn_empty_database;
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::migrate::Migrate;
+use sqlx::PgPool;
+use zero2prod::authentication::Role;
+use zero2prod::domain::SubscriptionStatus;
+use zero2prod::lists::DEFAULT_LIST_ID;
+
+async fn migrate_until(pool: &PgPool, version: i64) {
+    let migrator = sqlx::migrate!("./migrations");
+    let mut connection = pool.acquire().await.unwrap();
+    connection.ensure_migrations_table().await.unwrap();
+    for migration in migrator.iter().filter(|m| m.version < version) {
+        connection.apply(migration).await.unwrap();
+    }
+}
+
+async fn migrate_all(pool: &PgPool) {
+    sqlx::migrate!("./migrations").run(pool).await.unwrap();
+}
+
+#[tokio::test]
+async fn existing_newsletter_issues_survive_the_published_at_migration() {
+    let pool = spawn_empty_database().await;
+    migrate_until(&pool, 20261018120000).await;
+
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            published_at
+        )
+        VALUES (
+            'a4b2b4ae-5a1b-4b8e-9d8e-3f1b0c6c7d21',
+            'Newsletter title',
+            'Newsletter body as plain text',
+            '<p>Newsletter body as HTML</p>',
+            '2024-12-01 13:26:22.123456+00'
+        )
+        "#
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    migrate_all(&pool).await;
+
+    let saved = sqlx::query!(
+        // language=SQL
+        "SELECT title, published_at FROM newsletter_issues",
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to fetch saved newsletter issue.");
+
+    let expected: DateTime<Utc> = Utc.with_ymd_and_hms(2024, 12, 1, 13, 26, 22).unwrap()
+        + chrono::Duration::microseconds(123456);
+    assert_eq!(saved.title, "Newsletter title");
+    assert_eq!(saved.published_at, expected);
+}
+
+#[tokio::test]
+async fn queued_deliveries_are_rekeyed_by_subscriber_id() {
+    let pool = spawn_empty_database().await;
+    migrate_until(&pool, 20261018120100).await;
+
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES (
+            '6f1c8a52-0c5e-4d7e-9a53-2b8f4f1e9c10',
+            'ursula_le_guin@gmail.com',
+            'le guin',
+            now(),
+            'confirmed'
+        )
+        "#
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            published_at
+        )
+        VALUES (
+            'a4b2b4ae-5a1b-4b8e-9d8e-3f1b0c6c7d21',
+            'Newsletter title',
+            'Newsletter body as plain text',
+            '<p>Newsletter body as HTML</p>',
+            now()
+        )
+        "#
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    for email in ["ursula_le_guin@gmail.com", "unknown@example.com"] {
+        sqlx::query(
+            // language=SQL
+            r#"
+            INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+            VALUES ('a4b2b4ae-5a1b-4b8e-9d8e-3f1b0c6c7d21', $1)
+            "#,
+        )
+        .bind(email)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    migrate_all(&pool).await;
+
+    let queued = sqlx::query!(
+        // language=SQL
+        "SELECT newsletter_issue_id, subscriber_id FROM issue_delivery_queue",
+    )
+    .fetch_all(&pool)
+    .await
+    .expect("Failed to fetch queued deliveries.");
+
+    assert_eq!(queued.len(), 1);
+    assert_eq!(
+        queued[0].subscriber_id.to_string(),
+        "6f1c8a52-0c5e-4d7e-9a53-2b8f4f1e9c10"
+    );
+}
+
+#[tokio::test]
+async fn existing_statuses_survive_the_subscription_status_migration() {
+    let pool = spawn_empty_database().await;
+    migrate_until(&pool, 20261018120200).await;
+
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES
+            ('6f1c8a52-0c5e-4d7e-9a53-2b8f4f1e9c10', 'a@example.com', 'a', now(), 'confirmed'),
+            ('0d3e7a1b-94c4-4a36-8f0e-7e2d5b6c9a11', 'b@example.com', 'b', now(), 'pending_confirmation')
+        "#
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    migrate_all(&pool).await;
+
+    let saved = sqlx::query!(
+        // language=SQL
+        r#"SELECT email, status AS "status: SubscriptionStatus" FROM subscriptions ORDER BY email"#,
+    )
+    .fetch_all(&pool)
+    .await
+    .expect("Failed to fetch saved subscriptions.");
+
+    assert_eq!(saved[0].status, SubscriptionStatus::Confirmed);
+    assert_eq!(saved[1].status, SubscriptionStatus::PendingConfirmation);
+}
+
+#[tokio::test]
+async fn existing_subscribers_and_issues_are_assigned_to_the_default_list() {
+    let pool = spawn_empty_database().await;
+    migrate_until(&pool, 20261018120300).await;
+
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ('6f1c8a52-0c5e-4d7e-9a53-2b8f4f1e9c10', 'a@example.com', 'a', now(), 'confirmed')
+        "#
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        // language=SQL
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            published_at
+        )
+        VALUES (
+            'a4b2b4ae-5a1b-4b8e-9d8e-3f1b0c6c7d21',
+            'Newsletter title',
+            'Newsletter body as plain text',
+            '<p>Newsletter body as HTML</p>',
+            now()
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    migrate_all(&pool).await;
+
+    let membership = sqlx::query!("SELECT list_id, subscriber_id FROM list_subscriptions")
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to fetch list memberships.");
+    assert_eq!(membership.list_id, DEFAULT_LIST_ID);
+    let issue = sqlx::query!("SELECT list_id FROM newsletter_issues")
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to fetch newsletter issues.");
+    assert_eq!(issue.list_id, DEFAULT_LIST_ID);
+}
+
+#[tokio::test]
+async fn existing_subscribers_get_a_preference_token() {
+    let pool = spawn_empty_database().await;
+    migrate_until(&pool, 20261018120400).await;
+
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ('6f1c8a52-0c5e-4d7e-9a53-2b8f4f1e9c10', 'a@example.com', 'a', now(), 'confirmed')
+        "#
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    migrate_all(&pool).await;
+
+    let saved = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT plain_text_only, preference_token
+        FROM subscriptions
+        JOIN preference_tokens ON preference_tokens.subscriber_id = subscriptions.id
+        "#
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to fetch the subscriber preferences.");
+    assert!(!saved.plain_text_only);
+    assert!(!saved.preference_token.is_empty());
+}
+
+#[tokio::test]
+async fn existing_subscribers_get_a_history_ending_in_their_current_status() {
+    let pool = spawn_empty_database().await;
+    migrate_until(&pool, 20261018120600).await;
+
+    sqlx::query!(
+        // language=SQL
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES
+            ('0b7d3e4c-8f2a-4c61-b5d9-1e6a7f3c2d48', 'a@example.com', 'a', now(), 'confirmed'),
+            ('5c2e9a1f-3b7d-4e08-a6c4-9d1f2e8b7a35', 'b@example.com', 'b', now(), 'pending_confirmation')
+        "#
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    migrate_all(&pool).await;
+
+    let history = sqlx::query!(
+        // language=SQL
+        r#"
+        SELECT subscriptions.email,
+               subscription_status_changes.status AS "status: SubscriptionStatus",
+               changed_at = subscriptions.subscribed_at AS "at_sign_up!"
+        FROM subscription_status_changes
+        JOIN subscriptions ON subscriptions.id = subscription_status_changes.subscriber_id
+        ORDER BY subscriptions.email, changed_at
+        "#
+    )
+    .fetch_all(&pool)
+    .await
+    .expect("Failed to fetch the subscription history.")
+    .into_iter()
+    .map(|r| (r.email, r.status, r.at_sign_up))
+    .collect::<Vec<_>>();
+    assert_eq!(
+        history,
+        vec![
+            (
+                "a@example.com".to_string(),
+                SubscriptionStatus::PendingConfirmation,
+                true
+            ),
+            (
+                "a@example.com".to_string(),
+                SubscriptionStatus::Confirmed,
+                false
+            ),
+            (
+                "b@example.com".to_string(),
+                SubscriptionStatus::PendingConfirmation,
+                true
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn existing_users_become_owners() {
+    let pool = spawn_empty_database().await;
+    migrate_until(&pool, 20261018121500).await;
+
+    migrate_all(&pool).await;
+
+    let user = sqlx::query!(
+        // language=SQL
+        r#"SELECT username, role AS "role: Role" FROM users"#
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to fetch the seeded user.");
+    assert_eq!(user.username, "admin");
+    assert_eq!(user.role, Role::Owner);
+}
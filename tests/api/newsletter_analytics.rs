@@ -0,0 +1,219 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, when_sending_an_email, TestApp,
+};
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+async fn publish_newsletter(app: &TestApp) -> Uuid {
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": r#"<p>Newsletter body as HTML. <a href="https://example.com/post">Read more</a></p>"#,
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "track_opens": "true",
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+}
+
+/// Returns the HTML body of every newsletter sent so far, together with the recipient.
+async fn sent_newsletters(app: &TestApp) -> Vec<(String, String)> {
+    let mut newsletters = vec![];
+    for request in app.email_server.received_requests().await.unwrap() {
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        if body["personalizations"][0]["subject"] != "Newsletter title" {
+            continue;
+        }
+        let html = body["content"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|v| v["type"] == "text/html")
+            .unwrap()["value"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+        let recipient = body["personalizations"][0]["to"][0]["email"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+        newsletters.push((recipient, html));
+    }
+    newsletters
+}
+
+fn find_url(html: &str, prefix: &str) -> String {
+    let start = html.find(prefix).unwrap();
+    let end = start + html[start..].find('"').unwrap();
+    html[start..end].to_owned()
+}
+
+async fn subscriber_id(app: &TestApp, email: &str) -> Uuid {
+    sqlx::query!("SELECT id FROM subscriptions WHERE email = $1", email)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+}
+
+#[tokio::test]
+async fn the_issue_page_summarizes_what_happened_to_an_issue() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for _ in 0..4 {
+        create_confirmed_subscriber(&app).await;
+    }
+    let issue_id = publish_newsletter(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .up_to_n_times(3)
+        .mount(&app.email_server)
+        .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    let newsletters = sent_newsletters(&app).await;
+    let pixel_url = find_url(&newsletters[0].1, &format!("{}/o/", app.address));
+    reqwest::get(pixel_url).await.unwrap();
+    let link_url = find_url(&newsletters[1].1, &format!("{}/r/", app.address));
+    app.api_client.get(&link_url).send().await.unwrap();
+    app.api_client.get(&link_url).send().await.unwrap();
+    let unsubscriber = subscriber_id(&app, &newsletters[1].0).await;
+    app.post_subscriber_action(unsubscriber, "unsubscribe")
+        .await;
+    let response = app
+        .post_email_events(&serde_json::json!([{
+            "email": newsletters[2].0,
+            "timestamp": 1_700_000_000,
+            "event": "bounce",
+            "type": "bounce",
+            "sg_event_id": Uuid::new_v4().to_string(),
+        }]))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let html_page = app.get_issue_analytics_html(issue_id).await;
+
+    assert!(html_page.contains("<h1>Newsletter title</h1>"));
+    assert!(html_page.contains("<tr><th>Enqueued</th><td>4</td></tr>"));
+    assert!(html_page.contains("<tr><th>Still queued</th><td>0</td></tr>"));
+    assert!(html_page.contains("<tr><th>Sent</th><td>3</td></tr>"));
+    assert!(html_page.contains("<tr><th>Failed</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Skipped</th><td>0</td></tr>"));
+    assert!(html_page.contains("<tr><th>Bounced</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Opened</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Clicked</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Unsubscribed</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><td>https://example.com/post</td><td>2</td><td>1</td></tr>"));
+    assert!(html_page.contains("<td>3</td><td>1</td><td>4 / 4</td>"));
+}
+
+#[tokio::test]
+async fn the_issue_page_shows_queued_recipients_before_delivery() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+
+    let issue_id = publish_newsletter(&app).await;
+
+    let html_page = app.get_issue_analytics_html(issue_id).await;
+    assert!(html_page.contains("<tr><th>Enqueued</th><td>2</td></tr>"));
+    assert!(html_page.contains("<tr><th>Still queued</th><td>2</td></tr>"));
+    assert!(html_page.contains("Nothing delivered yet."));
+}
+
+#[tokio::test]
+async fn subscribers_dropped_before_delivery_are_counted_as_skipped() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let issue_id = publish_newsletter(&app).await;
+    let unsubscriber = sqlx::query!("SELECT id FROM subscriptions LIMIT 1")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.post_subscriber_action(unsubscriber, "unsubscribe")
+        .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_issue_analytics_html(issue_id).await;
+    assert!(html_page.contains("<tr><th>Enqueued</th><td>2</td></tr>"));
+    assert!(html_page.contains("<tr><th>Sent</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Skipped</th><td>1</td></tr>"));
+}
+
+#[tokio::test]
+async fn unsubscribes_are_attributed_to_the_last_issue_sent() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let first_issue_id = publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Second issue",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    app.dispatch_all_pending_emails().await;
+    let second_issue_id = sqlx::query!(
+        "SELECT newsletter_issue_id FROM newsletter_issues WHERE title = 'Second issue'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .newsletter_issue_id;
+
+    let subscriber = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.post_subscriber_action(subscriber, "unsubscribe").await;
+
+    let first_page = app.get_issue_analytics_html(first_issue_id).await;
+    assert!(first_page.contains("<tr><th>Unsubscribed</th><td>0</td></tr>"));
+    let second_page = app.get_issue_analytics_html(second_issue_id).await;
+    assert!(second_page.contains("<tr><th>Unsubscribed</th><td>1</td></tr>"));
+}
+
+#[tokio::test]
+async fn unknown_issues_are_a_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_issue_analytics(Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_issue_analytics() {
+    let app = spawn_app().await;
+
+    let response = app.get_issue_analytics(Uuid::new_v4()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -1,56 +1,13 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_list_subscriber,
+    create_unconfirmed_subscriber, spawn_app, when_sending_an_email, TestApp,
+};
 use fake::faker::internet::en::SafeEmail;
-use fake::faker::name::en::Name;
 use fake::Fake;
 use std::time::Duration;
-use wiremock::matchers::{any, method, path};
-use wiremock::{Mock, MockBuilder, ResponseTemplate};
-
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(&serde_json::json!({
-        "name": name,
-        "email": email
-    }))
-    .unwrap();
-
-    let _mock_guard = when_sending_an_email()
-        .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
-        .expect(1)
-        .mount_as_scoped(&app.email_server)
-        .await;
-
-    app.post_subscriptions(body.into())
-        .await
-        .error_for_status()
-        .unwrap();
-
-    let email_request = &app
-        .email_server
-        .received_requests()
-        .await
-        .unwrap()
-        .pop()
-        .unwrap();
-
-    app.get_confirmation_links(&email_request)
-}
-
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_link = create_unconfirmed_subscriber(app).await;
-
-    reqwest::get(confirmation_link.html)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
-}
-
-fn when_sending_an_email() -> MockBuilder {
-    Mock::given(path("/v3/mail/send")).and(method("POST"))
-}
+use uuid::Uuid;
+use wiremock::matchers::{any, body_string_contains};
+use wiremock::{Mock, ResponseTemplate};
 
 #[tokio::test]
 async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
@@ -227,3 +184,248 @@ async fn concurrent_form_submission_is_handled_gracefully() {
 
     app.dispatch_all_pending_emails().await;
 }
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_subscribers_who_are_no_longer_confirmed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    sqlx::query!(
+        // language=SQL
+        "UPDATE subscriptions SET status = 'unsubscribed'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn newsletters_are_delivered_to_the_current_address_of_a_subscriber() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let new_email: String = SafeEmail().fake();
+    sqlx::query!(
+        // language=SQL
+        "UPDATE subscriptions SET email = $1",
+        new_email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    when_sending_an_email()
+        .and(body_string_contains(new_email.as_str()))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn newsletters_are_only_delivered_to_members_of_the_chosen_list() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = app.create_list("Weekly digest").await;
+    create_confirmed_subscriber(&app).await;
+    let list_member_email: String = SafeEmail().fake();
+    let confirmation_link =
+        create_unconfirmed_list_subscriber(&app, &list_member_email, Some(list_id)).await;
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "list_id": list_id.to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let newsletter_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body = String::from_utf8(newsletter_request.body).unwrap();
+    assert!(body.contains("Newsletter title"));
+    assert!(body.contains(&list_member_email));
+}
+
+#[tokio::test]
+async fn newsletters_cannot_be_published_to_an_unknown_list() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "list_id": uuid::Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+async fn publish_newsletter(app: &TestApp) -> Uuid {
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+}
+
+async fn n_queued_tasks(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n
+}
+
+#[tokio::test]
+async fn paused_issues_are_not_delivered_until_resumed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let issue_id = publish_newsletter(&app).await;
+
+    let response = app.post_issue_action(issue_id, "pause").await;
+    assert_is_redirect_to(&response, &format!("/admin/newsletters/{issue_id}"));
+    let html_page = app.get_issue_analytics_html(issue_id).await;
+    assert!(html_page.contains("<p><i>The issue has been paused.</i></p>"));
+    assert!(html_page.contains("<p>State: paused</p>"));
+
+    {
+        let _mock_guard = Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount_as_scoped(&app.email_server)
+            .await;
+        app.dispatch_all_pending_emails().await;
+    }
+    assert_eq!(n_queued_tasks(&app).await, 1);
+
+    let response = app.post_issue_action(issue_id, "resume").await;
+    assert_is_redirect_to(&response, &format!("/admin/newsletters/{issue_id}"));
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(n_queued_tasks(&app).await, 0);
+}
+
+#[tokio::test]
+async fn cancelling_an_issue_removes_and_records_its_remaining_deliveries() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let issue_id = publish_newsletter(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_issue_action(issue_id, "cancel").await;
+    assert_is_redirect_to(&response, &format!("/admin/newsletters/{issue_id}"));
+
+    assert_eq!(n_queued_tasks(&app).await, 0);
+    let html_page = app.get_issue_analytics_html(issue_id).await;
+    assert!(html_page.contains("<p><i>The issue has been cancelled.</i></p>"));
+    assert!(html_page.contains("<tr><th>Cancelled</th><td>2</td></tr>"));
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn a_cancelled_issue_cannot_be_resumed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let issue_id = publish_newsletter(&app).await;
+    app.post_issue_action(issue_id, "cancel").await;
+    app.get_issue_analytics_html(issue_id).await;
+
+    let response = app.post_issue_action(issue_id, "resume").await;
+    assert_is_redirect_to(&response, &format!("/admin/newsletters/{issue_id}"));
+
+    let html_page = app.get_issue_analytics_html(issue_id).await;
+    assert!(html_page.contains("<p><i>An issue cannot go from cancelled to sending.</i></p>"));
+    assert!(html_page.contains("<p>State: cancelled</p>"));
+}
+
+#[tokio::test]
+async fn changing_the_state_of_an_unknown_issue_returns_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_issue_action(Uuid::new_v4(), "pause").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_the_state_of_an_issue() {
+    let app = spawn_app().await;
+
+    let response = app.post_issue_action(Uuid::new_v4(), "cancel").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
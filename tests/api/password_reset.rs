@@ -0,0 +1,380 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, when_sending_an_email, TestApp};
+use std::time::Duration;
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+const GENERIC_ANSWER: &str =
+    "If an account uses that address, we have sent it a link to reset the password.";
+
+async fn set_test_user_email(app: &TestApp, email: &str) {
+    sqlx::query!(
+        "UPDATE users SET email = $2 WHERE user_id = $1",
+        app.test_user.user_id,
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+/// Requests a reset for the test user and returns the token from the email.
+async fn request_reset_token(app: &TestApp) -> String {
+    set_test_user_email(app, "admin@example.com").await;
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+    let response = app
+        .post_request_password_reset(&serde_json::json!({ "email": "Admin@Example.com" }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let email_request = wait_for_email(app, already_sent).await;
+    let link = app.get_confirmation_links(&email_request).html;
+    assert_eq!(link.path(), "/password-reset/confirm");
+    link.query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+/// Reset links are sent in the background, after the response.
+async fn wait_for_email(app: &TestApp, already_sent: usize) -> wiremock::Request {
+    for _ in 0..50 {
+        let mut requests = app.email_server.received_requests().await.unwrap();
+        if requests.len() > already_sent {
+            return requests.pop().unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("No reset link was sent.");
+}
+
+async fn get_reset_form(app: &TestApp, token: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/password-reset/confirm", app.address))
+        .query(&[("token", token)])
+        .send()
+        .await
+        .unwrap()
+}
+
+fn reset_body(token: &str, new_password: &str) -> serde_json::Value {
+    serde_json::json!({
+        "token": token,
+        "new_password": new_password,
+        "new_password_check": new_password,
+    })
+}
+
+#[tokio::test]
+async fn the_login_form_links_to_the_password_reset_form() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_login_html().await;
+
+    assert!(html_page.contains(r#"<a href="/password-reset">Forgot your password?</a>"#));
+}
+
+#[tokio::test]
+async fn a_reset_link_is_emailed_to_known_addresses() {
+    let app = spawn_app().await;
+
+    let token = request_reset_token(&app).await;
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(GENERIC_ANSWER));
+    let stored = sqlx::query!("SELECT token_hash FROM password_reset_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.token_hash, token);
+    assert_eq!(get_reset_form(&app, &token).await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn unknown_addresses_get_the_same_answer_but_no_email() {
+    let app = spawn_app().await;
+    set_test_user_email(&app, "admin@example.com").await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for email in ["nobody@example.com", "not-an-email"] {
+        let response = app
+            .post_request_password_reset(&serde_json::json!({ "email": email }))
+            .await;
+
+        assert_is_redirect_to(&response, "/login");
+        let html_page = app.get_login_html().await;
+        assert!(html_page.contains(GENERIC_ANSWER));
+    }
+}
+
+#[tokio::test]
+async fn a_reset_link_lets_you_choose_a_new_password() {
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    let response = app
+        .post_reset_password(&reset_body(&token, &new_password))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your password has been reset - you can now log in.</i></p>"));
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    let another_token = request_reset_token(&app).await;
+    app.post_reset_password(&reset_body(&token, &Uuid::new_v4().to_string()))
+        .await;
+
+    for token in [token, another_token] {
+        assert_eq!(get_reset_form(&app, &token).await.status().as_u16(), 401);
+        let response = app
+            .post_reset_password(&reset_body(&token, &Uuid::new_v4().to_string()))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
+
+#[tokio::test]
+async fn expired_reset_links_are_rejected() {
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert_eq!(get_reset_form(&app, &token).await.status().as_u16(), 401);
+    let response = app
+        .post_reset_password(&reset_body(&token, &Uuid::new_v4().to_string()))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn new_passwords_must_match() {
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    let mut body = reset_body(&token, &Uuid::new_v4().to_string());
+    body["new_password_check"] = Uuid::new_v4().to_string().into();
+
+    let response = app.post_reset_password(&body).await;
+
+    assert_is_redirect_to(&response, &format!("/password-reset/confirm?token={token}"));
+    let html_page = get_reset_form(&app, &token).await.text().await.unwrap();
+    assert!(html_page.contains("You entered two different new passwords"));
+}
+
+/// Asks to change the test user's account email and returns the confirmation link.
+async fn request_account_email_change(app: &TestApp, email: &str) -> reqwest::Url {
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .post_change_account_email(&serde_json::json!({
+            "email": email,
+            "current_password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let link = app.get_confirmation_links(&email_request).html;
+    assert_eq!(link.path(), "/account-email/confirm");
+    link
+}
+
+async fn account_email(app: &TestApp) -> Option<String> {
+    sqlx::query!(
+        "SELECT email FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .email
+}
+
+#[tokio::test]
+async fn admins_can_set_the_email_address_for_password_resets() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let link = request_account_email_change(&app, "admin@example.com").await;
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>We have sent a confirmation link to your new address."));
+    assert_eq!(account_email(&app).await, None);
+
+    let response = app.api_client.get(link).send().await.unwrap();
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Your email address has been changed.</i></p>"));
+    assert!(html_page.contains(r#"value="admin@example.com""#));
+}
+
+#[tokio::test]
+async fn an_account_email_confirmation_link_works_only_once() {
+    let app = spawn_app().await;
+    set_test_user_email(&app, "old@example.com").await;
+    app.test_user.login(&app).await;
+    let link = request_account_email_change(&app, "new@example.com").await;
+    app.api_client.get(link.clone()).send().await.unwrap();
+    set_test_user_email(&app, "old@example.com").await;
+
+    let response = app.api_client.get(link).send().await.unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+    assert_eq!(
+        account_email(&app).await.as_deref(),
+        Some("old@example.com")
+    );
+}
+
+#[tokio::test]
+async fn changing_the_account_email_requires_the_current_password() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let response = app
+        .post_change_account_email(&serde_json::json!({
+            "email": "attacker@example.com",
+            "current_password": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+    assert_eq!(account_email(&app).await, None);
+}
+
+#[tokio::test]
+async fn account_email_addresses_must_be_valid_and_unique() {
+    let app = spawn_app().await;
+    sqlx::query!("UPDATE users SET email = 'taken@example.com' WHERE username = 'admin'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.test_user.login(&app).await;
+    let test_cases = [
+        ("not-an-email", "Please enter a valid email address."),
+        (
+            "TAKEN@example.com",
+            "That email address belongs to another user.",
+        ),
+    ];
+
+    for (email, message) in test_cases {
+        let response = app
+            .post_change_account_email(&serde_json::json!({
+                "email": email,
+                "current_password": &app.test_user.password,
+            }))
+            .await;
+
+        assert_is_redirect_to(&response, "/admin/password");
+        let html_page = app.get_change_password_html().await;
+        assert!(html_page.contains(message), "Expected {message:?}");
+    }
+}
+
+#[tokio::test]
+async fn reset_passwords_must_follow_the_password_policy() {
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+
+    let response = app
+        .post_reset_password(&reset_body(&token, "password1234"))
+        .await;
+
+    assert_is_redirect_to(&response, &format!("/password-reset/confirm?token={token}"));
+    let html_page = get_reset_form(&app, &token).await.text().await.unwrap();
+    assert!(html_page.contains("The new password is too common"));
+}
+
+#[tokio::test]
+async fn resetting_a_password_logs_out_every_session() {
+    let app = spawn_app().await;
+    let phone = app
+        .log_in_on_another_device(&app.test_user, "Phone Browser")
+        .await;
+    let token = request_reset_token(&app).await;
+
+    let new_password = Uuid::new_v4().to_string();
+    app.post_reset_password(&reset_body(&token, &new_password))
+        .await;
+
+    let response = phone
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_failed_reset_does_not_use_up_the_link() {
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    // Make the password update fail once the token has been checked.
+    sqlx::query!(
+        "ALTER TABLE users ADD CONSTRAINT reject_updates CHECK (password_hash = '') NOT VALID"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let new_password = Uuid::new_v4().to_string();
+    let response = app
+        .post_reset_password(&reset_body(&token, &new_password))
+        .await;
+    assert_eq!(response.status().as_u16(), 500);
+
+    sqlx::query!("ALTER TABLE users DROP CONSTRAINT reject_updates")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app
+        .post_reset_password(&reset_body(&token, &new_password))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,482 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    when_sending_an_email, TestApp,
+};
+use wiremock::matchers::{any, body_string_contains};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::SubscriptionStatus;
+use zero2prod::lists::DEFAULT_LIST_ID;
+
+async fn preference_token(app: &TestApp) -> String {
+    sqlx::query!("SELECT preference_token FROM preference_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch preference token.")
+        .preference_token
+}
+
+async fn publish_newsletter(app: &TestApp) {
+    app.test_user.login(app).await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+#[tokio::test]
+async fn newsletter_emails_link_to_the_preferences_page() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let links = app.get_confirmation_links(&email_request);
+    assert_eq!(links.html, links.plain_text);
+    assert_eq!(links.html.path(), "/preferences");
+
+    let response = reqwest::get(links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("Save preferences"));
+}
+
+#[tokio::test]
+async fn preferences_with_an_unknown_token_are_rejected_with_a_401() {
+    let app = spawn_app().await;
+
+    let response = app.get_preferences("unknown").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn subscribers_can_change_their_name() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preference_token(&app).await;
+
+    let response = app
+        .post_preferences(format!(
+            "token={token}&name=Ursula%20K.%20Le%20Guin&list_id={DEFAULT_LIST_ID}"
+        ))
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={token}"));
+
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("<p><i>Your preferences have been updated.</i></p>"));
+    let saved = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "Ursula K. Le Guin");
+}
+
+#[tokio::test]
+async fn invalid_names_are_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preference_token(&app).await;
+    let name_before = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .name;
+
+    let response = app
+        .post_preferences(format!("token={token}&name=%3Cscript%3E"))
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={token}"));
+
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("<p><i>Please enter a valid name.</i></p>"));
+    let saved = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, name_before);
+}
+
+#[tokio::test]
+async fn subscribers_can_pick_their_lists() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = app.create_list("Weekly digest").await;
+    create_confirmed_subscriber(&app).await;
+    let token = preference_token(&app).await;
+
+    app.post_preferences(format!("token={token}&name=le%20guin&list_id={list_id}"))
+        .await;
+
+    let memberships = sqlx::query!("SELECT list_id FROM list_subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(memberships.len(), 1);
+    assert_eq!(memberships[0].list_id, list_id);
+}
+
+#[tokio::test]
+async fn plain_text_only_subscribers_receive_plain_text_newsletters() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preference_token(&app).await;
+    app.post_preferences(format!(
+        "token={token}&name=le%20guin&list_id={DEFAULT_LIST_ID}&plain_text_only=true"
+    ))
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let content = body["content"].as_array().unwrap();
+    assert_eq!(content.len(), 1);
+    assert_eq!(content[0]["type"], "text/plain");
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_do_not_receive_newsletters() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preference_token(&app).await;
+
+    let response = app
+        .post_unsubscribe(&serde_json::json!({ "token": &token }))
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={token}"));
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("<p><i>You have been unsubscribed.</i></p>"));
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let saved = sqlx::query!(r#"SELECT status AS "status: SubscriptionStatus" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, SubscriptionStatus::Unsubscribed);
+}
+
+#[tokio::test]
+async fn pending_subscribers_cannot_unsubscribe() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    let token = preference_token(&app).await;
+
+    app.post_unsubscribe(&serde_json::json!({ "token": &token }))
+        .await;
+
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("<p><i>Only confirmed subscriptions can be cancelled.</i></p>"));
+}
+
+async fn current_email(app: &TestApp) -> String {
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.")
+        .email
+}
+
+#[tokio::test]
+async fn changing_email_sends_a_confirmation_to_the_new_address() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let old_email = current_email(&app).await;
+    let token = preference_token(&app).await;
+    when_sending_an_email()
+        .and(body_string_contains("new@example.com"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "token": token,
+            "new_email": "new@example.com",
+        }))
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={token}"));
+
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("We have sent a confirmation link to your new address."));
+    assert_eq!(current_email(&app).await, old_email);
+}
+
+#[tokio::test]
+async fn confirming_an_email_change_switches_the_address_and_notifies_the_old_one() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let old_email = current_email(&app).await;
+    let token = preference_token(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_change_email(&serde_json::json!({
+        "token": token,
+        "new_email": "new@example.com",
+    }))
+    .await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(current_email(&app).await, "new@example.com");
+    let notice = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body = String::from_utf8(notice.body).unwrap();
+    assert!(body.contains(&old_email));
+    assert!(body.contains("Your email address has been changed"));
+}
+
+#[tokio::test]
+async fn email_change_links_can_only_be_used_once() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preference_token(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_change_email(&serde_json::json!({
+        "token": token,
+        "new_email": "new@example.com",
+    }))
+    .await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    let response = reqwest::get(confirmation_links.html.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn changing_to_an_address_that_is_already_subscribed_sends_nothing() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preference_token(&app).await;
+    let other = create_unconfirmed_subscriber(&app).await;
+    let other_email = sqlx::query!(
+        "SELECT email FROM subscriptions \
+        JOIN subscription_tokens ON subscriber_id = id \
+        WHERE subscription_token = $1",
+        other
+            .html
+            .query_pairs()
+            .find(|(k, _)| k == "subscription_token")
+            .unwrap()
+            .1
+            .to_string(),
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .email;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "token": token,
+            "new_email": other_email,
+        }))
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={token}"));
+
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("We have sent a confirmation link to your new address."));
+}
+
+#[tokio::test]
+async fn changing_to_an_invalid_email_is_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let old_email = current_email(&app).await;
+    let token = preference_token(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "token": token,
+            "new_email": "definitely-not-an-email",
+        }))
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={token}"));
+
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("Please enter a valid email address."));
+    assert_eq!(current_email(&app).await, old_email);
+}
+
+#[tokio::test]
+async fn subscribers_can_download_their_data() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = current_email(&app).await;
+    let token = preference_token(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let response = app.get_subscriber_data(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Disposition"],
+        "attachment; filename=\"subscriber-data.json\""
+    );
+    let data: serde_json::Value = response.json().await.unwrap();
+
+    assert_eq!(data["email"], email);
+    assert_eq!(data["status"], "confirmed");
+    assert_eq!(data["lists"][0]["name"], "Newsletter");
+    assert_eq!(data["status_history"][1]["status"], "confirmed");
+    assert_eq!(data["deliveries"][0]["issue_title"], "Newsletter title");
+    assert_eq!(data["deliveries"][0]["succeeded"], true);
+}
+
+#[tokio::test]
+async fn data_downloads_with_an_unknown_token_are_rejected_with_a_401() {
+    let app = spawn_app().await;
+
+    let response = app.get_subscriber_data("unknown").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn erasing_data_removes_the_subscriber_but_keeps_delivery_statistics() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preference_token(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let response = app.post_erase_data(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("All your data has been erased."));
+
+    let remaining = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(*) FROM subscriptions) AS "subscriptions!",
+            (SELECT count(*) FROM subscription_tokens) AS "subscription_tokens!",
+            (SELECT count(*) FROM preference_tokens) AS "preference_tokens!",
+            (SELECT count(*) FROM list_subscriptions) AS "list_subscriptions!",
+            (SELECT count(*) FROM subscription_status_changes WHERE subscriber_id IS NULL) AS "anonymous_status_changes!",
+            (SELECT count(*) FROM issue_deliveries WHERE subscriber_id IS NULL) AS "anonymous_deliveries!"
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining.subscriptions, 0);
+    assert_eq!(remaining.subscription_tokens, 0);
+    assert_eq!(remaining.preference_tokens, 0);
+    assert_eq!(remaining.list_subscriptions, 0);
+    assert_eq!(remaining.anonymous_status_changes, 2);
+    assert_eq!(remaining.anonymous_deliveries, 1);
+
+    let erasure = sqlx::query!("SELECT requested_by FROM subscriber_erasures")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The erasure was not recorded.");
+    assert_eq!(erasure.requested_by, "subscriber");
+    assert_eq!(app.get_preferences(&token).await.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn erasing_a_subscriber_with_queued_issues_drops_them() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preference_token(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+
+    app.post_erase_data(&token).await;
+
+    let n_queued = sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_queued, 0);
+    app.dispatch_all_pending_emails().await;
+}
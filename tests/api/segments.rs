@@ -0,0 +1,262 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_unconfirmed_list_subscriber, spawn_app, when_sending_an_email,
+    TestApp,
+};
+use wiremock::ResponseTemplate;
+use zero2prod::lists::DEFAULT_LIST_ID;
+
+async fn create_confirmed_subscriber_with_email(app: &TestApp, email: &str) {
+    let confirmation_link = create_unconfirmed_list_subscriber(app, email, None).await;
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+fn recipients_message(n: usize) -> String {
+    format!("<p><i>This issue would go out to {n} subscriber(s).</i></p>")
+}
+
+#[tokio::test]
+async fn segments_can_be_created_and_listed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_create_segment(&serde_json::json!({
+            "name": "Gmail <early birds>",
+            "subscribed_before": "2025-01-01",
+            "email_domain": "@Gmail.com",
+            "opened_in_last_issues": "3",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/segments");
+
+    let html_page = app.get_segments_html().await;
+    assert!(html_page.contains("<p><i>The segment has been created.</i></p>"));
+    assert!(html_page.contains(
+        "<li>Gmail &lt;early birds&gt;: subscribed before 2025-01-01, \
+         opened one of the last 3 issues, email address at gmail.com</li>"
+    ));
+}
+
+#[tokio::test]
+async fn invalid_segments_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for (body, message) in [
+        (
+            serde_json::json!({ "name": "Nothing" }),
+            "A segment needs at least one rule.",
+        ),
+        (
+            serde_json::json!({ "name": "Bad dates", "subscribed_after": "yesterday" }),
+            "Please enter valid dates.",
+        ),
+        (
+            serde_json::json!({ "name": "Bad list", "list_id": uuid::Uuid::new_v4().to_string() }),
+            "Please pick a known mailing list.",
+        ),
+    ] {
+        let response = app.post_create_segment(&body).await;
+        assert_is_redirect_to(&response, "/admin/segments");
+
+        let html_page = app.get_segments_html().await;
+        assert!(html_page.contains(&format!("<p><i>{message}</i></p>")));
+    }
+    let n_segments = sqlx::query!(r#"SELECT count(*) AS "n!" FROM segments"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_segments, 0);
+}
+
+#[tokio::test]
+async fn segment_names_must_be_unique() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let body = serde_json::json!({ "name": "Gmail", "email_domain": "gmail.com" });
+    app.post_create_segment(&body).await;
+
+    app.post_create_segment(&body).await;
+
+    let html_page = app.get_segments_html().await;
+    assert!(html_page.contains("<p><i>A segment with that name already exists.</i></p>"));
+}
+
+#[tokio::test]
+async fn the_recipient_preview_applies_the_segment_rules() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber_with_email(&app, "ursula@gmail.com").await;
+    create_confirmed_subscriber_with_email(&app, "le_guin@GMAIL.com").await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    let gmail = app
+        .create_segment(serde_json::json!({ "name": "Gmail", "email_domain": "gmail.com" }))
+        .await;
+    sqlx::query!(
+        "UPDATE subscriptions SET subscribed_at = '2020-06-01' WHERE email = 'ursula@gmail.com'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let veterans = app
+        .create_segment(
+            serde_json::json!({ "name": "Veterans", "subscribed_before": "2021-01-01" }),
+        )
+        .await;
+    let weekly = app.create_list("Weekly digest").await;
+    sqlx::query!(
+        "INSERT INTO list_subscriptions (list_id, subscriber_id, subscribed_at)
+         SELECT $1, id, now() FROM subscriptions WHERE email LIKE 'ursula@%'",
+        weekly
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let weekly_readers = app
+        .create_segment(
+            serde_json::json!({ "name": "Weekly readers", "list_id": weekly.to_string() }),
+        )
+        .await;
+
+    for (segment_id, n_recipients) in [
+        (String::new(), 3),
+        (gmail.to_string(), 2),
+        (veterans.to_string(), 1),
+        (weekly_readers.to_string(), 2),
+    ] {
+        let html_page = app
+            .get_publish_newsletter_preview_html(&format!(
+                "list_id={DEFAULT_LIST_ID}&segment_id={segment_id}"
+            ))
+            .await;
+        assert!(
+            html_page.contains(&recipients_message(n_recipients)),
+            "Expected {n_recipients} recipients for segment {segment_id:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn the_recipient_preview_keeps_what_was_typed_so_far() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let html_page = app
+        .get_publish_newsletter_preview_html("title=%22Hello%22&html_content=%3Cp%3EHi%3C%2Fp%3E")
+        .await;
+
+    assert!(html_page.contains(&recipients_message(0)));
+    assert!(html_page.contains(r#"value="&quot;Hello&quot;""#));
+    assert!(html_page.contains("&lt;p&gt;Hi&lt;/p&gt;</textarea>"));
+}
+
+#[tokio::test]
+async fn engagement_segments_match_subscribers_who_opened_recent_issues() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber_with_email(&app, "reader@example.com").await;
+    create_confirmed_subscriber_with_email(&app, "lurker@example.com").await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Tracked issue",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "track_opens": "true",
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    sqlx::query!(
+        "INSERT INTO issue_opens
+         SELECT newsletter_issue_id, id, now(), now(), 1
+         FROM newsletter_issues, subscriptions
+         WHERE email = 'reader@example.com'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let engaged = app
+        .create_segment(serde_json::json!({ "name": "Engaged", "opened_in_last_issues": "1" }))
+        .await;
+
+    let html_page = app
+        .get_publish_newsletter_preview_html(&format!("segment_id={engaged}"))
+        .await;
+
+    assert!(html_page.contains(&recipients_message(1)));
+}
+
+#[tokio::test]
+async fn issues_published_to_a_segment_only_reach_its_members() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber_with_email(&app, "ursula@gmail.com").await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    let gmail = app
+        .create_segment(serde_json::json!({ "name": "Gmail", "email_domain": "gmail.com" }))
+        .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "segment_id": gmail.to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let newsletter_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body = String::from_utf8(newsletter_request.body).unwrap();
+    assert!(body.contains("ursula@gmail.com"));
+}
+
+#[tokio::test]
+async fn issues_cannot_be_published_to_an_unknown_segment() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "segment_id": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_segments() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_create_segment(&serde_json::json!({ "name": "Gmail", "email_domain": "gmail.com" }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
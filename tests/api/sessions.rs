@@ -0,0 +1,222 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+use uuid::Uuid;
+
+async fn is_logged_in(app: &TestApp, client: &reqwest::Client) -> bool {
+    let response = client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    response.status().as_u16() == 200
+}
+
+async fn session_id_for(app: &TestApp, user_agent: &str) -> Uuid {
+    sqlx::query!(
+        "SELECT session_id FROM user_sessions WHERE user_agent = $1",
+        user_agent
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .session_id
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/sessions", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn sessions_are_listed_with_their_device_and_ip_address() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.log_in_on_another_device(&app.test_user, "Phone <Browser>")
+        .await;
+
+    let html_page = app.get_sessions_html().await;
+
+    assert!(html_page.contains("<tr><td>Unknown device</td><td>127.0.0.1</td>"));
+    assert!(html_page.contains("<tr><td>Phone &lt;Browser&gt;</td><td>127.0.0.1</td>"));
+    assert_eq!(html_page.matches("This session").count(), 1);
+    let phone_session_id = session_id_for(&app, "Phone <Browser>").await;
+    assert!(html_page.contains(&format!(
+        r#"<form action="/admin/sessions/{phone_session_id}/revoke" method="post">"#
+    )));
+}
+
+#[tokio::test]
+async fn other_users_sessions_are_not_listed() {
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    app.log_in_on_another_device(&other_user, "Other Browser")
+        .await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_sessions_html().await;
+
+    assert!(!html_page.contains("Other Browser"));
+}
+
+#[tokio::test]
+async fn revoking_a_session_logs_that_device_out() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let phone = app
+        .log_in_on_another_device(&app.test_user, "Phone Browser")
+        .await;
+    let laptop = app
+        .log_in_on_another_device(&app.test_user, "Laptop Browser")
+        .await;
+    let phone_session_id = session_id_for(&app, "Phone Browser").await;
+
+    let response = app.post_revoke_session(phone_session_id).await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("<p><i>The session has been logged out.</i></p>"));
+    assert!(!html_page.contains("Phone Browser"));
+    assert!(!is_logged_in(&app, &phone).await);
+    assert!(is_logged_in(&app, &laptop).await);
+    assert!(is_logged_in(&app, &app.api_client).await);
+}
+
+#[tokio::test]
+async fn you_cannot_revoke_another_users_session() {
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    let other_device = app
+        .log_in_on_another_device(&other_user, "Other Browser")
+        .await;
+    app.test_user.login(&app).await;
+    let other_session_id = session_id_for(&app, "Other Browser").await;
+
+    let response = app.post_revoke_session(other_session_id).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert!(is_logged_in(&app, &other_device).await);
+}
+
+#[tokio::test]
+async fn logging_out_all_other_sessions_keeps_this_one() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let phone = app
+        .log_in_on_another_device(&app.test_user, "Phone Browser")
+        .await;
+    let laptop = app
+        .log_in_on_another_device(&app.test_user, "Laptop Browser")
+        .await;
+
+    let response = app.post_revoke_other_sessions().await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("<p><i>All your other sessions have been logged out.</i></p>"));
+    assert!(!is_logged_in(&app, &phone).await);
+    assert!(!is_logged_in(&app, &laptop).await);
+    assert!(is_logged_in(&app, &app.api_client).await);
+}
+
+#[tokio::test]
+async fn changing_your_password_logs_out_your_other_sessions() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let phone = app
+        .log_in_on_another_device(&app.test_user, "Phone Browser")
+        .await;
+    let new_password = Uuid::new_v4().to_string();
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    assert!(!is_logged_in(&app, &phone).await);
+    assert!(is_logged_in(&app, &app.api_client).await);
+}
+
+#[tokio::test]
+async fn logging_out_ends_the_session() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_logout().await;
+
+    let sessions = sqlx::query!("SELECT session_id FROM user_sessions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(sessions.is_empty());
+}
+
+#[tokio::test]
+async fn idle_sessions_expire() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    sqlx::query!("UPDATE user_sessions SET last_seen_at = now() - interval '25 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn expired_sessions_are_deleted_when_someone_logs_in() {
+    let app = spawn_app().await;
+    app.log_in_on_another_device(&app.test_user, "Old Browser")
+        .await;
+    sqlx::query!("UPDATE user_sessions SET last_seen_at = now() - interval '25 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    app.test_user.login(&app).await;
+
+    let sessions = sqlx::query!("SELECT user_agent FROM user_sessions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_ne!(sessions[0].user_agent, "Old Browser");
+}
+
+#[tokio::test]
+async fn last_use_is_only_recorded_every_few_minutes() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let set_last_seen = |minutes_ago: i32| {
+        sqlx::query!(
+            "UPDATE user_sessions SET last_seen_at = now() - make_interval(mins => $1) \
+            RETURNING last_seen_at",
+            minutes_ago,
+        )
+        .fetch_one(&app.db_pool)
+    };
+    let last_seen =
+        || sqlx::query!("SELECT last_seen_at FROM user_sessions").fetch_one(&app.db_pool);
+
+    let recently = set_last_seen(1).await.unwrap().last_seen_at;
+    app.get_admin_dashboard().await;
+    assert_eq!(last_seen().await.unwrap().last_seen_at, recently);
+
+    let a_while_ago = set_last_seen(10).await.unwrap().last_seen_at;
+    app.get_admin_dashboard().await;
+    assert!(last_seen().await.unwrap().last_seen_at > a_while_ago);
+}
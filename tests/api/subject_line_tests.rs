@@ -0,0 +1,242 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, when_sending_an_email, TestApp,
+};
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+use zero2prod::subject_line_tests::decide_due_subject_line_tests;
+
+fn newsletter_request_body(metric: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": r#"<p>Newsletter body as HTML. <a href="https://example.com/post">Read more</a></p>"#,
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "track_opens": "true",
+        "subject_variants": "Subject A\r\nSubject B",
+        "ab_test_sample_percent": "20",
+        "ab_test_wait_hours": "4",
+        "ab_test_metric": metric,
+    })
+}
+
+async fn publish_newsletter(app: &TestApp) -> Uuid {
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body("click_rate"))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+}
+
+/// Returns the subject and HTML body of every newsletter sent so far.
+async fn sent_newsletters(app: &TestApp) -> Vec<(String, String)> {
+    let mut newsletters = vec![];
+    for request in app.email_server.received_requests().await.unwrap() {
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let subject = body["personalizations"][0]["subject"].as_str().unwrap();
+        if !subject.starts_with("Subject ") {
+            continue;
+        }
+        let html = body["content"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|v| v["type"] == "text/html")
+            .unwrap()["value"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+        newsletters.push((subject.to_owned(), html));
+    }
+    newsletters
+}
+
+fn find_url(html: &str, prefix: &str) -> String {
+    let start = html.find(prefix).unwrap();
+    let end = start + html[start..].find('"').unwrap();
+    html[start..end].to_owned()
+}
+
+async fn make_test_due(app: &TestApp) {
+    sqlx::query!("UPDATE subject_line_tests SET decide_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn each_variant_goes_to_part_of_the_sample_and_everyone_else_waits() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for _ in 0..10 {
+        create_confirmed_subscriber(&app).await;
+    }
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let mut subjects: Vec<String> = sent_newsletters(&app)
+        .await
+        .into_iter()
+        .map(|(subject, _)| subject)
+        .collect();
+    subjects.sort();
+    assert_eq!(subjects, vec!["Subject A", "Subject B"]);
+    let held = sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_delivery_queue WHERE held"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(held, 8);
+}
+
+#[tokio::test]
+async fn nothing_is_released_before_the_waiting_period_is_over() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for _ in 0..10 {
+        create_confirmed_subscriber(&app).await;
+    }
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    decide_due_subject_line_tests(&app.db_pool).await.unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    assert_eq!(sent_newsletters(&app).await.len(), 2);
+}
+
+#[tokio::test]
+async fn the_winner_is_sent_to_the_rest_of_the_audience() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for _ in 0..10 {
+        create_confirmed_subscriber(&app).await;
+    }
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let issue_id = publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+    let (_, html) = sent_newsletters(&app)
+        .await
+        .into_iter()
+        .find(|(subject, _)| subject == "Subject B")
+        .unwrap();
+    app.api_client
+        .get(find_url(&html, &format!("{}/r/", app.address)))
+        .send()
+        .await
+        .unwrap();
+
+    make_test_due(&app).await;
+    decide_due_subject_line_tests(&app.db_pool).await.unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let subjects: Vec<String> = sent_newsletters(&app)
+        .await
+        .into_iter()
+        .map(|(subject, _)| subject)
+        .collect();
+    assert_eq!(subjects.len(), 10);
+    assert_eq!(subjects.iter().filter(|s| *s == "Subject B").count(), 9);
+    let html_page = app.get_issue_analytics_html(issue_id).await;
+    assert!(html_page.contains("The winner went out to the rest of the audience."));
+    assert!(html_page.contains(
+        "<tr><td>Subject B</td><td>9</td><td>0</td><td>0.0%</td><td>1</td><td>11.1%</td><td>Winner</td></tr>"
+    ));
+    assert!(html_page.contains(
+        "<tr><td>Subject A</td><td>1</td><td>0</td><td>0.0%</td><td>0</td><td>0.0%</td><td></td></tr>"
+    ));
+}
+
+#[tokio::test]
+async fn the_issue_page_shows_when_the_winner_will_be_picked() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+
+    let issue_id = publish_newsletter(&app).await;
+
+    let html_page = app.get_issue_analytics_html(issue_id).await;
+    assert!(html_page.contains("<h2>Subject line test</h2>"));
+    assert!(html_page.contains("Sent to 20% of the audience, winner picked by click rate."));
+    assert!(html_page.contains("The winner will be picked at "));
+    assert!(html_page.contains("<tr><td>Subject A</td><td>0</td>"));
+    assert!(html_page.contains("<tr><td>Subject B</td><td>0</td>"));
+}
+
+#[tokio::test]
+async fn invalid_subject_line_tests_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let mut only_one_variant = newsletter_request_body("click_rate");
+    only_one_variant["subject_variants"] = "Subject A".into();
+    let mut sample_too_large = newsletter_request_body("click_rate");
+    sample_too_large["ab_test_sample_percent"] = "150".into();
+    let mut opens_not_tracked = newsletter_request_body("open_rate");
+    opens_not_tracked
+        .as_object_mut()
+        .unwrap()
+        .remove("track_opens");
+    let test_cases = vec![
+        (only_one_variant, "a single variant"),
+        (sample_too_large, "a sample above 100%"),
+        (opens_not_tracked, "open rate without open tracking"),
+        (newsletter_request_body("revenue"), "an unknown metric"),
+    ];
+
+    for (body, description) in test_cases {
+        let response = app.post_publish_newsletter(&body).await;
+
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not fail with 400 Bad Request when the test had {description}."
+        );
+    }
+    let n_issues = sqlx::query!(r#"SELECT count(*) AS "n!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_issues, 0);
+}
+
+#[tokio::test]
+async fn issues_without_variants_use_the_title_as_subject() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let mut body = newsletter_request_body("click_rate");
+    body["subject_variants"] = " \r\n".into();
+
+    let response = app.post_publish_newsletter(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let n_tests = sqlx::query!(r#"SELECT count(*) AS "n!" FROM subject_line_tests"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_tests, 0);
+}
@@ -0,0 +1,252 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, when_sending_an_email, TestApp,
+};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::SubscriptionStatus;
+
+async fn saved_statuses(app: &TestApp) -> Vec<(String, SubscriptionStatus)> {
+    sqlx::query!(
+        r#"SELECT email, status AS "status: SubscriptionStatus" FROM subscriptions ORDER BY email"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscriptions.")
+    .into_iter()
+    .map(|r| (r.email, r.status))
+    .collect()
+}
+
+async fn list_membership_count(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT count(*) AS "count!" FROM list_subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_import_subscribers("email,name\nursula@example.com,Ursula\n", &[])
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert!(saved_statuses(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn imported_subscribers_receive_a_confirmation_email() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_import_subscribers(
+            "email,name\nursula@example.com,Ursula\nle.guin@example.com,Le Guin\n",
+            &[("mode", "send_confirmation")],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<p>Imported: 2</p>"));
+    assert!(html_page.contains("<p>Confirmation emails queued: 2</p>"));
+    // The emails are sent by the background worker, not while handling the upload.
+    assert!(app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert_eq!(
+        saved_statuses(&app).await,
+        vec![
+            (
+                "le.guin@example.com".to_string(),
+                SubscriptionStatus::PendingConfirmation
+            ),
+            (
+                "ursula@example.com".to_string(),
+                SubscriptionStatus::PendingConfirmation
+            ),
+        ]
+    );
+
+    app.send_all_queued_confirmation_emails().await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn pending_imports_join_the_list_only_once_confirmed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_import_subscribers(
+        "email,name\nursula@example.com,Ursula\n",
+        &[("mode", "send_confirmation")],
+    )
+    .await;
+    assert_eq!(list_membership_count(&app).await, 0);
+
+    app.send_all_queued_confirmation_emails().await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    assert_eq!(list_membership_count(&app).await, 1);
+}
+
+#[tokio::test]
+async fn queued_confirmation_emails_are_skipped_for_subscribers_no_longer_pending() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    app.post_import_subscribers(
+        "email,name\nursula@example.com,Ursula\n",
+        &[("mode", "send_confirmation")],
+    )
+    .await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    app.send_all_queued_confirmation_emails().await;
+
+    let queued = sqlx::query!("SELECT subscription_token FROM confirmation_email_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(queued.is_empty());
+}
+
+#[tokio::test]
+async fn subscribers_can_be_imported_as_confirmed_with_an_attestation() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_import_subscribers(
+            "email,name\nursula@example.com,Ursula\n",
+            &[
+                ("mode", "confirmed"),
+                (
+                    "attestation",
+                    "Double opt-in export from our previous provider.",
+                ),
+            ],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        saved_statuses(&app).await,
+        vec![(
+            "ursula@example.com".to_string(),
+            SubscriptionStatus::Confirmed
+        )]
+    );
+    let import = sqlx::query!(
+        "SELECT imported_by, attestation FROM subscriber_imports \
+        JOIN subscriptions ON subscriptions.import_id = subscriber_imports.import_id"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch the subscriber import.");
+    assert_eq!(import.imported_by, app.test_user.username);
+    assert_eq!(
+        import.attestation.as_deref(),
+        Some("Double opt-in export from our previous provider.")
+    );
+}
+
+#[tokio::test]
+async fn importing_as_confirmed_requires_an_attestation() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_import_subscribers(
+            "email,name\nursula@example.com,Ursula\n",
+            &[("mode", "confirmed"), ("attestation", "  ")],
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscribers/import");
+
+    let html_page = app.get_import_subscribers_html().await;
+    assert!(html_page.contains(
+        "<p><i>Importing subscribers as confirmed requires an attestation of their consent.</i></p>"
+    ));
+    assert!(saved_statuses(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn invalid_rows_are_reported_and_existing_subscribers_are_skipped() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (existing_email, _) = saved_statuses(&app).await.pop().unwrap();
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let csv = format!(
+        "email,name\n\
+        {existing_email},Someone else\n\
+        <script>@example.com,Mallory\n\
+        ursula@example.com,Ursula\n\
+        ursula@example.com,Ursula\n"
+    );
+    let response = app
+        .post_import_subscribers(&csv, &[("mode", "send_confirmation")])
+        .await;
+    let html_page = response.text().await.unwrap();
+
+    assert!(html_page.contains("<p>Imported: 1</p>"));
+    assert!(html_page.contains("<p>Already subscribed: 2</p>"));
+    assert!(html_page.contains("<p>Rejected: 1</p>"));
+    assert!(html_page.contains("<li>Line 3: &lt;script&gt;@example.com is not a valid"));
+    assert_eq!(saved_statuses(&app).await.len(), 2);
+}
+
+#[tokio::test]
+async fn a_file_without_the_expected_columns_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_import_subscribers("address\nursula@example.com\n", &[])
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscribers/import");
+
+    let html_page = app.get_import_subscribers_html().await;
+    assert!(html_page.contains("The CSV file must have an email and a name column."));
+}
@@ -0,0 +1,379 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    when_sending_an_email, TestApp,
+};
+use chrono::Utc;
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+use zero2prod::domain::SubscriptionStatus;
+
+async fn subscriber(app: &TestApp) -> (Uuid, String) {
+    let row = sqlx::query!("SELECT id, email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    (row.id, row.email)
+}
+
+async fn insert_subscriber(app: &TestApp, email: &str, status: SubscriptionStatus) {
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status) \
+        VALUES ($1, $2, 'Test subscriber', $3, $4)",
+        Uuid::new_v4(),
+        email,
+        Utc::now(),
+        status as SubscriptionStatus,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.get_subscribers("").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_delete_a_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (subscriber_id, _) = subscriber(&app).await;
+
+    let response = app.post_subscriber_action(subscriber_id, "delete").await;
+
+    assert_is_redirect_to(&response, "/login");
+    subscriber(&app).await;
+}
+
+#[tokio::test]
+async fn subscribers_can_be_searched_by_email_or_name() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(&app, "ursula@example.com", SubscriptionStatus::Confirmed).await;
+    insert_subscriber(&app, "le_guin@example.com", SubscriptionStatus::Confirmed).await;
+
+    let html_page = app.get_subscribers_html("search=URSULA").await;
+    assert!(html_page.contains("ursula@example.com"));
+    assert!(!html_page.contains("le_guin@example.com"));
+
+    // Wildcards are matched literally.
+    let html_page = app.get_subscribers_html("search=e_g").await;
+    assert!(html_page.contains("le_guin@example.com"));
+    assert!(!html_page.contains("ursula@example.com"));
+
+    let html_page = app.get_subscribers_html("search=test+subscriber").await;
+    assert!(html_page.contains("ursula@example.com"));
+    assert!(html_page.contains("le_guin@example.com"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_status() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(&app, "confirmed@example.com", SubscriptionStatus::Confirmed).await;
+    insert_subscriber(&app, "bounced@example.com", SubscriptionStatus::Bounced).await;
+
+    let html_page = app.get_subscribers_html("status=bounced").await;
+
+    assert!(html_page.contains("bounced@example.com"));
+    assert!(!html_page.contains("confirmed@example.com"));
+}
+
+#[tokio::test]
+async fn an_unknown_status_filter_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_subscribers("status=vip").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn subscribers_are_paginated() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for i in 0..51 {
+        insert_subscriber(
+            &app,
+            &format!("subscriber{i}@example.com"),
+            SubscriptionStatus::Confirmed,
+        )
+        .await;
+    }
+
+    let html_page = app.get_subscribers_html("status=confirmed").await;
+    assert_eq!(html_page.matches("@example.com").count(), 50);
+    assert!(html_page
+        .contains(r#"<a href="/admin/subscribers?status=confirmed&amp;page=2">Next page</a>"#));
+
+    let html_page = app.get_subscribers_html("status=confirmed&page=2").await;
+    assert_eq!(html_page.matches("@example.com").count(), 1);
+    assert!(!html_page.contains("Next page"));
+    assert!(html_page.contains("Previous page"));
+}
+
+#[tokio::test]
+async fn subscriber_details_show_history_and_deliveries() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (subscriber_id, email) = subscriber(&app).await;
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_subscriber_details_html(subscriber_id).await;
+
+    assert!(html_page.contains(&email));
+    assert!(html_page.contains("<li>Newsletter (since"));
+    assert!(html_page.contains("<td>pending_confirmation</td>"));
+    assert!(html_page.contains("<td>confirmed</td>"));
+    assert!(html_page.contains("<td>Newsletter title</td><td>sent</td>"));
+}
+
+#[tokio::test]
+async fn issue_titles_are_escaped_in_subscriber_details() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (subscriber_id, _) = subscriber(&app).await;
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "<script>alert('title')</script>",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_subscriber_details_html(subscriber_id).await;
+
+    assert!(!html_page.contains("<script>"));
+    assert!(html_page.contains("<td>&lt;script&gt;alert('title')&lt;/script&gt;</td><td>sent</td>"));
+}
+
+#[tokio::test]
+async fn subscriber_emails_and_names_are_escaped() {
+    let app = spawn_app().await;
+    let subscriber_id = Uuid::new_v4();
+    // The validators reject these characters, but the page must not rely on them.
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, '<i>x</i>@example.com', '<b>Ursula</b>', now(), 'confirmed')
+        "#,
+        subscriber_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    for html_page in [
+        app.get_subscribers_html("").await,
+        app.get_subscriber_details_html(subscriber_id).await,
+    ] {
+        assert!(!html_page.contains("<b>Ursula</b>"));
+        assert!(!html_page.contains("<i>x</i>"));
+        assert!(html_page.contains("&lt;b&gt;Ursula&lt;/b&gt;"));
+        assert!(html_page.contains("&lt;i&gt;x&lt;/i&gt;@example.com"));
+    }
+}
+
+#[tokio::test]
+async fn details_of_an_unknown_subscriber_are_a_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_subscriber_details(Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn admins_can_confirm_a_pending_subscriber() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    let (subscriber_id, _) = subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_subscriber_action(subscriber_id, "confirm").await;
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{subscriber_id}"));
+
+    let html_page = app.get_subscriber_details_html(subscriber_id).await;
+    assert!(html_page.contains("<p><i>The subscriber has been confirmed.</i></p>"));
+    assert!(html_page.contains("<p>Status: confirmed</p>"));
+}
+
+#[tokio::test]
+async fn admins_can_unsubscribe_a_confirmed_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (subscriber_id, _) = subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_subscriber_action(subscriber_id, "unsubscribe")
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{subscriber_id}"));
+
+    let html_page = app.get_subscriber_details_html(subscriber_id).await;
+    assert!(html_page.contains("<p><i>The subscriber has been unsubscribed.</i></p>"));
+    assert!(html_page.contains("<p>Status: unsubscribed</p>"));
+    assert!(html_page.contains("<td>unsubscribed</td>"));
+}
+
+#[tokio::test]
+async fn admins_cannot_confirm_an_unsubscribed_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (subscriber_id, _) = subscriber(&app).await;
+    app.test_user.login(&app).await;
+    app.post_subscriber_action(subscriber_id, "unsubscribe")
+        .await;
+
+    let response = app.post_subscriber_action(subscriber_id, "confirm").await;
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{subscriber_id}"));
+
+    let html_page = app.get_subscriber_details_html(subscriber_id).await;
+    assert!(html_page.contains("A subscription cannot go from unsubscribed to confirmed."));
+    assert!(html_page.contains("<p>Status: unsubscribed</p>"));
+}
+
+#[tokio::test]
+async fn admins_can_delete_a_subscriber() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    let (subscriber_id, email) = subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_subscriber_action(subscriber_id, "delete").await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+
+    let html_page = app.get_subscribers_html("").await;
+    assert!(html_page.contains("<p><i>The subscriber has been deleted.</i></p>"));
+    assert!(!html_page.contains(&email));
+    let n_subscribers = sqlx::query!("SELECT count(*) AS \"n!\" FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, 0);
+    let erasure = sqlx::query!("SELECT subscriber_id, requested_by FROM subscriber_erasures")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The erasure was not recorded.");
+    assert_eq!(erasure.subscriber_id, subscriber_id);
+    assert_eq!(erasure.requested_by, app.test_user.username);
+}
+
+#[tokio::test]
+async fn deleting_an_unknown_subscriber_is_a_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_subscriber_action(Uuid::new_v4(), "delete").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.get_subscribers_export("format=csv").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn subscribers_can_be_exported_as_csv() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (subscriber_id, email) = subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_subscribers_export("format=csv").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "text/csv");
+    assert_eq!(
+        response.headers()["Content-Disposition"],
+        "attachment; filename=\"subscribers.csv\""
+    );
+    let body = response.text().await.unwrap();
+
+    let mut lines = body.lines();
+    assert_eq!(
+        lines.next(),
+        Some("id,email,name,status,subscribed_at,confirmed_at")
+    );
+    let row: Vec<&str> = lines.next().unwrap().split(',').collect();
+    assert_eq!(row[0], subscriber_id.to_string());
+    assert_eq!(row[1], email);
+    assert_eq!(row[3], "confirmed");
+    assert!(!row[5].is_empty());
+    assert_eq!(lines.next(), None);
+}
+
+#[tokio::test]
+async fn subscribers_can_be_exported_as_json_filtered_by_status() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(&app, "confirmed@example.com", SubscriptionStatus::Confirmed).await;
+    insert_subscriber(&app, "bounced@example.com", SubscriptionStatus::Bounced).await;
+
+    let response = app
+        .get_subscribers_export("format=json&status=bounced")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "application/json");
+    let body: serde_json::Value = response.json().await.unwrap();
+
+    let rows = body.as_array().unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["email"], "bounced@example.com");
+    assert_eq!(rows[0]["status"], "bounced");
+    assert_eq!(rows[0]["confirmed_at"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn an_empty_export_is_still_valid_json() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_subscribers_export("format=json").await;
+    let body: serde_json::Value = response.json().await.unwrap();
+
+    assert_eq!(body, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn an_unknown_export_format_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_subscribers_export("format=xml").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -1,5 +1,6 @@
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::SubscriptionStatus;
 
 use crate::helpers::spawn_app;
 
@@ -32,14 +33,16 @@ async fn subscribe_persists_the_new_subscriber() {
 
     app.post_subscriptions(body.into()).await;
 
-    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
-        .fetch_one(&app.db_pool)
-        .await
-        .expect("Failed to fetch saved subscription.");
+    let saved = sqlx::query!(
+        r#"SELECT email, name, status AS "status: SubscriptionStatus" FROM subscriptions"#,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscription.");
 
     assert_eq!(saved.email, "ursula_le_guin@gmail.com");
     assert_eq!(saved.name, "le guin");
-    assert_eq!(saved.status, "pending_confirmation");
+    assert_eq!(saved.status, SubscriptionStatus::PendingConfirmation);
 }
 
 #[tokio::test]
@@ -135,3 +138,150 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
 
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn subscribe_returns_a_400_for_an_unknown_list() {
+    let app = spawn_app().await;
+    let body = format!(
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&list_id={}",
+        uuid::Uuid::new_v4()
+    );
+
+    let response = app.post_subscriptions(body).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+async fn list_memberships(app: &crate::helpers::TestApp) -> Vec<uuid::Uuid> {
+    sqlx::query!("SELECT list_id FROM list_subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch list memberships.")
+        .into_iter()
+        .map(|m| m.list_id)
+        .collect()
+}
+
+#[tokio::test]
+async fn a_new_subscriber_joins_the_list_only_once_confirmed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = app.create_list("Weekly digest").await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(format!(
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&list_id={list_id}"
+    ))
+    .await;
+    assert!(list_memberships(&app).await.is_empty());
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(list_memberships(&app).await, [list_id]);
+}
+
+#[tokio::test]
+async fn a_confirmed_subscriber_must_confirm_joining_another_list() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = app.create_list("Weekly digest").await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = app
+        .post_subscriptions(format!("{body}&list_id={list_id}"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(list_memberships(&app).await.len(), 1);
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let memberships = list_memberships(&app).await;
+    assert_eq!(memberships.len(), 2);
+    assert!(memberships.contains(&list_id));
+}
+
+#[tokio::test]
+async fn subscribing_a_confirmed_subscriber_to_a_list_they_are_on_sends_nothing() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = app.post_subscriptions(body.into()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn unsubscribed_addresses_are_not_added_to_lists() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = app.create_list("Weekly digest").await;
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, 'ursula_le_guin@gmail.com', 'le guin', now(), $2)",
+        uuid::Uuid::new_v4(),
+        SubscriptionStatus::Unsubscribed as SubscriptionStatus,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions(format!(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&list_id={list_id}"
+        ))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(list_memberships(&app).await.is_empty());
+}
@@ -1,5 +1,6 @@
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::SubscriptionStatus;
 
 use crate::helpers::spawn_app;
 
@@ -47,7 +48,7 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
 
     app.post_subscriptions(body.into()).await;
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
     reqwest::get(confirmation_links.html)
         .await
@@ -55,12 +56,87 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
         .error_for_status()
         .unwrap();
 
-    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
-        .fetch_one(&app.db_pool)
-        .await
-        .expect("Failed to fetch saved subscription.");
+    let saved = sqlx::query!(
+        r#"SELECT email, name, status AS "status: SubscriptionStatus" FROM subscriptions"#,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscription.");
 
     assert_eq!(saved.email, "ursula_le_guin@gmail.com");
     assert_eq!(saved.name, "le guin");
-    assert_eq!(saved.status, "confirmed");
+    assert_eq!(saved.status, SubscriptionStatus::Confirmed);
+}
+
+#[tokio::test]
+async fn clicking_on_the_confirmation_link_twice_is_harmless() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    for _ in 0..2 {
+        let response = reqwest::get(confirmation_links.html.clone()).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[tokio::test]
+async fn an_unsubscribed_subscriber_cannot_be_confirmed_again() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    sqlx::query!(
+        // language=SQL
+        "UPDATE subscriptions SET status = 'unsubscribed'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 409);
+    let saved =
+        sqlx::query!(r#"SELECT status AS "status: SubscriptionStatus" FROM subscriptions"#,)
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, SubscriptionStatus::Unsubscribed);
+}
+
+#[tokio::test]
+async fn confirmations_with_an_unknown_token_are_rejected_with_a_401() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(&format!(
+        "{}/subscriptions/confirm?subscription_token=unknown",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
 }
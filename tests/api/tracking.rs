@@ -0,0 +1,255 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, when_sending_an_email, TestApp,
+};
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD};
+use wiremock::ResponseTemplate;
+
+async fn publish_and_deliver(app: &TestApp, title: &str, track_opens: bool) -> String {
+    let _mock_guard = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let mut newsletter_request_body = serde_json::json!({
+        "title": title,
+        "text_content": "Newsletter body as plain text",
+        "html_content": r#"<p>Newsletter body as HTML. <a href="https://example.com/post?id=1&amp;ref=mail">Read more</a></p>"#,
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    if track_opens {
+        newsletter_request_body["track_opens"] = "true".into();
+    }
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    body["content"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|v| v["type"] == "text/html")
+        .unwrap()["value"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+fn find_url(html: &str, prefix: &str) -> Option<String> {
+    let start = html.find(prefix)?;
+    let end = start + html[start..].find('"')?;
+    Some(html[start..end].to_owned())
+}
+
+fn tracking_pixel_url(app: &TestApp, html: &str) -> Option<String> {
+    find_url(html, &format!("{}/o/", app.address))
+}
+
+fn tracked_link_url(app: &TestApp, html: &str) -> Option<String> {
+    find_url(html, &format!("{}/r/", app.address))
+}
+
+async fn get_open_rates_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/newsletters/opens", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn tracked_issues_embed_a_tracking_pixel() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+
+    let html = publish_and_deliver(&app, "Newsletter title", true).await;
+
+    let pixel_url = tracking_pixel_url(&app, &html).expect("No tracking pixel in the email.");
+    let response = reqwest::get(pixel_url).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "image/gif");
+    assert!(response.headers()["Cache-Control"]
+        .to_str()
+        .unwrap()
+        .contains("no-store"));
+}
+
+#[tokio::test]
+async fn untracked_issues_do_not_embed_a_tracking_pixel() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+
+    let html = publish_and_deliver(&app, "Newsletter title", false).await;
+
+    assert_eq!(tracking_pixel_url(&app, &html), None);
+}
+
+#[tokio::test]
+async fn first_and_subsequent_opens_are_recorded() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let html = publish_and_deliver(&app, "Newsletter title", true).await;
+    let pixel_url = tracking_pixel_url(&app, &html).unwrap();
+
+    for _ in 0..3 {
+        reqwest::get(&pixel_url)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    let open = sqlx::query!("SELECT first_opened_at, last_opened_at, open_count FROM issue_opens")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The open was not recorded.");
+    assert_eq!(open.open_count, 3);
+    assert!(open.first_opened_at < open.last_opened_at);
+}
+
+#[tokio::test]
+async fn forged_tracking_tokens_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let html = publish_and_deliver(&app, "Newsletter title", true).await;
+    let pixel_url = tracking_pixel_url(&app, &html).unwrap();
+    let forged_url = format!("{}AAAA", &pixel_url[..pixel_url.len() - 4]);
+
+    let response = reqwest::get(forged_url).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    let n_opens = sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_opens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_opens, 0);
+}
+
+#[tokio::test]
+async fn the_open_rate_report_shows_opens_per_issue() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    publish_and_deliver(&app, "Untracked issue", false).await;
+    let html = publish_and_deliver(&app, "Tracked issue", true).await;
+    let pixel_url = tracking_pixel_url(&app, &html).unwrap();
+    reqwest::get(&pixel_url).await.unwrap();
+    reqwest::get(&pixel_url).await.unwrap();
+
+    let html_page = get_open_rates_html(&app).await;
+
+    assert!(html_page.contains(">Untracked issue</a></td>"));
+    assert!(html_page.contains("<td>not tracked</td>"));
+    assert!(html_page.contains("<td>1</td><td>1</td><td>2</td><td>100.0%</td>"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_open_rate_report() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/newsletters/opens", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn links_are_rewritten_to_tracked_redirects() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+
+    let html = publish_and_deliver(&app, "Newsletter title", false).await;
+
+    assert!(!html.contains("https://example.com/post"));
+    assert!(tracked_link_url(&app, &html).is_some());
+    // Subscribers can still manage their subscription without going through the redirector.
+    assert!(html.contains(&format!(r#"href="{}/preferences?token="#, app.address)));
+}
+
+#[tokio::test]
+async fn clicking_a_tracked_link_redirects_to_the_original_url() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let html = publish_and_deliver(&app, "Newsletter title", false).await;
+    let link_url = tracked_link_url(&app, &html).unwrap();
+
+    let response = app.api_client.get(&link_url).send().await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 302);
+    assert_eq!(
+        response.headers()["Location"],
+        "https://example.com/post?id=1&ref=mail"
+    );
+}
+
+#[tokio::test]
+async fn each_click_is_recorded() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let html = publish_and_deliver(&app, "Newsletter title", false).await;
+    let link_url = tracked_link_url(&app, &html).unwrap();
+
+    app.api_client.get(&link_url).send().await.unwrap();
+    app.api_client.get(&link_url).send().await.unwrap();
+
+    let clicks = sqlx::query!("SELECT url, subscriber_id FROM issue_clicks")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(clicks.len(), 2);
+    assert_eq!(clicks[0].url, "https://example.com/post?id=1&ref=mail");
+    assert!(clicks[0].subscriber_id.is_some());
+}
+
+#[tokio::test]
+async fn the_redirector_cannot_be_used_as_an_open_redirect() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let html = publish_and_deliver(&app, "Newsletter title", false).await;
+    let link_url = tracked_link_url(&app, &html).unwrap();
+    // Swap the signed target for one of our choosing, keeping the rest of the token.
+    let token = link_url.rsplit('/').next().unwrap();
+    let mut payload = BASE64_URL_SAFE_NO_PAD.decode(token).unwrap();
+    let tag = payload.split_off(payload.len() - 16);
+    payload.truncate(32);
+    payload.extend_from_slice(b"https://evil.example.com");
+    payload.extend_from_slice(&tag);
+    let forged_url = format!(
+        "{}/r/{}",
+        app.address,
+        BASE64_URL_SAFE_NO_PAD.encode(payload)
+    );
+
+    let response = app.api_client.get(&forged_url).send().await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    let n_clicks = sqlx::query!(r#"SELECT count(*) AS "n!" FROM issue_clicks"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_clicks, 0);
+}
@@ -0,0 +1,312 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+use zero2prod::authentication::{current_totp_step, totp_code, Role};
+
+struct Enrolment {
+    secret: String,
+    recovery_codes: Vec<String>,
+}
+
+fn extract_between<'a>(html: &'a str, start: &str, end: &str) -> &'a str {
+    let from = html.find(start).expect("Start marker not found") + start.len();
+    let to = from + html[from..].find(end).expect("End marker not found");
+    &html[from..to]
+}
+
+/// Enrols the logged-in user, confirming with the code for the current time step.
+async fn enrol(app: &TestApp) -> Enrolment {
+    let html_page = app.get_two_factor_html().await;
+    let secret = extract_between(&html_page, "<code>", "</code>").to_string();
+
+    let code = totp_code(&secret, current_totp_step()).unwrap();
+    let response = app.post_enrol_two_factor(&code).await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("Two-factor authentication is on."));
+    let recovery_codes = extract_between(&html_page, "without your app: ", "</i>")
+        .split(' ')
+        .map(ToString::to_string)
+        .collect();
+    Enrolment {
+        secret,
+        recovery_codes,
+    }
+}
+
+/// A code the server has not seen yet: enrolment used the current step.
+fn next_code(secret: &str) -> String {
+    totp_code(secret, current_totp_step() + 1).unwrap()
+}
+
+async fn log_in_with_password(app: &TestApp, user: &TestUser) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "username": &user.username,
+        "password": &user.password,
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn enrolment_shows_a_qr_code_and_the_secret() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_two_factor_html().await;
+
+    assert!(html_page.contains("<svg"));
+    let secret = extract_between(&html_page, "<code>", "</code>");
+    assert_eq!(secret.len(), 32);
+    // Reloading the page keeps the same secret, in case it was already scanned.
+    assert!(app.get_two_factor_html().await.contains(secret));
+}
+
+#[tokio::test]
+async fn enrolment_needs_a_valid_code() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.get_two_factor_html().await;
+
+    let response = app.post_enrol_two_factor("000000x").await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("<p><i>The code is not valid.</i></p>"));
+    app.post_logout().await;
+    let response = log_in_with_password(&app, &app.test_user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn enrolment_hands_out_recovery_codes_and_stores_only_their_hashes() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let enrolment = enrol(&app).await;
+
+    assert_eq!(enrolment.recovery_codes.len(), 10);
+    let stored = sqlx::query!("SELECT code_hash FROM two_factor_recovery_codes")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 10);
+    for row in stored {
+        assert!(!enrolment.recovery_codes.contains(&row.code_hash));
+    }
+}
+
+#[tokio::test]
+async fn enrolled_users_need_a_code_after_their_password() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let enrolment = enrol(&app).await;
+    app.post_logout().await;
+
+    let response = log_in_with_password(&app, &app.test_user).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    // Half-authenticated users are not logged in yet.
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+
+    let response = app
+        .post_login_two_factor(&next_code(&enrolment.secret))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn wrong_codes_are_rejected_at_login() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    enrol(&app).await;
+    app.post_logout().await;
+    log_in_with_password(&app, &app.test_user).await;
+
+    let response = app.post_login_two_factor("123456x").await;
+
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let html_page = app
+        .api_client
+        .get(format!("{}/login/two-factor", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>The code is not valid.</i></p>"));
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn wrong_codes_count_as_failed_logins() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let enrolment = enrol(&app).await;
+    app.post_logout().await;
+    log_in_with_password(&app, &app.test_user).await;
+    for _ in 0..3 {
+        app.post_login_two_factor("123456x").await;
+    }
+
+    let response = app
+        .post_login_two_factor(&next_code(&enrolment.secret))
+        .await;
+
+    assert_is_redirect_to(&response, "/login/two-factor");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn too_many_wrong_codes_end_the_pending_login() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let enrolment = enrol(&app).await;
+    app.post_logout().await;
+    log_in_with_password(&app, &app.test_user).await;
+    for _ in 0..4 {
+        app.post_login_two_factor("123456x").await;
+        // Keep the login throttle out of the way: this is about the pending login alone.
+        sqlx::query!("DELETE FROM login_failures")
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+    }
+
+    let response = app.post_login_two_factor("123456x").await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Too many wrong codes - please log in again.</i></p>"));
+
+    sqlx::query!("DELETE FROM login_failures")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app
+        .post_login_two_factor(&next_code(&enrolment.secret))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn a_code_cannot_be_used_twice() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let enrolment = enrol(&app).await;
+    let code = next_code(&enrolment.secret);
+    app.post_logout().await;
+    log_in_with_password(&app, &app.test_user).await;
+    app.post_login_two_factor(&code).await;
+    app.post_logout().await;
+    log_in_with_password(&app, &app.test_user).await;
+
+    let response = app.post_login_two_factor(&code).await;
+
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn each_recovery_code_works_once() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let enrolment = enrol(&app).await;
+    let recovery_code = &enrolment.recovery_codes[0];
+    app.post_logout().await;
+
+    log_in_with_password(&app, &app.test_user).await;
+    let response = app.post_login_two_factor(recovery_code).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    log_in_with_password(&app, &app.test_user).await;
+    let response = app.post_login_two_factor(recovery_code).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn the_second_step_needs_a_correct_password_first() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/login/two-factor", app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+    let response = app.post_login_two_factor("123456").await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn users_can_turn_two_factor_off_with_a_code() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let enrolment = enrol(&app).await;
+
+    let response = app
+        .post_disable_two_factor(&next_code(&enrolment.secret))
+        .await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("<p><i>Two-factor authentication is off.</i></p>"));
+    app.post_logout().await;
+    let response = log_in_with_password(&app, &app.test_user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn owners_can_require_two_factor_for_everybody() {
+    let app = spawn_app().await;
+    let editor = TestUser::with_role(Role::Editor);
+    editor.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_require_two_factor(true).await;
+    assert_is_redirect_to(&response, "/admin/users");
+    app.post_logout().await;
+    editor.login(&app).await;
+
+    // Users without two-factor authentication can only set it up.
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/admin/two-factor");
+    let html_page = app.get_two_factor_html().await;
+    assert!(
+        html_page.contains("You must set up two-factor authentication before you can continue.")
+    );
+    let enrolment = enrol(&app).await;
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+
+    let response = app
+        .post_disable_two_factor(&next_code(&enrolment.secret))
+        .await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let html_page = app.get_two_factor_html().await;
+    assert!(
+        html_page.contains("<p><i>Two-factor authentication is required for all users.</i></p>")
+    );
+}
+
+#[tokio::test]
+async fn only_owners_can_require_two_factor() {
+    let app = spawn_app().await;
+    let editor = TestUser::with_role(Role::Editor);
+    editor.store(&app.db_pool).await;
+    editor.login(&app).await;
+
+    let response = app.post_require_two_factor(true).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn the_users_page_shows_who_uses_two_factor() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    enrol(&app).await;
+
+    let html_page = app.get_users_html().await;
+
+    assert!(html_page.contains("<td>on</td>"));
+    assert!(html_page.contains("<td>off</td>"));
+}
@@ -0,0 +1,242 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+use uuid::Uuid;
+use zero2prod::authentication::Role;
+
+/// Logs the test user out and logs `user` in instead.
+async fn switch_to(app: &TestApp, user: &TestUser) {
+    app.post_logout().await;
+    user.login(app).await;
+}
+
+async fn user_with_role(app: &TestApp, role: Role) -> TestUser {
+    let user = TestUser::with_role(role);
+    user.store(&app.db_pool).await;
+    user
+}
+
+async fn role_of(app: &TestApp, user_id: Uuid) -> Option<Role> {
+    sqlx::query!(
+        r#"SELECT role AS "role: Role" FROM users WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .map(|r| r.role)
+}
+
+#[tokio::test]
+async fn viewers_can_see_analytics_but_not_publish() {
+    let app = spawn_app().await;
+    let viewer = user_with_role(&app, Role::Viewer).await;
+    viewer.login(&app).await;
+
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+    let response = app
+        .api_client
+        .get(format!("{}/admin/newsletters/opens", app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(app.get_publish_newsletter().await.status().as_u16(), 403);
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    let n_issues = sqlx::query!(r#"SELECT count(*) AS "n!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_issues, 0);
+}
+
+#[tokio::test]
+async fn viewers_cannot_change_the_audience() {
+    let app = spawn_app().await;
+    let viewer = user_with_role(&app, Role::Viewer).await;
+    viewer.login(&app).await;
+
+    assert_eq!(app.get_subscribers("").await.status().as_u16(), 200);
+    let response = app
+        .post_create_list(&serde_json::json!({ "name": "Weekly" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app.get_subscribers_export("").await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn editors_can_publish_but_not_manage_users() {
+    let app = spawn_app().await;
+    let editor = user_with_role(&app, Role::Editor).await;
+    editor.login(&app).await;
+
+    assert_eq!(app.get_publish_newsletter().await.status().as_u16(), 200);
+    assert_eq!(app.get_users().await.status().as_u16(), 403);
+    let response = app.post_change_user_role(editor.user_id, "owner").await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(role_of(&app, editor.user_id).await, Some(Role::Editor));
+}
+
+#[tokio::test]
+async fn only_owners_see_the_link_to_manage_users() {
+    let app = spawn_app().await;
+    let editor = user_with_role(&app, Role::Editor).await;
+    app.test_user.login(&app).await;
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(r#"<a href="/admin/users">Manage users</a>"#));
+
+    switch_to(&app, &editor).await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(!html_page.contains("/admin/users"));
+}
+
+#[tokio::test]
+async fn owners_see_every_user_and_their_role() {
+    let app = spawn_app().await;
+    let editor = user_with_role(&app, Role::Editor).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_users_html().await;
+
+    assert!(html_page.contains(&format!("<td>{} (you)</td>", app.test_user.username)));
+    assert!(html_page.contains(&format!("<td>{}</td>", editor.username)));
+    assert!(html_page.contains(&format!(
+        r#"action="/admin/users/{}/delete""#,
+        editor.user_id
+    )));
+    assert!(!html_page.contains(&format!(
+        r#"action="/admin/users/{}/delete""#,
+        app.test_user.user_id
+    )));
+}
+
+#[tokio::test]
+async fn owners_can_change_roles_and_the_change_takes_effect() {
+    let app = spawn_app().await;
+    let viewer = user_with_role(&app, Role::Viewer).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_change_user_role(viewer.user_id, "editor").await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>The role has been changed.</i></p>"));
+
+    switch_to(&app, &viewer).await;
+    assert_eq!(app.get_publish_newsletter().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn unknown_roles_are_rejected() {
+    let app = spawn_app().await;
+    let viewer = user_with_role(&app, Role::Viewer).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_change_user_role(viewer.user_id, "admin").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(role_of(&app, viewer.user_id).await, Some(Role::Viewer));
+}
+
+#[tokio::test]
+async fn the_last_owner_cannot_be_demoted() {
+    let app = spawn_app().await;
+    // The seed migration creates an owner too.
+    sqlx::query!("UPDATE users SET role = 'viewer' WHERE username = 'admin'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_change_user_role(app.test_user.user_id, "viewer")
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>There must always be at least one owner.</i></p>"));
+    assert_eq!(
+        role_of(&app, app.test_user.user_id).await,
+        Some(Role::Owner)
+    );
+}
+
+#[tokio::test]
+async fn an_owner_can_step_down_when_there_is_another_owner() {
+    let app = spawn_app().await;
+    user_with_role(&app, Role::Owner).await;
+    app.test_user.login(&app).await;
+
+    app.post_change_user_role(app.test_user.user_id, "editor")
+        .await;
+
+    assert_eq!(
+        role_of(&app, app.test_user.user_id).await,
+        Some(Role::Editor)
+    );
+    assert_eq!(app.get_users().await.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn removed_users_are_logged_out() {
+    let app = spawn_app().await;
+    let editor = user_with_role(&app, Role::Editor).await;
+    editor.login(&app).await;
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+    sqlx::query!("DELETE FROM users WHERE user_id = $1", editor.user_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = app.get_admin_dashboard().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn owners_can_remove_other_users() {
+    let app = spawn_app().await;
+    let editor = user_with_role(&app, Role::Editor).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_remove_user(editor.user_id).await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>The user has been removed.</i></p>"));
+    assert!(!html_page.contains(&editor.username));
+    assert_eq!(role_of(&app, editor.user_id).await, None);
+}
+
+#[tokio::test]
+async fn owners_cannot_remove_themselves() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_remove_user(app.test_user.user_id).await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>You cannot remove yourself.</i></p>"));
+    assert_eq!(
+        role_of(&app, app.test_user.user_id).await,
+        Some(Role::Owner)
+    );
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_users() {
+    let app = spawn_app().await;
+
+    let response = app.get_users().await;
+
+    assert_is_redirect_to(&response, "/login");
+}